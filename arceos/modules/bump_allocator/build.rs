@@ -0,0 +1,4 @@
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(kani)");
+    println!("cargo::rustc-check-cfg=cfg(loom)");
+}
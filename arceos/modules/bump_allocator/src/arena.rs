@@ -0,0 +1,70 @@
+//! Disposable workspaces carved from a page run.
+
+use allocator::{AllocError, AllocResult};
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use crate::region::align_up;
+use crate::{LockedEarlyAllocator, PageBox};
+
+/// A chunk reserved for strictly temporary use, such as parsing a device
+/// tree, loading an ELF image or decompressing an initrd.
+///
+/// Blocks are bumped upward and only freed all at once, by
+/// [`reset`](Self::reset) or by dropping the arena, which gives the chunk
+/// back to the allocator. Returned by
+/// [`LockedEarlyAllocator::scratch_arena`].
+pub struct ScratchArena<'a, const PAGE_SIZE: usize> {
+    pages: PageBox<'a, PAGE_SIZE>,
+    pos: usize,
+}
+
+impl<const PAGE_SIZE: usize> ScratchArena<'_, PAGE_SIZE> {
+    /// Allocates a block from the arena.
+    ///
+    /// Fails with [`AllocError::NoMemory`] if the arena is full; it never
+    /// grows.
+    pub fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        let pos = align_up(self.pos, layout.align()).ok_or(AllocError::NoMemory)?;
+        let end = pos
+            .checked_add(layout.size())
+            .filter(|&end| end <= self.pages.addr() + self.capacity())
+            .ok_or(AllocError::NoMemory)?;
+        self.pos = end;
+        NonNull::new(pos as *mut u8).ok_or(AllocError::NoMemory)
+    }
+
+    /// Frees every block at once, keeping the chunk for reuse. Pointers
+    /// handed out before must not be used afterwards.
+    pub fn reset(&mut self) {
+        self.pos = self.pages.addr();
+    }
+
+    /// Size of the chunk in bytes.
+    pub fn capacity(&self) -> usize {
+        self.pages.size()
+    }
+
+    /// Bytes handed out since the last reset, alignment padding included.
+    pub fn used(&self) -> usize {
+        self.pos - self.pages.addr()
+    }
+
+    /// Bytes left in the chunk.
+    pub fn remaining(&self) -> usize {
+        self.capacity() - self.used()
+    }
+}
+
+impl<const PAGE_SIZE: usize> LockedEarlyAllocator<PAGE_SIZE> {
+    /// Reserves a page-aligned chunk of at least `capacity` bytes as a
+    /// [`ScratchArena`], given back when the arena is dropped.
+    pub fn scratch_arena(&self, capacity: usize) -> AllocResult<ScratchArena<'_, PAGE_SIZE>> {
+        let num_pages = capacity.div_ceil(PAGE_SIZE).max(1);
+        let pages = self.alloc_pages_owned(num_pages, PAGE_SIZE)?;
+        Ok(ScratchArena {
+            pos: pages.addr(),
+            pages,
+        })
+    }
+}
@@ -0,0 +1,85 @@
+//! A drop-in byte allocator for `axalloc`.
+
+use allocator::{AllocError, AllocResult, BaseAllocator, ByteAllocator};
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use crate::EarlyAllocator;
+
+/// An [`EarlyAllocator`] in the byte allocator slot of `axalloc`'s
+/// `GlobalAllocator`, which is built with a `const fn new()` and grown on
+/// demand:
+///
+/// ```ignore
+/// pub type DefaultByteAllocator = bump_allocator::AxallocByteAllocator<PAGE_SIZE>;
+/// ```
+///
+/// When an allocation fails with [`AllocError::NoMemory`], `axalloc` takes
+/// at least [`total_bytes`](ByteAllocator::total_bytes) more from its page
+/// allocator, passes them to [`add_memory`](BaseAllocator::add_memory) and
+/// retries. Every failure that more memory could cure is reported as
+/// `NoMemory` for that, and each chunk becomes a region of its own, so up to
+/// [`MAX_REGIONS`](crate::MAX_REGIONS) chunks, doubling in size, can be added.
+pub struct AxallocByteAllocator<const PAGE_SIZE: usize> {
+    inner: EarlyAllocator<PAGE_SIZE>,
+}
+
+impl<const PAGE_SIZE: usize> AxallocByteAllocator<PAGE_SIZE> {
+    /// Creates an empty allocator.
+    pub const fn new() -> Self {
+        Self {
+            inner: EarlyAllocator::new(),
+        }
+    }
+
+    /// Returns the wrapped allocator, e.g. to read its statistics.
+    pub fn inner(&self) -> &EarlyAllocator<PAGE_SIZE> {
+        &self.inner
+    }
+
+    /// Returns the wrapped allocator mutably, e.g. to install hooks.
+    pub fn inner_mut(&mut self) -> &mut EarlyAllocator<PAGE_SIZE> {
+        &mut self.inner
+    }
+}
+
+impl<const PAGE_SIZE: usize> Default for AxallocByteAllocator<PAGE_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const PAGE_SIZE: usize> BaseAllocator for AxallocByteAllocator<PAGE_SIZE> {
+    fn init(&mut self, start: usize, size: usize) {
+        self.inner.init(start, size);
+    }
+
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        self.inner.add_memory(start, size)
+    }
+}
+
+impl<const PAGE_SIZE: usize> ByteAllocator for AxallocByteAllocator<PAGE_SIZE> {
+    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        self.inner.alloc(layout).map_err(|err| match err {
+            AllocError::MemoryOverlap => AllocError::NoMemory,
+            err => err,
+        })
+    }
+
+    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
+        self.inner.dealloc(pos, layout);
+    }
+
+    fn total_bytes(&self) -> usize {
+        self.inner.total_bytes()
+    }
+
+    fn used_bytes(&self) -> usize {
+        self.inner.used_bytes()
+    }
+
+    fn available_bytes(&self) -> usize {
+        self.inner.available_bytes()
+    }
+}
@@ -0,0 +1,108 @@
+//! Bounce buffers for devices that cannot reach every address.
+
+use allocator::{AllocError, AllocResult};
+
+use crate::EarlyAllocator;
+
+/// Maximum number of buffers in a [`BouncePool`].
+pub const BOUNCE_CAPACITY: usize = 16;
+
+/// A bounce buffer handed out by [`BouncePool::grab`], with its mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BounceBuffer {
+    /// Start of the bounce buffer, reachable by the device.
+    pub addr: usize,
+    /// Start of the caller's buffer it stands in for.
+    pub orig: usize,
+    /// Number of bytes mapped.
+    pub len: usize,
+}
+
+/// A fixed set of page-backed buffers below a DMA address limit, for
+/// drivers whose devices cannot reach the buffer the caller already has.
+///
+/// The buffers are taken from the pages area when the pool is created and
+/// are never given back.
+pub struct BouncePool {
+    bufs: [usize; BOUNCE_CAPACITY],
+    mappings: [Option<BounceBuffer>; BOUNCE_CAPACITY],
+    count: usize,
+    buf_size: usize,
+}
+
+impl BouncePool {
+    /// Allocates `count` buffers of `buf_size` bytes (rounded up to whole
+    /// pages) from `alloc`, all reachable under the DMA mask `mask` (see
+    /// [`EarlyAllocator::alloc_dma`]).
+    pub fn new<const PAGE_SIZE: usize>(
+        alloc: &mut EarlyAllocator<PAGE_SIZE>,
+        count: usize,
+        buf_size: usize,
+        mask: u64,
+    ) -> AllocResult<Self> {
+        if count == 0 || count > BOUNCE_CAPACITY || buf_size == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let num_pages = buf_size.div_ceil(PAGE_SIZE);
+        let mut bufs = [0; BOUNCE_CAPACITY];
+        for buf in &mut bufs[..count] {
+            *buf = alloc.alloc_pages_dma(num_pages, mask)?;
+        }
+        Ok(Self {
+            bufs,
+            mappings: [None; BOUNCE_CAPACITY],
+            count,
+            buf_size: num_pages * PAGE_SIZE,
+        })
+    }
+
+    /// Size of each buffer in bytes.
+    pub fn buf_size(&self) -> usize {
+        self.buf_size
+    }
+
+    /// Number of buffers not handed out.
+    pub fn available(&self) -> usize {
+        self.mappings[..self.count]
+            .iter()
+            .filter(|m| m.is_none())
+            .count()
+    }
+
+    /// Grabs a free buffer to stand in for `[orig, orig + len)`. Returns
+    /// `None` if `len` exceeds [`buf_size`](Self::buf_size) or every buffer
+    /// is in use.
+    pub fn grab(&mut self, orig: usize, len: usize) -> Option<BounceBuffer> {
+        if len > self.buf_size {
+            return None;
+        }
+        let idx = self.mappings[..self.count]
+            .iter()
+            .position(|m| m.is_none())?;
+        let buf = BounceBuffer {
+            addr: self.bufs[idx],
+            orig,
+            len,
+        };
+        self.mappings[idx] = Some(buf);
+        Some(buf)
+    }
+
+    /// Returns the mapping of the grabbed buffer starting at `addr`.
+    pub fn lookup(&self, addr: usize) -> Option<BounceBuffer> {
+        self.mappings[..self.count]
+            .iter()
+            .flatten()
+            .find(|m| m.addr == addr)
+            .copied()
+    }
+
+    /// Releases the grabbed buffer starting at `addr` and returns its
+    /// mapping, e.g. to copy the data back to the caller's buffer.
+    pub fn release(&mut self, addr: usize) -> Option<BounceBuffer> {
+        self.mappings[..self.count]
+            .iter_mut()
+            .find(|m| m.is_some_and(|m| m.addr == addr))?
+            .take()
+    }
+}
@@ -0,0 +1,63 @@
+//! Canary words guarding both edges of each free gap, enabled by the
+//! `canary` feature.
+//!
+//! After every allocation a canary is written right above the bytes area
+//! and right below the pages area of the region it came from. All canaries
+//! still lying in a free gap are checked before the next allocation from a
+//! region, so a write running past the last byte block or below the lowest
+//! page run is caught there instead of at some later crash.
+//!
+//! The canaries are written into the free gaps, so every region allocated
+//! from must be backed by writable memory, up to the top of the address
+//! space for a region that reaches it.
+
+use core::mem::size_of;
+
+use crate::EarlyAllocator;
+
+const CANARY: usize = 0x5afe_c0de_5afe_c0de_u64 as usize;
+const WORD: usize = size_of::<usize>();
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Checks every canary still lying in a free gap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one has been overwritten.
+    pub(crate) fn check_canaries(&self) {
+        for idx in self.region_indices() {
+            let region = &self.regions[idx];
+            for pos in self.canaries[idx].into_iter().flatten() {
+                if pos < region.b_pos || pos + WORD > region.p_pos {
+                    continue;
+                }
+                // SAFETY: `pos` lies in the free gap, which nothing but the
+                // allocator may touch.
+                let value = unsafe { (pos as *const usize).read_unaligned() };
+                assert!(
+                    value == CANARY,
+                    "canary at {:#x} in region {} overwritten with {:#x}",
+                    pos,
+                    idx,
+                    value
+                );
+            }
+        }
+    }
+
+    /// Writes fresh canaries at both edges of the free gap of region `idx`,
+    /// if it holds two words.
+    pub(crate) fn place_canaries(&mut self, idx: usize) {
+        let region = &self.regions[idx];
+        if region.available_bytes() < 2 * WORD {
+            self.canaries[idx] = [None; 2];
+            return;
+        }
+        let spots = [region.b_pos, region.p_pos - WORD];
+        for pos in spots {
+            // SAFETY: `pos` lies in the free gap, see `check_canaries`.
+            unsafe { (pos as *mut usize).write_unaligned(CANARY) };
+        }
+        self.canaries[idx] = spots.map(Some);
+    }
+}
@@ -0,0 +1,241 @@
+//! Conformance checks for any allocator implementing both [`ByteAllocator`]
+//! and [`PageAllocator`], enabled by the `conformance` feature.
+//!
+//! They are plain functions that panic on the first violation, so the same
+//! suite validates the early allocator and the other `allocator` backends
+//! from each crate's tests:
+//!
+//! ```ignore
+//! #[test]
+//! fn conformance() {
+//!     bump_allocator::conformance::run_all(|| {
+//!         let mut alloc = MyAllocator::new();
+//!         alloc.init(heap_start(), 64 * 4096);
+//!         alloc
+//!     });
+//! }
+//! ```
+//!
+//! Every check is given a fresh allocator managing at least [`MIN_PAGES`]
+//! pages of real memory, since the blocks handed out are written to.
+
+use allocator::{ByteAllocator, PageAllocator};
+use core::alloc::Layout;
+use core::ops::Range;
+use core::ptr::NonNull;
+
+/// Fewest pages an allocator under test must manage.
+pub const MIN_PAGES: usize = 16;
+
+/// Number of byte blocks kept alive at once by [`check_non_overlap`].
+const BLOCKS: usize = 16;
+
+fn check_size<A: ByteAllocator + PageAllocator>(alloc: &A) {
+    assert!(
+        alloc.total_pages() >= MIN_PAGES,
+        "conformance: {} pages managed, {} needed",
+        alloc.total_pages(),
+        MIN_PAGES
+    );
+}
+
+fn layout(size: usize, align: usize) -> Layout {
+    Layout::from_size_align(size, align).unwrap()
+}
+
+fn block_range(ptr: NonNull<u8>, size: usize) -> Range<usize> {
+    let start = ptr.as_ptr() as usize;
+    start..start + size
+}
+
+fn disjoint(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.end <= b.start || b.end <= a.start
+}
+
+/// Runs every check, each on an allocator returned by `make`.
+pub fn run_all<A, F>(mut make: F)
+where
+    A: ByteAllocator + PageAllocator,
+    F: FnMut() -> A,
+{
+    check_alignment(&mut make());
+    check_non_overlap(&mut make());
+    check_stats(&mut make());
+    check_oom(&mut make());
+}
+
+/// Checks that byte blocks honour every alignment up to the page size, and
+/// page runs alignments of up to four pages.
+pub fn check_alignment<A: ByteAllocator + PageAllocator>(alloc: &mut A) {
+    check_size(alloc);
+    let mut align = 1;
+    while align <= A::PAGE_SIZE {
+        for size in [1, 24, align] {
+            let layout = layout(size, align);
+            let ptr = alloc
+                .alloc(layout)
+                .expect("conformance: byte allocation failed");
+            assert_eq!(
+                ptr.as_ptr() as usize % align,
+                0,
+                "conformance: block of {size} bytes misaligned for {align:#x}"
+            );
+            alloc.dealloc(ptr, layout);
+        }
+        align <<= 1;
+    }
+    for align in [A::PAGE_SIZE, 2 * A::PAGE_SIZE, 4 * A::PAGE_SIZE] {
+        let page = alloc
+            .alloc_pages(1, align)
+            .expect("conformance: page allocation failed");
+        assert_eq!(
+            page % align,
+            0,
+            "conformance: page at {page:#x} misaligned for {align:#x}"
+        );
+        alloc.dealloc_pages(page, 1);
+    }
+}
+
+/// Checks that live byte blocks and page runs never overlap, and that
+/// writing to one leaves the others intact.
+pub fn check_non_overlap<A: ByteAllocator + PageAllocator>(alloc: &mut A) {
+    check_size(alloc);
+    let mut blocks = [None; BLOCKS];
+    for (i, slot) in blocks.iter_mut().enumerate() {
+        let layout = layout(8 << (i % 6), 1 << (i % 7));
+        let ptr = alloc
+            .alloc(layout)
+            .expect("conformance: byte allocation failed");
+        // SAFETY: the block was just allocated with this size.
+        unsafe { ptr.as_ptr().write_bytes(i as u8, layout.size()) };
+        *slot = Some((ptr, layout));
+    }
+    let pages = [1, 2].map(|num_pages| {
+        let pos = alloc
+            .alloc_pages(num_pages, A::PAGE_SIZE)
+            .expect("conformance: page allocation failed");
+        // SAFETY: the run was just allocated with this size.
+        unsafe { (pos as *mut u8).write_bytes(0xa5, num_pages * A::PAGE_SIZE) };
+        (pos, num_pages)
+    });
+
+    let ranges = blocks
+        .iter()
+        .flatten()
+        .map(|&(ptr, layout)| block_range(ptr, layout.size()))
+        .chain(pages.iter().map(|&(pos, n)| pos..pos + n * A::PAGE_SIZE));
+    for (i, a) in ranges.clone().enumerate() {
+        for b in ranges.clone().skip(i + 1) {
+            assert!(disjoint(&a, &b), "conformance: {a:#x?} overlaps {b:#x?}");
+        }
+    }
+    for (i, &(ptr, layout)) in blocks.iter().flatten().enumerate() {
+        // SAFETY: the block is live and was filled above.
+        let bytes = unsafe { core::slice::from_raw_parts(ptr.as_ptr(), layout.size()) };
+        assert!(
+            bytes.iter().all(|&b| b == i as u8),
+            "conformance: block {i} was overwritten"
+        );
+    }
+
+    for &(pos, num_pages) in pages.iter().rev() {
+        alloc.dealloc_pages(pos, num_pages);
+    }
+    for &(ptr, layout) in blocks.iter().flatten().rev() {
+        alloc.dealloc(ptr, layout);
+    }
+}
+
+/// Checks that the statistics follow allocations and frees, and never
+/// account for more than the managed memory.
+pub fn check_stats<A: ByteAllocator + PageAllocator>(alloc: &mut A) {
+    check_size(alloc);
+    let consistent = |alloc: &A| {
+        assert!(
+            alloc.used_bytes() + alloc.available_bytes() <= alloc.total_bytes(),
+            "conformance: more bytes used and available than managed"
+        );
+        assert!(
+            alloc.used_pages() + alloc.available_pages() <= alloc.total_pages(),
+            "conformance: more pages used and available than managed"
+        );
+    };
+    consistent(alloc);
+
+    let used = alloc.used_bytes();
+    let layout = layout(100, 8);
+    let ptr = alloc
+        .alloc(layout)
+        .expect("conformance: byte allocation failed");
+    assert!(
+        alloc.used_bytes() >= used + layout.size(),
+        "conformance: used_bytes did not grow by the block"
+    );
+    consistent(alloc);
+    let grown = alloc.used_bytes();
+    alloc.dealloc(ptr, layout);
+    assert!(
+        alloc.used_bytes() <= grown,
+        "conformance: used_bytes grew on free"
+    );
+    consistent(alloc);
+
+    let used = alloc.used_pages();
+    let pos = alloc
+        .alloc_pages(3, A::PAGE_SIZE)
+        .expect("conformance: page allocation failed");
+    assert_eq!(
+        alloc.used_pages(),
+        used + 3,
+        "conformance: used_pages did not grow by the run"
+    );
+    consistent(alloc);
+    alloc.dealloc_pages(pos, 3);
+    assert_eq!(
+        alloc.used_pages(),
+        used,
+        "conformance: used_pages not restored by the free"
+    );
+    consistent(alloc);
+}
+
+/// Checks that requests beyond the managed memory fail without side
+/// effects, and that memory freed after running out can be allocated again.
+pub fn check_oom<A: ByteAllocator + PageAllocator>(alloc: &mut A) {
+    check_size(alloc);
+    let (used_bytes, used_pages) = (alloc.used_bytes(), alloc.used_pages());
+    assert!(
+        alloc.alloc(layout(alloc.total_bytes() + 1, 1)).is_err(),
+        "conformance: allocated more bytes than managed"
+    );
+    assert!(
+        alloc
+            .alloc_pages(alloc.total_pages() + 1, A::PAGE_SIZE)
+            .is_err(),
+        "conformance: allocated more pages than managed"
+    );
+    assert_eq!(
+        (alloc.used_bytes(), alloc.used_pages()),
+        (used_bytes, used_pages),
+        "conformance: a failed allocation changed the statistics"
+    );
+
+    let mut last = None;
+    for _ in 0..=alloc.total_pages() {
+        match alloc.alloc_pages(1, A::PAGE_SIZE) {
+            Ok(pos) => last = Some(pos),
+            Err(_) => break,
+        }
+    }
+    assert!(
+        alloc.alloc_pages(1, A::PAGE_SIZE).is_err(),
+        "conformance: pages never ran out"
+    );
+    let last = last.expect("conformance: page allocation failed");
+    alloc.dealloc_pages(last, 1);
+    let again = alloc
+        .alloc_pages(1, A::PAGE_SIZE)
+        .expect("conformance: a freed page could not be allocated again");
+    alloc.dealloc_pages(again, 1);
+}
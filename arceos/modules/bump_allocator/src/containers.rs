@@ -0,0 +1,194 @@
+//! Minimal owned containers usable before the global heap exists.
+
+use allocator::{AllocError, AllocResult, ByteAllocator};
+use core::alloc::Layout;
+use core::cell::RefCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use crate::EarlyAllocator;
+
+/// A shared handle to an allocator that containers can allocate from and
+/// give their memory back to.
+pub trait AllocHandle {
+    /// Allocates a block for `layout`.
+    fn allocate(&self, layout: Layout) -> AllocResult<NonNull<u8>>;
+
+    /// Gives back a block obtained from [`allocate`](Self::allocate).
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been allocated by this handle with the same `layout`.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+impl<const PAGE_SIZE: usize> AllocHandle for RefCell<EarlyAllocator<PAGE_SIZE>> {
+    fn allocate(&self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        self.borrow_mut().alloc(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.borrow_mut().dealloc(ptr, layout)
+    }
+}
+
+/// An owned `T` allocated from an early allocator, freed on drop.
+pub struct EarlyBox<'a, T, A: AllocHandle + ?Sized> {
+    ptr: NonNull<T>,
+    alloc: &'a A,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T, A: AllocHandle + ?Sized> EarlyBox<'a, T, A> {
+    /// Moves `value` into a block allocated from `alloc`.
+    pub fn new(value: T, alloc: &'a A) -> AllocResult<Self> {
+        let ptr = alloc.allocate(Layout::new::<T>())?.cast::<T>();
+        // SAFETY: the block is fresh and fits a `T`.
+        unsafe { ptr.as_ptr().write(value) };
+        Ok(Self {
+            ptr,
+            alloc,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Consumes the box without freeing it, returning a reference that lives
+    /// as long as the allocator.
+    pub fn leak(self) -> &'a mut T {
+        let ptr = self.ptr;
+        core::mem::forget(self);
+        // SAFETY: the block is never freed, and we owned the only reference.
+        unsafe { &mut *ptr.as_ptr() }
+    }
+}
+
+impl<T, A: AllocHandle + ?Sized> Deref for EarlyBox<'_, T, A> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the box owns an initialized `T`.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T, A: AllocHandle + ?Sized> DerefMut for EarlyBox<'_, T, A> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the box owns an initialized `T`.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: fmt::Debug, A: AllocHandle + ?Sized> fmt::Debug for EarlyBox<'_, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T, A: AllocHandle + ?Sized> Drop for EarlyBox<'_, T, A> {
+    fn drop(&mut self) {
+        // SAFETY: the box owns the `T` and the block it lives in.
+        unsafe {
+            self.ptr.as_ptr().drop_in_place();
+            self.alloc.deallocate(self.ptr.cast(), Layout::new::<T>());
+        }
+    }
+}
+
+/// A fixed-capacity vector allocated from an early allocator, freed on drop.
+pub struct EarlyVec<'a, T, A: AllocHandle + ?Sized> {
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+    alloc: &'a A,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T, A: AllocHandle + ?Sized> EarlyVec<'a, T, A> {
+    /// Allocates room for exactly `cap` elements from `alloc`.
+    pub fn with_capacity(cap: usize, alloc: &'a A) -> AllocResult<Self> {
+        let layout = Layout::array::<T>(cap).map_err(|_| AllocError::InvalidParam)?;
+        let ptr = alloc.allocate(layout)?.cast::<T>();
+        Ok(Self {
+            ptr,
+            len: 0,
+            cap,
+            alloc,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the vector holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the fixed capacity.
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Appends `value`, or hands it back if the vector is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.len == self.cap {
+            return Err(value);
+        }
+        // SAFETY: `len < cap`, so the slot is inside the block and unused.
+        unsafe { self.ptr.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes and returns the last element.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // SAFETY: the slot was initialized and is now considered unused.
+        Some(unsafe { self.ptr.as_ptr().add(self.len).read() })
+    }
+
+    /// Drops all elements, keeping the capacity.
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T, A: AllocHandle + ?Sized> Deref for EarlyVec<'_, T, A> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the first `len` slots are initialized.
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T, A: AllocHandle + ?Sized> DerefMut for EarlyVec<'_, T, A> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: the first `len` slots are initialized.
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: fmt::Debug, A: AllocHandle + ?Sized> fmt::Debug for EarlyVec<'_, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T, A: AllocHandle + ?Sized> Drop for EarlyVec<'_, T, A> {
+    fn drop(&mut self) {
+        self.clear();
+        if let Ok(layout) = Layout::array::<T>(self.cap) {
+            // SAFETY: the block was allocated with this layout.
+            unsafe { self.alloc.deallocate(self.ptr.cast(), layout) };
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use core::alloc::Layout;
+use core::ops::Range;
+
+use crate::region::align_up;
+
+/// Pages set aside for [`EarlyAllocator::alloc_critical`](crate::EarlyAllocator::alloc_critical).
+///
+/// Blocks are bumped upward and never freed: the reserve is meant for
+/// diagnostics on the way down, not for steady use.
+pub(crate) struct EmergencyReserve {
+    range: Range<usize>,
+    pos: usize,
+}
+
+impl EmergencyReserve {
+    pub fn new(range: Range<usize>) -> Self {
+        Self {
+            pos: range.start,
+            range,
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Returns `true` if no block has been taken from the reserve yet.
+    pub fn is_untouched(&self) -> bool {
+        self.pos == self.range.start
+    }
+
+    pub fn available(&self) -> usize {
+        self.range.end - self.pos
+    }
+
+    /// Takes a block for `layout` whose last byte is at or below `limit`.
+    pub fn alloc(&mut self, layout: Layout, limit: usize) -> Option<usize> {
+        let pos = align_up(self.pos, layout.align())?;
+        let end = pos.checked_add(layout.size())?;
+        let last = if layout.size() == 0 { pos } else { end - 1 };
+        if end > self.range.end || last > limit {
+            return None;
+        }
+        self.pos = end;
+        Some(pos)
+    }
+}
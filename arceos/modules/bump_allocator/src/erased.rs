@@ -0,0 +1,154 @@
+use allocator::{AllocResult, BaseAllocator, ByteAllocator, PageAllocator};
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use crate::EarlyAllocator;
+
+/// The object-safe operations of an [`EarlyAllocator`] of any page size.
+trait ErasedOps {
+    fn init(&mut self, start: usize, size: usize);
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult;
+    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>>;
+    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout);
+    fn total_bytes(&self) -> usize;
+    fn used_bytes(&self) -> usize;
+    fn available_bytes(&self) -> usize;
+    fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize>;
+    fn dealloc_pages(&mut self, pos: usize, num_pages: usize);
+    fn total_pages(&self) -> usize;
+    fn used_pages(&self) -> usize;
+    fn available_pages(&self) -> usize;
+}
+
+impl<const PAGE_SIZE: usize> ErasedOps for EarlyAllocator<PAGE_SIZE> {
+    fn init(&mut self, start: usize, size: usize) {
+        BaseAllocator::init(self, start, size)
+    }
+
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        BaseAllocator::add_memory(self, start, size)
+    }
+
+    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        ByteAllocator::alloc(self, layout)
+    }
+
+    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
+        ByteAllocator::dealloc(self, pos, layout)
+    }
+
+    fn total_bytes(&self) -> usize {
+        ByteAllocator::total_bytes(self)
+    }
+
+    fn used_bytes(&self) -> usize {
+        ByteAllocator::used_bytes(self)
+    }
+
+    fn available_bytes(&self) -> usize {
+        ByteAllocator::available_bytes(self)
+    }
+
+    fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        PageAllocator::alloc_pages(self, num_pages, align_pow2)
+    }
+
+    fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
+        PageAllocator::dealloc_pages(self, pos, num_pages)
+    }
+
+    fn total_pages(&self) -> usize {
+        PageAllocator::total_pages(self)
+    }
+
+    fn used_pages(&self) -> usize {
+        PageAllocator::used_pages(self)
+    }
+
+    fn available_pages(&self) -> usize {
+        PageAllocator::available_pages(self)
+    }
+}
+
+/// An [`EarlyAllocator`] with its `PAGE_SIZE` turned into a runtime value,
+/// so allocators of different page sizes share one type and can be used
+/// as `&mut dyn ByteAllocator`.
+///
+/// [`PageAllocator`] has the page size as an associated constant, so page
+/// allocations are inherent methods here instead.
+pub struct ErasedEarlyAllocator<'a> {
+    inner: &'a mut dyn ErasedOps,
+    page_size: usize,
+}
+
+impl<'a> ErasedEarlyAllocator<'a> {
+    /// Erases the page size of `alloc`.
+    pub fn new<const PAGE_SIZE: usize>(alloc: &'a mut EarlyAllocator<PAGE_SIZE>) -> Self {
+        Self {
+            inner: alloc,
+            page_size: PAGE_SIZE,
+        }
+    }
+
+    /// The page size captured at construction.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// See [`PageAllocator::alloc_pages`].
+    pub fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        self.inner.alloc_pages(num_pages, align_pow2)
+    }
+
+    /// See [`PageAllocator::dealloc_pages`].
+    pub fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
+        self.inner.dealloc_pages(pos, num_pages)
+    }
+
+    /// See [`PageAllocator::total_pages`].
+    pub fn total_pages(&self) -> usize {
+        self.inner.total_pages()
+    }
+
+    /// See [`PageAllocator::used_pages`].
+    pub fn used_pages(&self) -> usize {
+        self.inner.used_pages()
+    }
+
+    /// See [`PageAllocator::available_pages`].
+    pub fn available_pages(&self) -> usize {
+        self.inner.available_pages()
+    }
+}
+
+impl BaseAllocator for ErasedEarlyAllocator<'_> {
+    fn init(&mut self, start: usize, size: usize) {
+        self.inner.init(start, size)
+    }
+
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        self.inner.add_memory(start, size)
+    }
+}
+
+impl ByteAllocator for ErasedEarlyAllocator<'_> {
+    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        self.inner.alloc(layout)
+    }
+
+    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
+        self.inner.dealloc(pos, layout)
+    }
+
+    fn total_bytes(&self) -> usize {
+        self.inner.total_bytes()
+    }
+
+    fn used_bytes(&self) -> usize {
+        self.inner.used_bytes()
+    }
+
+    fn available_bytes(&self) -> usize {
+        self.inner.available_bytes()
+    }
+}
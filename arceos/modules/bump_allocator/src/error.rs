@@ -0,0 +1,81 @@
+//! Errors more specific than [`AllocError`].
+
+use allocator::AllocError;
+use core::fmt;
+
+use crate::Area;
+
+/// An allocation error that can tell fragmentation from exhaustion.
+#[derive(Debug)]
+pub enum EarlyAllocError {
+    /// Enough memory is free in total, but no single region can hold the run.
+    /// Runs never span regions, which need not be contiguous.
+    Fragmented,
+    /// No region has room for `size` bytes aligned to `align`.
+    OutOfMemory { size: usize, align: usize },
+    /// The request would fit in a region, but the `growing` area ran into
+    /// the other one: the bytes area hitting the pages means too small a
+    /// heap, the pages area hitting the bytes means too many pages taken.
+    Collision { growing: Area },
+    /// Memory the allocator does not hand out was overwritten at `addr`, or
+    /// the bookkeeping of region `region` is inconsistent.
+    Corrupted { region: usize, addr: usize },
+    /// The lock was held, e.g. by the code an interrupt handler interrupted.
+    WouldBlock,
+    /// The allocator was used before any memory was added to it.
+    Uninitialized,
+    /// Any other error.
+    Alloc(AllocError),
+}
+
+impl From<AllocError> for EarlyAllocError {
+    fn from(err: AllocError) -> Self {
+        Self::Alloc(err)
+    }
+}
+
+impl From<EarlyAllocError> for AllocError {
+    fn from(err: EarlyAllocError) -> Self {
+        match err {
+            EarlyAllocError::Fragmented => AllocError::NoMemory,
+            EarlyAllocError::OutOfMemory { .. } => AllocError::NoMemory,
+            EarlyAllocError::Collision { .. } => AllocError::MemoryOverlap,
+            EarlyAllocError::Corrupted { .. } => AllocError::MemoryOverlap,
+            EarlyAllocError::WouldBlock => AllocError::NoMemory,
+            EarlyAllocError::Uninitialized => AllocError::NoMemory,
+            EarlyAllocError::Alloc(err) => err,
+        }
+    }
+}
+
+impl fmt::Display for EarlyAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Fragmented => {
+                f.write_str("enough memory is free, but no region can hold the run")
+            }
+            Self::OutOfMemory { size, align } => write!(
+                f,
+                "out of memory for {} bytes aligned to {:#x}",
+                size, align
+            ),
+            Self::Collision {
+                growing: Area::Bytes,
+            } => f.write_str("bytes area ran into the pages area"),
+            Self::Collision {
+                growing: Area::Pages,
+            } => f.write_str("pages area ran into the bytes area"),
+            Self::Corrupted { region, addr } => {
+                write!(f, "memory corrupted at {:#x} in region {}", addr, region)
+            }
+            Self::WouldBlock => f.write_str("allocator lock is held"),
+            Self::Uninitialized => f.write_str("allocator used before init"),
+            Self::Alloc(AllocError::InvalidParam) => f.write_str("invalid parameter"),
+            Self::Alloc(AllocError::MemoryOverlap) => f.write_str("memory overlaps a region"),
+            Self::Alloc(AllocError::NoMemory) => f.write_str("out of memory"),
+            Self::Alloc(AllocError::NotAllocated) => f.write_str("memory was not allocated"),
+        }
+    }
+}
+
+impl core::error::Error for EarlyAllocError {}
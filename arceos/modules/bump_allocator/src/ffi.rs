@@ -0,0 +1,80 @@
+//! C entry points over a global allocator, enabled by the `ffi` feature.
+//!
+//! Boot stages written in assembly or C, and firmware shims, allocate from
+//! the same pool as Rust code, which reaches it with [`ffi_allocator`].
+
+use allocator::{ByteAllocator, PageAllocator};
+use core::alloc::Layout;
+use core::ffi::c_int;
+use core::ptr::{self, NonNull};
+
+use crate::LockedEarlyAllocator;
+
+/// The page size of the global allocator behind the C entry points.
+pub const FFI_PAGE_SIZE: usize = 0x1000;
+
+static FFI_ALLOCATOR: LockedEarlyAllocator<FFI_PAGE_SIZE> = LockedEarlyAllocator::new();
+
+/// Returns the global allocator behind the C entry points.
+pub fn ffi_allocator() -> &'static LockedEarlyAllocator<FFI_PAGE_SIZE> {
+    &FFI_ALLOCATOR
+}
+
+/// Adds `[start, start + size)` to the pool, which starts out empty.
+/// Returns `0` on success, `-1` if the range is invalid or overlaps memory
+/// already added.
+///
+/// # Safety
+///
+/// The range must be valid memory that nothing else uses.
+#[no_mangle]
+pub unsafe extern "C" fn early_add_memory(start: usize, size: usize) -> c_int {
+    match FFI_ALLOCATOR.add_memory(start, size) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Allocates `size` bytes aligned to `align`, which must be a power of two.
+/// Returns null on failure.
+#[no_mangle]
+pub extern "C" fn early_alloc(size: usize, align: usize) -> *mut u8 {
+    Layout::from_size_align(size, align)
+        .ok()
+        .and_then(|layout| FFI_ALLOCATOR.lock().alloc(layout).ok())
+        .map_or(ptr::null_mut(), NonNull::as_ptr)
+}
+
+/// Frees a block returned by [`early_alloc`] with the same `size` and
+/// `align`. Null is ignored.
+///
+/// # Safety
+///
+/// `ptr` must come from `early_alloc` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn early_free(ptr: *mut u8, size: usize, align: usize) {
+    let (Some(ptr), Ok(layout)) = (NonNull::new(ptr), Layout::from_size_align(size, align)) else {
+        return;
+    };
+    FFI_ALLOCATOR.lock().dealloc(ptr, layout);
+}
+
+/// Allocates `num_pages` contiguous pages aligned to `align` bytes. Returns
+/// the address of the first page, or `0` on failure.
+#[no_mangle]
+pub extern "C" fn early_alloc_pages(num_pages: usize, align: usize) -> usize {
+    FFI_ALLOCATOR
+        .lock()
+        .alloc_pages(num_pages, align)
+        .unwrap_or(0)
+}
+
+/// Frees `num_pages` pages at `addr` returned by [`early_alloc_pages`].
+///
+/// # Safety
+///
+/// The pages must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn early_free_pages(addr: usize, num_pages: usize) {
+    FFI_ALLOCATOR.lock().dealloc_pages(addr, num_pages);
+}
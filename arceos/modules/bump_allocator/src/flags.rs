@@ -0,0 +1,63 @@
+use core::ops::{BitOr, BitOrAssign};
+
+/// Modifiers for
+/// [`EarlyAllocator::alloc_flags`](crate::EarlyAllocator::alloc_flags),
+/// combined with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AllocFlags(u32);
+
+impl AllocFlags {
+    /// No modifier: the block is allocated like `alloc` does.
+    pub const NONE: Self = Self(0);
+    /// The block is filled with zeroes.
+    pub const ZEROED: Self = Self(1 << 0);
+    /// The block lies below 4 GiB, like
+    /// [`alloc_dma`](crate::EarlyAllocator::alloc_dma) with
+    /// [`DMA32_MASK`](crate::DMA32_MASK). Use `alloc_dma` directly for
+    /// another mask.
+    pub const DMA: Self = Self(1 << 1);
+    /// The block is taken from the high end of a region, like
+    /// [`alloc_from_top`](crate::EarlyAllocator::alloc_from_top).
+    pub const HIGH: Self = Self(1 << 2);
+    /// Only the preferred region is tried, as with
+    /// [`SpillPolicy::Never`](crate::SpillPolicy::Never).
+    pub const NO_SPILL: Self = Self(1 << 3);
+    /// The emergency reserve may be used, like
+    /// [`alloc_critical`](crate::EarlyAllocator::alloc_critical).
+    pub const CRITICAL: Self = Self(1 << 4);
+
+    const ALL: u32 = 0x1f;
+
+    /// Returns the raw bits.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Converts raw bits, or returns `None` if unknown bits are set.
+    pub const fn from_bits(bits: u32) -> Option<Self> {
+        if bits & !Self::ALL == 0 {
+            Some(Self(bits))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if all flags of `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for AllocFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for AllocFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
@@ -0,0 +1,211 @@
+//! Page-granular freeing in the pages area.
+//!
+//! Freeing the run lying at `p_pos` just moves it back up. Any other freed
+//! run is marked in a per-region bitmap of one bit per page, which is reused
+//! by later page allocations. The bitmap is stolen from the tail of the pages
+//! area on the first such free, so allocators that never free pages out of
+//! order pay nothing for it.
+
+use core::ops::Range;
+
+use crate::region::{align_down, align_up, Region};
+use crate::Rebalance;
+
+impl Region {
+    fn frame_index(&self, page: usize, page_size: usize) -> usize {
+        (page - align_down(self.start, page_size)) / page_size
+    }
+
+    /// Returns `true` if the page at `page` has been freed and not reused.
+    pub fn is_freed(&self, page: usize, page_size: usize) -> bool {
+        let Some(frames) = self.frames else {
+            return false;
+        };
+        let idx = self.frame_index(page, page_size);
+        // SAFETY: `frames` points to the bitmap stolen from this region, which
+        // holds one bit for every page of it.
+        let byte = unsafe { *((frames + idx / 8) as *const u8) };
+        byte & (1 << (idx % 8)) != 0
+    }
+
+    fn mark(&mut self, run: Range<usize>, freed: bool, page_size: usize) {
+        let Some(frames) = self.frames else {
+            return;
+        };
+        for page in run.step_by(page_size) {
+            let idx = self.frame_index(page, page_size);
+            // SAFETY: see `is_freed`.
+            let byte = unsafe { &mut *((frames + idx / 8) as *mut u8) };
+            if freed {
+                *byte |= 1 << (idx % 8);
+            } else {
+                *byte &= !(1 << (idx % 8));
+            }
+        }
+    }
+
+    /// Finds the lowest run of `size` bytes of freed pages aligned to `align`.
+    pub fn find_freed(&self, size: usize, align: usize, page_size: usize) -> Option<usize> {
+        if self.frames.is_none() || size == 0 || self.freed < size {
+            return None;
+        }
+        let mut pos = align_up(self.p_pos, align)?;
+        while pos.checked_add(size)? <= self.end {
+            if (pos..pos + size)
+                .step_by(page_size)
+                .all(|page| self.is_freed(page, page_size))
+            {
+                return Some(pos);
+            }
+            pos = pos.checked_add(align)?;
+        }
+        None
+    }
+
+    /// Hands out the freed run found by [`find_freed`](Self::find_freed).
+    pub fn take_freed(&mut self, pos: usize, size: usize, page_size: usize) {
+        self.mark(pos..pos + size, false, page_size);
+        self.freed -= size;
+    }
+
+    /// Frees the page run `[pos, pos + size)`.
+    ///
+    /// Returns `false` if `pos` is not page-aligned, if the run is not
+    /// allocated from the pages area or covers the bitmap, or if no room is
+    /// left for the bitmap.
+    pub fn free_pages(
+        &mut self,
+        pos: usize,
+        size: usize,
+        page_size: usize,
+        rebalance: Rebalance,
+    ) -> bool {
+        let Some(end) = pos.checked_add(size) else {
+            return false;
+        };
+        if size == 0 || pos % page_size != 0 || pos < self.p_pos || end > self.end {
+            return false;
+        }
+        let bitmap = self.frames_pages(page_size);
+        if bitmap.start < end && pos < bitmap.end {
+            return false;
+        }
+        if (pos..end)
+            .step_by(page_size)
+            .any(|page| self.is_freed(page, page_size))
+        {
+            return false;
+        }
+        if pos == self.p_pos {
+            self.p_pos = end;
+            while self.p_pos < self.end && self.is_freed(self.p_pos, page_size) {
+                self.take_freed(self.p_pos, page_size, page_size);
+                self.p_pos += page_size;
+            }
+            // Give a sliver past the last whole page back to the free gap.
+            if self.p_pos >= align_down(self.end, page_size) {
+                self.p_pos = self.end;
+            }
+            return true;
+        }
+        if self.frames.is_none() && !self.steal_frames(page_size, rebalance) {
+            return false;
+        }
+        self.mark(pos..end, true, page_size);
+        self.freed += size;
+        true
+    }
+
+    /// Iterates over the freed page runs, lowest first.
+    pub fn freed_runs(&self, page_size: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+        let mut page = if self.freed == 0 {
+            self.end
+        } else {
+            align_up(self.p_pos, page_size).unwrap_or(self.end)
+        };
+        core::iter::from_fn(move || {
+            while page < self.end && !self.is_freed(page, page_size) {
+                page += page_size;
+            }
+            let start = page;
+            while page < self.end && self.is_freed(page, page_size) {
+                page += page_size;
+            }
+            (start < page).then_some(start..page)
+        })
+    }
+
+    /// Iterates over the page runs in use, lowest first. The page holding
+    /// `p_pos` counts as used even if it is shared with the free gap.
+    pub fn used_runs(&self, page_size: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+        let mut page = if self.p_pos < self.end {
+            align_down(self.p_pos, page_size)
+        } else {
+            self.end
+        };
+        core::iter::from_fn(move || {
+            while page < self.end && self.is_freed(page, page_size) {
+                page += page_size;
+            }
+            let start = page;
+            while page < self.end && !self.is_freed(page, page_size) {
+                page += page_size;
+            }
+            (start < page).then(|| start..page.min(self.end))
+        })
+    }
+
+    /// Drops the freed marks below `p_pos`, which are stale once the cursors
+    /// have been moved back, and recounts `freed`.
+    pub fn sync_freed(&mut self, page_size: usize) {
+        self.mark(
+            align_down(self.start, page_size)..self.p_pos,
+            false,
+            page_size,
+        );
+        self.freed = match align_up(self.p_pos, page_size) {
+            Some(first) => {
+                (first..self.end)
+                    .step_by(page_size)
+                    .filter(|&page| self.is_freed(page, page_size))
+                    .count()
+                    * page_size
+            }
+            None => 0,
+        };
+    }
+
+    /// Size of the bitmap in bytes: one bit for every page of the region.
+    fn frames_len(&self, page_size: usize) -> usize {
+        (self.end - align_down(self.start, page_size))
+            .div_ceil(page_size)
+            .div_ceil(8)
+    }
+
+    /// The pages holding the bitmap, empty if it was not stolen yet.
+    pub fn frames_pages(&self, page_size: usize) -> Range<usize> {
+        self.frames.map_or(0..0, |frames| {
+            let end = frames + self.frames_len(page_size);
+            align_down(frames, page_size)..align_up(end, page_size).unwrap_or(usize::MAX)
+        })
+    }
+
+    /// Returns `true` if no page of the pages area is in use except the ones
+    /// holding the bitmap.
+    pub fn pages_idle(&self, page_size: usize) -> bool {
+        let bitmap = self.frames_pages(page_size);
+        self.used_runs(page_size)
+            .all(|run| bitmap.start <= run.start && run.end <= bitmap.end)
+    }
+
+    fn steal_frames(&mut self, page_size: usize, rebalance: Rebalance) -> bool {
+        let len = self.frames_len(page_size);
+        let Ok(pos) = self.alloc_pages(len, 1, rebalance) else {
+            return false;
+        };
+        // SAFETY: `[pos, pos + len)` was just taken from this region.
+        unsafe { core::ptr::write_bytes(pos as *mut u8, 0, len) };
+        self.frames = Some(pos);
+        true
+    }
+}
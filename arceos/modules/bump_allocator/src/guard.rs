@@ -0,0 +1,52 @@
+use allocator::ByteAllocator;
+use core::alloc::Layout;
+use core::mem::ManuallyDrop;
+use core::ptr::NonNull;
+
+use crate::EarlyAllocator;
+
+/// A byte block that is given back to its [`EarlyAllocator`] on drop.
+///
+/// Returned by [`EarlyAllocator::alloc_guarded`].
+pub struct AllocGuard<'a, const PAGE_SIZE: usize> {
+    alloc: &'a mut EarlyAllocator<PAGE_SIZE>,
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl<'a, const PAGE_SIZE: usize> AllocGuard<'a, PAGE_SIZE> {
+    pub(crate) fn new(
+        alloc: &'a mut EarlyAllocator<PAGE_SIZE>,
+        ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Self {
+        Self { alloc, ptr, layout }
+    }
+
+    /// Returns the start of the guarded block.
+    pub fn as_ptr(&self) -> NonNull<u8> {
+        self.ptr
+    }
+
+    /// Returns the layout the block was allocated with.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Returns the allocator, e.g. to make further (guarded) allocations
+    /// while this block is alive.
+    pub fn allocator(&mut self) -> &mut EarlyAllocator<PAGE_SIZE> {
+        self.alloc
+    }
+
+    /// Keeps the block allocated and returns it without deallocating.
+    pub fn leak(self) -> NonNull<u8> {
+        ManuallyDrop::new(self).ptr
+    }
+}
+
+impl<const PAGE_SIZE: usize> Drop for AllocGuard<'_, PAGE_SIZE> {
+    fn drop(&mut self) {
+        self.alloc.dealloc(self.ptr, self.layout);
+    }
+}
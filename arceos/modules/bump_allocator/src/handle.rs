@@ -0,0 +1,175 @@
+//! Handles naming the regions added by
+//! [`EarlyAllocator::add_memory_handle`].
+
+use allocator::{AllocError, AllocResult};
+use core::alloc::Layout;
+use core::ops::Range;
+use core::ptr::NonNull;
+
+use crate::region::Region;
+use crate::{EarlyAllocator, RegionSnapshot};
+
+/// An opaque name for a region, returned by
+/// [`EarlyAllocator::add_memory_handle`].
+///
+/// It remembers where the region starts, so once the region is removed and
+/// its slot reused, the handle is refused instead of naming the newcomer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RegionHandle {
+    idx: u8,
+    start: usize,
+}
+
+impl RegionHandle {
+    /// Index of the region, for the methods taking one.
+    pub fn index(&self) -> usize {
+        self.idx as usize
+    }
+
+    /// See [`EarlyAllocator::region_stats`].
+    pub fn stats<const PAGE_SIZE: usize>(
+        &self,
+        alloc: &EarlyAllocator<PAGE_SIZE>,
+    ) -> AllocResult<RegionSnapshot> {
+        alloc.region_stats(*self)
+    }
+
+    /// See [`EarlyAllocator::disable_region`].
+    pub fn disable<const PAGE_SIZE: usize>(
+        &self,
+        alloc: &mut EarlyAllocator<PAGE_SIZE>,
+    ) -> AllocResult {
+        let idx = alloc.resolve(*self)?;
+        alloc.disable_region(idx)
+    }
+
+    /// See [`EarlyAllocator::enable_region`].
+    pub fn enable<const PAGE_SIZE: usize>(
+        &self,
+        alloc: &mut EarlyAllocator<PAGE_SIZE>,
+    ) -> AllocResult {
+        let idx = alloc.resolve(*self)?;
+        alloc.enable_region(idx)
+    }
+
+    /// Returns `true` if the region is still there and not disabled.
+    pub fn is_enabled<const PAGE_SIZE: usize>(&self, alloc: &EarlyAllocator<PAGE_SIZE>) -> bool {
+        alloc
+            .resolve(*self)
+            .is_ok_and(|idx| alloc.is_region_enabled(idx))
+    }
+
+    /// See [`EarlyAllocator::withdraw_tail`]. The handle stays valid.
+    pub fn withdraw_tail<const PAGE_SIZE: usize>(
+        &self,
+        alloc: &mut EarlyAllocator<PAGE_SIZE>,
+        bytes: usize,
+    ) -> AllocResult<Range<usize>> {
+        let idx = alloc.resolve(*self)?;
+        alloc.withdraw_tail(idx, bytes)
+    }
+}
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Like [`add_memory_flags`](Self::add_memory_flags), but returns a
+    /// handle to the new region. If exclusions split the range, the handle
+    /// names the lowest piece.
+    pub fn add_memory_handle(
+        &mut self,
+        start: usize,
+        size: usize,
+        flags: u32,
+    ) -> AllocResult<RegionHandle> {
+        let before = self.bitmap;
+        self.add_memory_flags(start, size, flags)?;
+        let added = self.bitmap & !before;
+        let idx = self
+            .sorted_indices()
+            .iter()
+            .copied()
+            .find(|&idx| added & (1 << idx) != 0)
+            .ok_or(AllocError::InvalidParam)?;
+        Ok(RegionHandle {
+            idx,
+            start: self.regions[idx as usize].start,
+        })
+    }
+
+    /// Returns the index of the region `handle` names, or
+    /// [`AllocError::InvalidParam`] if it has been removed.
+    fn resolve(&self, handle: RegionHandle) -> AllocResult<usize> {
+        let idx = handle.index();
+        if self.region(idx)?.start != handle.start {
+            return Err(AllocError::InvalidParam);
+        }
+        Ok(idx)
+    }
+
+    /// Allocates a byte block from the region `handle` names only.
+    #[track_caller]
+    pub fn alloc_in_region(
+        &mut self,
+        handle: RegionHandle,
+        layout: Layout,
+    ) -> AllocResult<NonNull<u8>> {
+        let idx = self.resolve(handle)?;
+        self.alloc_bytes_in(idx, layout)
+    }
+
+    /// Allocates a page run from the region `handle` names only.
+    pub fn alloc_pages_in_region(
+        &mut self,
+        handle: RegionHandle,
+        num_pages: usize,
+        align_pow2: usize,
+    ) -> AllocResult<usize> {
+        let idx = self.resolve(handle)?;
+        self.alloc_page_run(num_pages, align_pow2, false, Some(idx))
+    }
+
+    /// Returns the cursors and counters of the region `handle` names.
+    pub fn region_stats(&self, handle: RegionHandle) -> AllocResult<RegionSnapshot> {
+        let idx = self.resolve(handle)?;
+        Ok(RegionSnapshot::from(&self.regions[idx]))
+    }
+
+    /// Removes the region `handle` names and returns its range, e.g. before
+    /// memory is hot-unplugged or handed to another allocator.
+    ///
+    /// Fails with [`AllocError::MemoryOverlap`] while anything is allocated
+    /// from it. Pages lost to the padding of aligned page runs count as
+    /// allocated.
+    pub fn remove_region(&mut self, handle: RegionHandle) -> AllocResult<Range<usize>> {
+        let idx = self.resolve(handle)?;
+        let region = &self.regions[idx];
+        if region.count != 0 || region.b_pos != region.start || !region.pages_idle(PAGE_SIZE) {
+            return Err(AllocError::MemoryOverlap);
+        }
+        let range = region.start..region.end;
+        let num = self.bitmap.count_ones() as usize;
+        let pos = self.sorted[..num]
+            .iter()
+            .position(|&i| i as usize == idx)
+            .ok_or(AllocError::InvalidParam)?;
+        self.sorted.copy_within(pos + 1..num, pos);
+        self.bitmap &= !(1 << idx);
+        self.disabled &= !(1 << idx);
+        self.regions[idx] = Region::EMPTY;
+        self.raw_bytes = self.raw_bytes.saturating_sub(range.len());
+        if self.exec_region == Some(idx) {
+            self.exec_region = None;
+        }
+        if self.current_region == idx {
+            self.current_region = self.sorted_indices().first().map_or(0, |&i| i as usize);
+        }
+        #[cfg(feature = "sentinel")]
+        {
+            self.sentinels &= !(1 << idx);
+        }
+        #[cfg(feature = "canary")]
+        {
+            self.canaries[idx] = [None; 2];
+        }
+        Ok(range)
+    }
+}
@@ -0,0 +1,89 @@
+//! Byte allocations that remember their layout, enabled by the `header`
+//! feature.
+
+use allocator::{AllocError, AllocResult, ByteAllocator};
+use core::alloc::Layout;
+use core::mem::{align_of, size_of};
+use core::ptr::NonNull;
+
+use crate::EarlyAllocator;
+
+/// The layout of a block, stored right below it.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Header {
+    size: usize,
+    align: usize,
+}
+
+/// Bytes taken by the header of a block from
+/// [`alloc_with_header`](EarlyAllocator::alloc_with_header). Blocks aligned
+/// to more than this cost their alignment instead.
+pub const HEADER_SIZE: usize = size_of::<Header>();
+
+/// Returns the layout actually allocated for `layout`, and the offset of the
+/// block in it.
+fn outer_layout(layout: Layout) -> AllocResult<(Layout, usize)> {
+    let offset = HEADER_SIZE.max(layout.align());
+    let size = layout
+        .size()
+        .checked_add(offset)
+        .ok_or(AllocError::InvalidParam)?;
+    let outer = Layout::from_size_align(size, layout.align().max(align_of::<Header>()))
+        .map_err(|_| AllocError::InvalidParam)?;
+    Ok((outer, offset))
+}
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Like [`ByteAllocator::alloc`], but records `layout` in a header below
+    /// the block, so it can be freed with
+    /// [`dealloc_with_header`](Self::dealloc_with_header) alone, as a
+    /// `GlobalAlloc` or C `free` shim needs.
+    #[track_caller]
+    pub fn alloc_with_header(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        let (outer, offset) = outer_layout(layout)?;
+        let base = self.alloc(outer)?;
+        // SAFETY: the block is `offset + layout.size()` bytes long, and
+        // `offset` is at least `HEADER_SIZE`.
+        unsafe {
+            let ptr = base.as_ptr().add(offset);
+            ptr.cast::<Header>().sub(1).write(Header {
+                size: layout.size(),
+                align: layout.align(),
+            });
+            Ok(NonNull::new_unchecked(ptr))
+        }
+    }
+
+    /// Returns the layout a block from
+    /// [`alloc_with_header`](Self::alloc_with_header) was requested with.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `alloc_with_header` on this allocator and not
+    /// have been freed.
+    pub unsafe fn header_layout(&self, ptr: NonNull<u8>) -> Layout {
+        // SAFETY: the caller guarantees a live header sits below `ptr`.
+        let header = unsafe { ptr.as_ptr().cast::<Header>().sub(1).read() };
+        // SAFETY: the header was written from a valid layout.
+        unsafe { Layout::from_size_align_unchecked(header.size, header.align) }
+    }
+
+    /// Frees a block from [`alloc_with_header`](Self::alloc_with_header)
+    /// without being told its layout.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `alloc_with_header` on this allocator and not
+    /// have been freed.
+    pub unsafe fn dealloc_with_header(&mut self, ptr: NonNull<u8>) {
+        // SAFETY: guaranteed by the caller.
+        let layout = unsafe { self.header_layout(ptr) };
+        let Ok((outer, offset)) = outer_layout(layout) else {
+            return;
+        };
+        // SAFETY: the block starts `offset` bytes below `ptr`.
+        let base = unsafe { NonNull::new_unchecked(ptr.as_ptr().sub(offset)) };
+        self.dealloc(base, outer);
+    }
+}
@@ -0,0 +1,94 @@
+use core::fmt;
+
+/// Optional instrumentation callbacks invoked by [`EarlyAllocator`].
+///
+/// Every callback receives the exact `(start, len)` of the affected range, so
+/// a sanitizer can keep its shadow memory in sync with early allocations.
+///
+/// [`EarlyAllocator`]: crate::EarlyAllocator
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hooks {
+    /// Called after a range has been handed out (bytes or pages).
+    pub on_range_allocated: Option<fn(usize, usize)>,
+    /// Called when a range becomes inaccessible: a freed byte block, or a
+    /// guard hole that is never handed out.
+    pub on_range_freed: Option<fn(usize, usize)>,
+    /// Called when a handed out range overlaps the range being watched.
+    ///
+    /// See [`EarlyAllocator::watch`](crate::EarlyAllocator::watch).
+    pub on_watch_hit: Option<fn(usize, usize)>,
+    /// Called when an allocation runs out of memory, before the error is
+    /// returned, with a report of the failing request, the region table and
+    /// the counters to print to the early console.
+    pub on_oom: Option<fn(&dyn fmt::Display)>,
+}
+
+impl Hooks {
+    /// No callbacks installed.
+    pub const NONE: Self = Self {
+        on_range_allocated: None,
+        on_range_freed: None,
+        on_watch_hit: None,
+        on_oom: None,
+    };
+}
+
+#[cfg(not(feature = "no-stats"))]
+impl Hooks {
+    pub(crate) fn allocated(&self, start: usize, len: usize) {
+        if let Some(f) = self.on_range_allocated {
+            f(start, len);
+        }
+    }
+
+    pub(crate) fn watch_hit(&self, start: usize, len: usize) {
+        match self.on_watch_hit {
+            Some(f) => f(start, len),
+            None => debug_assert!(
+                false,
+                "allocation [{:#x}, {:#x}) hits the watched range",
+                start,
+                start + len
+            ),
+        }
+    }
+
+    pub(crate) fn freed(&self, start: usize, len: usize) {
+        if let Some(f) = self.on_range_freed {
+            f(start, len);
+        }
+    }
+
+    pub(crate) fn oom(&self, report: &dyn fmt::Display) {
+        if let Some(f) = self.on_oom {
+            f(report);
+        }
+    }
+}
+
+/// The hooks stored by the allocator.
+#[cfg(not(feature = "no-stats"))]
+pub(crate) type HookSlot = Hooks;
+
+/// The hooks stored by the allocator.
+#[cfg(feature = "no-stats")]
+pub(crate) type HookSlot = NoHooks;
+
+/// Stands in for [`Hooks`] with the `no-stats` feature: nothing is stored
+/// and no callback is ever made.
+#[cfg(feature = "no-stats")]
+#[derive(Clone, Copy)]
+pub(crate) struct NoHooks;
+
+#[cfg(feature = "no-stats")]
+impl NoHooks {
+    pub const NONE: Self = Self;
+
+    pub(crate) fn allocated(&self, _start: usize, _len: usize) {}
+
+    pub(crate) fn watch_hit(&self, _start: usize, _len: usize) {}
+
+    pub(crate) fn freed(&self, _start: usize, _len: usize) {}
+
+    pub(crate) fn oom(&self, _report: &dyn fmt::Display) {}
+}
@@ -0,0 +1,108 @@
+//! Allocation latency, enabled by the `latency` feature.
+
+/// A free-running counter to time allocations with, e.g. `rdtsc` on x86 or
+/// the `cntvct_el0` register on AArch64.
+pub trait CycleCounter: Sync {
+    /// Returns the current count. It must not go backwards on one CPU.
+    fn cycles(&self) -> u64;
+}
+
+/// Minimum, average and maximum of the samples of one operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Number of samples.
+    pub count: u64,
+    /// Shortest sample, in cycles; `u64::MAX` if there are none.
+    pub min: u64,
+    /// Longest sample, in cycles.
+    pub max: u64,
+    /// Sum of the samples, in cycles.
+    pub total: u64,
+}
+
+impl LatencyStats {
+    /// No samples.
+    pub const EMPTY: Self = Self {
+        count: 0,
+        min: u64::MAX,
+        max: 0,
+        total: 0,
+    };
+
+    /// Returns the average sample, or `0` if there are none.
+    pub fn avg(&self) -> u64 {
+        self.total.checked_div(self.count).unwrap_or(0)
+    }
+
+    fn record(&mut self, cycles: u64) {
+        self.count += 1;
+        self.min = self.min.min(cycles);
+        self.max = self.max.max(cycles);
+        self.total = self.total.saturating_add(cycles);
+    }
+}
+
+impl Default for LatencyStats {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+/// The samples kept by an allocator with a counter set.
+pub(crate) struct Latency {
+    counter: Option<&'static dyn CycleCounter>,
+    pub bytes: LatencyStats,
+    pub pages: LatencyStats,
+    pub locked: LatencyStats,
+}
+
+impl Latency {
+    pub const fn new() -> Self {
+        Self {
+            counter: None,
+            bytes: LatencyStats::EMPTY,
+            pages: LatencyStats::EMPTY,
+            locked: LatencyStats::EMPTY,
+        }
+    }
+
+    pub fn set_counter(&mut self, counter: Option<&'static dyn CycleCounter>) {
+        self.counter = counter;
+    }
+
+    /// Clears the samples, keeping the counter.
+    pub fn reset(&mut self) {
+        *self = Self {
+            counter: self.counter,
+            ..Self::new()
+        };
+    }
+
+    /// Reads the counter, if one is set.
+    pub fn now(&self) -> Option<u64> {
+        self.counter.map(|counter| counter.cycles())
+    }
+
+    /// Returns the cycles elapsed since `start`, read with [`now`](Self::now).
+    fn since(&self, start: Option<u64>) -> Option<u64> {
+        Some(self.now()?.wrapping_sub(start?))
+    }
+
+    pub fn record_bytes(&mut self, start: Option<u64>) {
+        if let Some(cycles) = self.since(start) {
+            self.bytes.record(cycles);
+        }
+    }
+
+    pub fn record_pages(&mut self, start: Option<u64>) {
+        if let Some(cycles) = self.since(start) {
+            self.pages.record(cycles);
+        }
+    }
+
+    pub fn record_locked(&mut self, start: Option<u64>) {
+        if let Some(cycles) = self.since(start) {
+            self.locked.record(cycles);
+        }
+    }
+}
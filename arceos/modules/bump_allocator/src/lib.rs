@@ -1,6 +1,337 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+#![feature(error_in_core)]
 
-use allocator::{BaseAllocator, ByteAllocator, PageAllocator};
+use allocator::{AllocError, AllocResult, BaseAllocator, ByteAllocator, PageAllocator};
+use core::alloc::Layout;
+use core::fmt;
+use core::ops::Range;
+use core::ptr::NonNull;
+
+/// Logs an allocator warning through `axlog` with the `axlog` feature, and
+/// compiles to nothing otherwise.
+macro_rules! early_warn {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "axlog")]
+        axlog::warn!($($arg)+);
+    };
+}
+
+mod arena;
+mod bootstrap;
+mod bounce;
+#[cfg(feature = "canary")]
+mod canary;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+#[cfg(feature = "containers")]
+mod containers;
+mod emergency;
+mod erased;
+mod error;
+#[cfg(all(feature = "ffi", not(loom)))]
+mod ffi;
+mod flags;
+mod frames;
+mod guard;
+mod handle;
+#[cfg(feature = "header")]
+mod header;
+mod hooks;
+#[cfg(feature = "latency")]
+mod latency;
+mod locked;
+#[cfg(feature = "model")]
+mod model;
+#[cfg(feature = "mte")]
+mod mte;
+mod observer;
+mod oom;
+mod pagebox;
+mod pgtable;
+#[cfg(all(kani, feature = "verify-kani"))]
+mod proofs;
+mod quarantine;
+mod raw;
+mod recycle;
+mod refcount;
+mod region;
+#[cfg(all(feature = "registry", not(loom)))]
+mod registry;
+mod reserve;
+mod scratch;
+mod seal;
+#[cfg(feature = "sentinel")]
+mod sentinel;
+mod snapshot;
+#[cfg(not(loom))]
+mod statics;
+mod stats;
+mod sync;
+#[cfg(test)]
+mod tests;
+mod top;
+#[cfg(feature = "trace")]
+mod trace;
+#[cfg(feature = "track")]
+mod track;
+mod tracked;
+
+use emergency::EmergencyReserve;
+use hooks::HookSlot;
+#[cfg(feature = "latency")]
+use latency::Latency;
+use observer::Observers;
+use oom::{OomReport, OomRequest};
+use quarantine::Quarantine;
+use recycle::FreeLists;
+use refcount::PageRefs;
+use region::{align_down, align_up, Region};
+use scratch::Scratch;
+use stats::Counters;
+use top::TopSpans;
+#[cfg(feature = "trace")]
+use trace::Tracer;
+#[cfg(feature = "track")]
+use track::Tracker;
+
+pub use arena::ScratchArena;
+pub use bootstrap::AxallocByteAllocator;
+pub use bounce::{BounceBuffer, BouncePool, BOUNCE_CAPACITY};
+#[cfg(feature = "containers")]
+pub use containers::{AllocHandle, EarlyBox, EarlyVec};
+pub use erased::ErasedEarlyAllocator;
+pub use error::EarlyAllocError;
+#[cfg(all(feature = "ffi", not(loom)))]
+pub use ffi::{
+    early_add_memory, early_alloc, early_alloc_pages, early_free, early_free_pages, ffi_allocator,
+    FFI_PAGE_SIZE,
+};
+pub use flags::AllocFlags;
+pub use guard::AllocGuard;
+pub use handle::RegionHandle;
+#[cfg(feature = "header")]
+pub use header::HEADER_SIZE;
+pub use hooks::Hooks;
+#[cfg(feature = "latency")]
+pub use latency::{CycleCounter, LatencyStats};
+pub use locked::{EarlyAllocatorGuard, LockedEarlyAllocator, StatsSnapshot};
+#[cfg(feature = "model")]
+pub use model::{ModelChecked, ShadowModel, MODEL_CAPACITY};
+#[cfg(feature = "mte")]
+pub use mte::{MemoryTagger, TAG_GRANULE};
+pub use observer::{MemoryObserver, OBSERVER_CAPACITY};
+pub use pagebox::PageBox;
+pub use pgtable::PageTableFrame;
+pub use quarantine::QUARANTINE_CAPACITY;
+pub use raw::{RawRegion, RawState, RAW_STATE_MAGIC, RAW_STATE_VERSION};
+pub use refcount::PAGE_REF_CAPACITY;
+pub use region::{Area, Rebalance};
+#[cfg(all(feature = "registry", not(loom)))]
+pub use registry::{
+    dump_allocators, register_allocator, registered_allocators, RegisteredAllocator,
+    REGISTRY_CAPACITY,
+};
+pub use reserve::Reservation;
+pub use scratch::SCRATCH_CAPACITY;
+pub use seal::SealSummary;
+pub use snapshot::{EarlyAllocatorSnapshot, Epoch, RegionDiff, RegionSnapshot};
+#[cfg(not(loom))]
+pub use statics::StaticEarlyAllocator;
+pub use stats::{CpuStats, SizeClassStats, MAX_CPUS, SIZE_CLASSES};
+pub use top::TOP_SPAN_CAPACITY;
+#[cfg(feature = "trace")]
+pub use trace::{TraceEntry, TraceOp};
+#[cfg(feature = "track")]
+pub use track::{AllocRecord, TRACK_CAPACITY};
+pub use tracked::TrackedFrame;
+
+/// Maximum number of memory regions one allocator can manage.
+///
+/// Regions are tracked by a one-word bitmap, so this is 64 on 64-bit
+/// targets, 32 on 32-bit ones and 16 on 16-bit ones.
+pub const MAX_REGIONS: usize = usize::BITS as usize;
+
+/// Cache line size assumed by [`EarlyAllocator::alloc_cacheline`].
+pub const CACHE_LINE: usize = 64;
+
+/// DMA address mask of a 32-bit device, the one [`AllocFlags::DMA`] uses.
+pub const DMA32_MASK: u64 = 0xFFFF_FFFF;
+
+/// Largest `align_pow2` accepted by page allocations: 1 GiB, the biggest
+/// common huge page, or half the address space on small targets.
+pub const MAX_PAGE_ALIGN: usize = if usize::BITS > 31 {
+    1 << 30
+} else {
+    1 << (usize::BITS - 1)
+};
+
+/// Maximum number of ranges that can be passed to [`EarlyAllocator::exclude`].
+pub const EXCLUDE_CAPACITY: usize = 16;
+
+/// A stack allocated by [`EarlyAllocator::alloc_stack`].
+///
+/// The stack occupies `[bottom, top)` and grows downward towards `guard`, a
+/// hole that is never handed out by the allocator so it can be left unmapped.
+///
+/// The guard is allocated together with the stack as one page run, even
+/// though it is reported to [`Hooks::on_range_freed`]. Free the whole run
+/// at once, guard included, with
+/// `dealloc_pages(guard.start, (top - guard.start) / PAGE_SIZE)`; `guard`
+/// starts at `bottom` if it is empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackDesc {
+    /// Lowest usable address of the stack.
+    pub bottom: usize,
+    /// Initial stack pointer (one past the highest usable address).
+    pub top: usize,
+    /// Guard hole directly below `bottom`. Empty if no guard was requested.
+    pub guard: Range<usize>,
+}
+
+/// Where [`PageAllocator::alloc_pages`] leaves unallocated guard pages
+/// around each run, so that overruns fault instead of corrupting the
+/// neighbouring run.
+///
+/// See [`EarlyAllocator::set_page_guards`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageGuard {
+    /// Runs are packed next to each other.
+    #[default]
+    Off,
+    /// One guard page directly above each run.
+    After,
+    /// One guard page directly above and one directly below each run.
+    Around,
+}
+
+/// What allocations do when the preferred region, the one that served the
+/// previous request, cannot satisfy them.
+///
+/// See [`EarlyAllocator::set_spill_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpillPolicy {
+    /// Fall back to any other region.
+    #[default]
+    Any,
+    /// Fall back only to regions added with the same
+    /// [flags](EarlyAllocator::add_memory_flags), i.e. of the same zone.
+    SameZone,
+    /// Fail instead of falling back.
+    Never,
+}
+
+/// How tight the early heap is, as returned by
+/// [`EarlyAllocator::pressure`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Pressure {
+    /// Plenty of memory is left.
+    #[default]
+    None,
+    /// Optional work, e.g. large caches, should be skipped.
+    Moderate,
+    /// Only essential allocations should be made.
+    Critical,
+}
+
+/// The available bytes and pages below which [`Pressure`] rises.
+///
+/// See [`EarlyAllocator::set_pressure_thresholds`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PressureThresholds {
+    /// Pressure is at least moderate below this many available bytes.
+    pub moderate_bytes: usize,
+    /// Pressure is critical below this many available bytes.
+    pub critical_bytes: usize,
+    /// Pressure is at least moderate below this many available pages.
+    pub moderate_pages: usize,
+    /// Pressure is critical below this many available pages.
+    pub critical_pages: usize,
+}
+
+/// An opaque handle to a page run, returned by
+/// [`EarlyAllocator::alloc_pages_id`].
+///
+/// It remembers the region and extent of the run, so
+/// [`EarlyAllocator::dealloc_pages_id`] does not need the caller to supply
+/// the size again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AllocationId {
+    region: u8,
+    addr: usize,
+    num_pages: usize,
+}
+
+impl AllocationId {
+    /// Start address of the run.
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    /// Number of pages in the run.
+    pub fn num_pages(&self) -> usize {
+        self.num_pages
+    }
+
+    /// Index of the region the run was taken from.
+    pub fn region(&self) -> usize {
+        self.region as usize
+    }
+}
+
+/// Discontiguous page runs returned by
+/// [`EarlyAllocator::alloc_pages_vectored`].
+#[derive(Debug, Clone)]
+pub struct PageExtents {
+    runs: [(usize, usize); MAX_REGIONS],
+    len: usize,
+}
+
+impl PageExtents {
+    /// Number of runs.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no runs.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the runs as `(addr, num_pages)`, in allocation order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.runs[..self.len].iter().copied()
+    }
+}
+
+/// A chunk of guest RAM fenced off by
+/// [`EarlyAllocator::carve_guest_memory`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestMemory {
+    region: u8,
+    range: Range<usize>,
+}
+
+impl GuestMemory {
+    /// Start address of the chunk.
+    pub fn start(&self) -> usize {
+        self.range.start
+    }
+
+    /// Size of the chunk in bytes.
+    pub fn size(&self) -> usize {
+        self.range.len()
+    }
+
+    /// Address range of the chunk.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Index of the region the chunk was taken from.
+    pub fn region(&self) -> usize {
+        self.region as usize
+    }
+}
 
 /// Early memory allocator
 /// Use it before formal bytes-allocator and pages-allocator can work!
@@ -14,18 +345,2625 @@ use allocator::{BaseAllocator, ByteAllocator, PageAllocator};
 ///
 /// For bytes area, 'count' records number of allocations.
 /// When it goes down to ZERO, free bytes-used area.
-/// For pages area, it will never be freed!
+/// Before that, freeing the most recent block rolls `b_pos` back, and other
+/// freed blocks are kept in small per-size-class free lists and reused
+/// before bumping.
+/// For pages area, freeing the lowest run rolls `p_pos` back, and other
+/// freed runs are marked in a per-region page bitmap and reused before
+/// bumping. The `align_pow2` of page allocations is an alignment in bytes,
+/// from `PAGE_SIZE` up to [`MAX_PAGE_ALIGN`]; anything else is rejected.
+/// Regions need not be page-aligned: page runs are carved from the whole
+/// pages inside them only, and the slivers at either edge stay available to
+/// the bytes area.
+///
+/// Up to [`MAX_REGIONS`] such ranges can be managed at once: `init` sets up
+/// the first one and `add_memory` appends more. Allocations start from the
+/// region that served the previous request and fall back to the others,
+/// lower [priorities](EarlyAllocator::set_region_priority) first.
 ///
-pub struct EarlyAllocator;
+/// No method panics on bad input: invalid requests fail with an
+/// [`AllocError`], and invalid frees are ignored. The only panics are
+/// `debug_assert!`s of internal invariants, the watchpoint firing without
+/// an [`on_watch_hit`](Hooks::on_watch_hit) callback in debug builds, and a
+/// corrupted canary with the `canary` feature.
+pub struct EarlyAllocator<const PAGE_SIZE: usize> {
+    regions: [Region; MAX_REGIONS],
+    bitmap: usize,
+    /// Bytes handed to `init` and `add_memory`, before any trimming.
+    raw_bytes: usize,
+    /// Regions taken out of allocation by `disable_region`.
+    disabled: usize,
+    counters: Counters,
+    #[cfg(feature = "axlog")]
+    low_memory: bool,
+    /// Indices of the regions in use, sorted by start address.
+    sorted: [u8; MAX_REGIONS],
+    current_region: usize,
+    rebalance: Rebalance,
+    page_guard: PageGuard,
+    spill: SpillPolicy,
+    pressure: Option<PressureThresholds>,
+    exec_region: Option<usize>,
+    high_align: Option<usize>,
+    min_align: usize,
+    quarantine: Quarantine,
+    free_lists: FreeLists,
+    page_refs: PageRefs,
+    top_spans: TopSpans,
+    exclusions: [(usize, usize); EXCLUDE_CAPACITY],
+    num_exclusions: usize,
+    observers: Observers,
+    hooks: HookSlot,
+    watched: Option<Range<usize>>,
+    reclaimer: Option<fn(Range<usize>) -> bool>,
+    emergency: Option<EmergencyReserve>,
+    initialized: bool,
+    sealed: bool,
+    scratch: Scratch,
+    #[cfg(feature = "trace")]
+    tracer: Option<Tracer>,
+    #[cfg(feature = "trace")]
+    trace_clock: Option<fn() -> u64>,
+    #[cfg(feature = "latency")]
+    latency: Latency,
+    /// Canary positions at the gap edges of each region.
+    #[cfg(feature = "canary")]
+    canaries: [[Option<usize>; 2]; MAX_REGIONS],
+    /// Regions whose last word still holds a sentinel.
+    #[cfg(feature = "sentinel")]
+    sentinels: usize,
+    #[cfg(feature = "track")]
+    tracker: Tracker,
+    #[cfg(feature = "mte")]
+    tagger: Option<&'static dyn MemoryTagger>,
+    #[cfg(feature = "randomize")]
+    rng: Option<fn() -> u64>,
+    #[cfg(feature = "randomize")]
+    max_jitter: usize,
+}
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Rejects a `PAGE_SIZE` that is not a power of two at compile time, so
+    /// that no page computation can divide by zero at run time.
+    const VALID_PAGE_SIZE: () = assert!(PAGE_SIZE.is_power_of_two());
+
+    /// Creates an empty [`EarlyAllocator`].
+    pub const fn new() -> Self {
+        let () = Self::VALID_PAGE_SIZE;
+        Self {
+            regions: [Region::EMPTY; MAX_REGIONS],
+            bitmap: 0,
+            raw_bytes: 0,
+            disabled: 0,
+            counters: Counters::new(),
+            #[cfg(feature = "axlog")]
+            low_memory: false,
+            sorted: [0; MAX_REGIONS],
+            current_region: 0,
+            rebalance: Rebalance::Fixed,
+            page_guard: PageGuard::Off,
+            spill: SpillPolicy::Any,
+            pressure: None,
+            exec_region: None,
+            high_align: None,
+            min_align: 1,
+            quarantine: Quarantine::new(),
+            free_lists: FreeLists::new(),
+            page_refs: PageRefs::new(),
+            top_spans: TopSpans::new(),
+            exclusions: [(0, 0); EXCLUDE_CAPACITY],
+            num_exclusions: 0,
+            observers: Observers::new(),
+            hooks: HookSlot::NONE,
+            watched: None,
+            reclaimer: None,
+            emergency: None,
+            initialized: false,
+            sealed: false,
+            scratch: Scratch::new(),
+            #[cfg(feature = "trace")]
+            tracer: None,
+            #[cfg(feature = "trace")]
+            trace_clock: None,
+            #[cfg(feature = "latency")]
+            latency: Latency::new(),
+            #[cfg(feature = "canary")]
+            canaries: [[None; 2]; MAX_REGIONS],
+            #[cfg(feature = "sentinel")]
+            sentinels: 0,
+            #[cfg(feature = "track")]
+            tracker: Tracker::new(),
+            #[cfg(feature = "mte")]
+            tagger: None,
+            #[cfg(feature = "randomize")]
+            rng: None,
+            #[cfg(feature = "randomize")]
+            max_jitter: 0,
+        }
+    }
+
+    /// Creates an allocator already managing `[start, start + size)`, for a
+    /// `static` on platforms whose RAM layout is known at compile time, so
+    /// no code has to run before the first allocation.
+    ///
+    /// Panics, at compile time when evaluated in a constant, if the range
+    /// is empty or wraps around. Unlike `init`, no sentinel is placed in the
+    /// region.
+    pub const fn with_region(start: usize, size: usize) -> Self {
+        assert!(size != 0, "empty early allocator region");
+        // Like `add_memory_flags`, a region reaching the top of the address
+        // space gives up its last byte.
+        let end = match start.checked_add(size) {
+            Some(end) => end,
+            None => {
+                assert!(
+                    start.wrapping_add(size) == 0,
+                    "early allocator region wraps around"
+                );
+                usize::MAX
+            }
+        };
+        let mut alloc = Self::new();
+        alloc.regions[0] = Region::new(start, end);
+        alloc.bitmap = 1;
+        alloc.raw_bytes = size;
+        alloc.initialized = true;
+        alloc
+    }
+
+    /// Installs a random number source used to perturb the bytes cursor.
+    ///
+    /// Before every byte allocation the cursor is moved forward by a random
+    /// amount in `[0, max_jitter]`, so early allocations do not land at
+    /// predictable addresses. Pass `None` to disable it again.
+    #[cfg(feature = "randomize")]
+    pub fn set_randomizer(&mut self, rng: Option<fn() -> u64>, max_jitter: usize) {
+        self.rng = rng;
+        self.max_jitter = max_jitter;
+    }
+
+    /// Returns the byte allocations that are still live, along with the code
+    /// location that requested each of them.
+    #[cfg(feature = "track")]
+    pub fn live_allocations(&self) -> impl Iterator<Item = &AllocRecord> {
+        self.tracker.iter()
+    }
+
+    /// Iterates over `(range, tag, order index)` for every tracked byte
+    /// allocation still live, e.g. for a debug shell command listing what
+    /// the allocator holds. The order index is [`AllocRecord::seq`].
+    #[cfg(feature = "track")]
+    pub fn iter_live(
+        &self,
+    ) -> impl Iterator<Item = (Range<usize>, Option<&'static str>, u64)> + '_ {
+        self.tracker
+            .iter()
+            .map(|r| (r.addr..r.addr + r.size, r.tag, r.seq))
+    }
+
+    /// Tags the byte allocations made from now on, e.g. with the name of the
+    /// subsystem being brought up, so leak reports can tell who they belong
+    /// to. `None` stops tagging.
+    #[cfg(feature = "track")]
+    pub fn set_alloc_tag(&mut self, tag: Option<&'static str>) {
+        self.tracker.tag = tag;
+    }
+
+    /// Returns the index of the live allocation at `ptr` among all byte
+    /// allocations since `init`, see [`AllocRecord::seq`].
+    #[cfg(feature = "track")]
+    pub fn allocation_index(&self, ptr: NonNull<u8>) -> Option<u64> {
+        let addr = self.untag(ptr.as_ptr() as usize, None);
+        self.tracker.iter().find(|r| r.addr == addr).map(|r| r.seq)
+    }
+
+    /// Starts recording every byte and page allocation and deallocation into
+    /// `buf`, and returns the previous buffer, if any. Entries that do not
+    /// fit are dropped and counted.
+    #[cfg(feature = "trace")]
+    pub fn start_trace(
+        &mut self,
+        buf: &'static mut [TraceEntry],
+    ) -> Option<&'static mut [TraceEntry]> {
+        self.tracer.replace(Tracer::new(buf)).map(Tracer::into_buf)
+    }
+
+    /// Sets the clock that traced entries are timestamped with, e.g. the
+    /// boot timer, so they can be lined up with other boot events. Entries
+    /// recorded without one have a timestamp of `0`.
+    #[cfg(feature = "trace")]
+    pub fn set_trace_clock(&mut self, clock: Option<fn() -> u64>) {
+        self.trace_clock = clock;
+    }
+
+    /// Stops recording and gives the buffer back.
+    #[cfg(feature = "trace")]
+    pub fn stop_trace(&mut self) -> Option<&'static mut [TraceEntry]> {
+        self.tracer.take().map(Tracer::into_buf)
+    }
+
+    /// Returns the entries recorded so far and how many were dropped.
+    #[cfg(feature = "trace")]
+    pub fn trace(&self) -> (&[TraceEntry], usize) {
+        match &self.tracer {
+            Some(tracer) => (tracer.entries(), tracer.dropped()),
+            None => (&[], 0),
+        }
+    }
+
+    /// Replays a recorded trace against this allocator, which should be
+    /// freshly set up like the recording one, e.g. to turn a boot-heap
+    /// corruption report into a deterministic test.
+    ///
+    /// Returns the index of the first allocation whose outcome differs from
+    /// the recorded one. Frees of blocks whose allocation failed are skipped.
+    #[cfg(feature = "trace")]
+    pub fn replay(&mut self, trace: &[TraceEntry]) -> Result<(), usize> {
+        let base = self.trace_base();
+        for (i, entry) in trace.iter().enumerate() {
+            let addr = entry.offset.map(|offset| base.wrapping_add(offset));
+            let granted = match entry.op {
+                TraceOp::Alloc => {
+                    let layout = Layout::from_size_align(entry.size, entry.align).map_err(|_| i)?;
+                    self.alloc(layout).ok().map(|p| p.as_ptr() as usize)
+                }
+                TraceOp::AllocPages => self.alloc_pages(entry.size, entry.align).ok(),
+                TraceOp::Dealloc => {
+                    let layout = Layout::from_size_align(entry.size, entry.align).map_err(|_| i)?;
+                    if let Some(ptr) = addr.and_then(|addr| NonNull::new(addr as *mut u8)) {
+                        self.dealloc(ptr, layout);
+                    }
+                    continue;
+                }
+                TraceOp::DeallocPages => {
+                    if let Some(addr) = addr {
+                        self.dealloc_pages(addr, entry.size);
+                    }
+                    continue;
+                }
+            };
+            if granted != addr {
+                return Err(i);
+            }
+        }
+        Ok(())
+    }
+
+    /// Address trace offsets are relative to: the start of region 0.
+    #[cfg(feature = "trace")]
+    fn trace_base(&self) -> usize {
+        self.region(0).map_or(0, |r| r.start)
+    }
+
+    #[cfg(feature = "trace")]
+    fn record(&mut self, op: TraceOp, size: usize, align: usize, addr: Option<usize>) {
+        let base = self.trace_base();
+        if let Some(tracer) = &mut self.tracer {
+            tracer.push(TraceEntry {
+                op,
+                size,
+                align,
+                offset: addr.map(|addr| addr.wrapping_sub(base)),
+                timestamp: self.trace_clock.map_or(0, |clock| clock()),
+            });
+        }
+    }
+
+    /// Returns how many live byte allocations are missing from
+    /// [`live_allocations`](Self::live_allocations) because the table was full.
+    #[cfg(feature = "track")]
+    pub fn untracked_allocations(&self) -> usize {
+        self.tracker.untracked()
+    }
+
+    /// Returns how many byte allocations of each size class have been made
+    /// since `init`.
+    pub fn size_class_stats(&self) -> &SizeClassStats {
+        self.counters.size_classes()
+    }
+
+    /// Sets the function returning the id of the calling CPU, so that
+    /// allocations are counted per CPU; without one they are all counted for
+    /// CPU 0. CPUs from [`MAX_CPUS`] on are not counted.
+    ///
+    /// With the `no-stats` feature nothing is counted.
+    pub fn set_cpu_id(&mut self, cpu_id: Option<fn() -> usize>) {
+        self.counters.set_cpu_id(cpu_id);
+    }
+
+    /// Sets the counter that [`ByteAllocator::alloc`] and
+    /// [`PageAllocator::alloc_pages`] are timed with; nothing is timed
+    /// without one.
+    #[cfg(feature = "latency")]
+    pub fn set_cycle_counter(&mut self, counter: Option<&'static dyn CycleCounter>) {
+        self.latency.set_counter(counter);
+    }
+
+    /// Returns the latency of [`ByteAllocator::alloc`] since `init`, failed
+    /// calls included.
+    #[cfg(feature = "latency")]
+    pub fn alloc_latency(&self) -> LatencyStats {
+        self.latency.bytes
+    }
+
+    /// Returns the latency of [`PageAllocator::alloc_pages`] since `init`.
+    #[cfg(feature = "latency")]
+    pub fn alloc_pages_latency(&self) -> LatencyStats {
+        self.latency.pages
+    }
+
+    /// Returns for how long the lock of a [`LockedEarlyAllocator`] around this
+    /// allocator was held, from acquiring it to releasing it, since `init`.
+    /// Compared with [`alloc_latency`](Self::alloc_latency) for guards taken
+    /// for one allocation, it shows the cost of the wrapper.
+    #[cfg(feature = "latency")]
+    pub fn lock_hold_latency(&self) -> LatencyStats {
+        self.latency.locked
+    }
+
+    /// Returns the allocations made by CPU `cpu` since `init`, or `None` if
+    /// it is not counted.
+    pub fn cpu_stats(&self, cpu: usize) -> Option<CpuStats> {
+        self.counters.cpu_stats(cpu)
+    }
+
+    /// Returns the allocations made by all counted CPUs since `init`.
+    pub fn cpu_stats_total(&self) -> CpuStats {
+        self.counters.cpu_stats_total()
+    }
+
+    /// Records a granted byte block in the statistics and, with the `track`
+    /// feature, in the table of live allocations.
+    #[track_caller]
+    fn track_alloc(&mut self, _addr: usize, _layout: Layout) {
+        self.counters.byte_block(_layout.size());
+        #[cfg(feature = "track")]
+        {
+            let seq = self.tracker.next_seq();
+            self.tracker.insert(AllocRecord {
+                addr: _addr,
+                size: _layout.size(),
+                align: _layout.align(),
+                caller: core::panic::Location::caller(),
+                tag: self.tracker.tag,
+                seq,
+            });
+        }
+    }
+
+    /// Slides the live byte blocks of every region down over the holes left
+    /// by freed ones, and calls `relocate(old, new, len)` for each moved
+    /// block. Quarantined blocks are released first. Returns the number of
+    /// bytes given back to the free gaps.
+    ///
+    /// Every live block must be tracked, and no memory tagger may be
+    /// installed; otherwise this fails with [`AllocError::InvalidParam`].
+    /// Blocks from [`alloc_from_top`](Self::alloc_from_top) never move.
+    ///
+    /// # Safety
+    ///
+    /// A moved block must no longer be accessed through its old address:
+    /// `relocate` has to fix up every reference to it.
+    #[cfg(feature = "track")]
+    pub unsafe fn compact<F>(&mut self, mut relocate: F) -> AllocResult<usize>
+    where
+        F: FnMut(usize, usize, usize),
+    {
+        #[cfg(feature = "mte")]
+        if self.tagger.is_some() {
+            return Err(AllocError::InvalidParam);
+        }
+        if self.tracker.untracked() > 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        self.flush_quarantine();
+        self.tracker.sort_by_addr();
+        let mut reclaimed = 0;
+        for idx in self.region_indices() {
+            let region = &mut self.regions[idx];
+            self.free_lists.purge(region.start..region.b_pos);
+            let mut cursor = region.start;
+            for record in self.tracker.iter_mut() {
+                if record.addr < region.start || record.addr >= region.b_pos {
+                    continue;
+                }
+                // `cursor` only ever lags behind `record.addr`.
+                let new = align_up(cursor, record.align).unwrap_or(record.addr);
+                if new < record.addr {
+                    // SAFETY: both ranges lie in the bytes area of this region,
+                    // and the caller takes care of references to the old one.
+                    unsafe {
+                        core::ptr::copy(record.addr as *const u8, new as *mut u8, record.size);
+                    }
+                    self.hooks.freed(record.addr, record.size);
+                    self.hooks.allocated(new, record.size);
+                    relocate(record.addr, new, record.size);
+                    record.addr = new;
+                }
+                cursor = record.addr + record.size;
+            }
+            reclaimed += region.b_pos - cursor;
+            region.b_pos = cursor;
+        }
+        Ok(reclaimed)
+    }
+
+    /// Installs instrumentation callbacks, replacing the previous ones.
+    ///
+    /// With the `no-stats` feature the hooks are ignored.
+    pub fn set_hooks(&mut self, hooks: Hooks) {
+        #[cfg(not(feature = "no-stats"))]
+        {
+            self.hooks = hooks;
+        }
+        #[cfg(feature = "no-stats")]
+        let _ = hooks;
+    }
+
+    /// Sets the callback that prints a diagnostic dump when an allocation
+    /// runs out of memory; see [`Hooks::on_oom`].
+    pub fn set_oom_dump(&mut self, dump: Option<fn(&dyn fmt::Display)>) {
+        #[cfg(not(feature = "no-stats"))]
+        {
+            self.hooks.on_oom = dump;
+        }
+        #[cfg(feature = "no-stats")]
+        let _ = dump;
+    }
+
+    /// Installs the memory tagging hooks used for byte allocations.
+    ///
+    /// Freed blocks are retagged so that stale pointers fault on access.
+    #[cfg(feature = "mte")]
+    pub fn set_tagger(&mut self, tagger: Option<&'static dyn MemoryTagger>) {
+        self.tagger = tagger;
+    }
+
+    /// Applies the minimum alignment to `layout`, and pads it to whole tag
+    /// granules if a tagger is installed.
+    fn tagged_layout(&self, layout: Layout) -> AllocResult<Layout> {
+        let layout = layout
+            .align_to(self.min_align)
+            .map_err(|_| AllocError::InvalidParam)?;
+        #[cfg(feature = "mte")]
+        if self.tagger.is_some() {
+            return Layout::from_size_align(layout.size(), layout.align().max(TAG_GRANULE))
+                .map(|l| l.pad_to_align())
+                .map_err(|_| AllocError::InvalidParam);
+        }
+        Ok(layout)
+    }
+
+    /// Tags a fresh byte block at `pos` and returns the pointer to hand out.
+    fn tag(&self, pos: usize, _size: usize) -> usize {
+        #[cfg(feature = "mte")]
+        if let Some(tagger) = self.tagger {
+            return tagger.tag_range(pos, _size);
+        }
+        pos
+    }
+
+    /// Strips the tag from `ptr`, retagging the freed block if `size` is given.
+    fn untag(&self, ptr: usize, _size: Option<usize>) -> usize {
+        #[cfg(feature = "mte")]
+        if let Some(tagger) = self.tagger {
+            let pos = tagger.untag(ptr);
+            if let Some(size) = _size {
+                tagger.tag_range(pos, align_up(size, TAG_GRANULE).unwrap_or(size));
+            }
+            return pos;
+        }
+        ptr
+    }
+
+    /// Takes an extra reference to the allocated page at `page`, e.g. for a
+    /// shared zero page, and returns its new reference count.
+    ///
+    /// An allocated page starts with a count of one. Only up to
+    /// [`PAGE_REF_CAPACITY`] pages can be shared at once.
+    pub fn get_page(&mut self, page: usize) -> AllocResult<usize> {
+        self.check_page(page)?;
+        let count = self.page_refs.entry(page).ok_or(AllocError::NoMemory)?;
+        if *count == 0 {
+            return Err(AllocError::NotAllocated);
+        }
+        *count += 1;
+        Ok(*count)
+    }
+
+    /// Drops a reference to the allocated page at `page` and returns its new
+    /// reference count.
+    ///
+    /// When the count reaches zero the page is freed like with
+    /// [`dealloc_pages`](PageAllocator::dealloc_pages), and can be handed
+    /// out again.
+    pub fn put_page(&mut self, page: usize) -> AllocResult<usize> {
+        self.check_page(page)?;
+        let count = self.page_refs.entry(page).ok_or(AllocError::NoMemory)?;
+        *count -= 1;
+        let count = *count;
+        if count == 0 {
+            self.page_refs.retain(|p| p != page);
+            self.dealloc_pages(page, 1);
+        }
+        Ok(count)
+    }
+
+    /// Returns the reference count of the allocated page at `page`.
+    pub fn page_refcount(&self, page: usize) -> AllocResult<usize> {
+        self.check_page(page)?;
+        Ok(self.page_refs.count(page).unwrap_or(1))
+    }
+
+    /// Checks that `page` is a page-aligned address in a pages area, and
+    /// that it belongs to a page run rather than to byte blocks taken from
+    /// the top or to the bitmap of freed pages.
+    fn check_page(&self, page: usize) -> AllocResult {
+        if page % PAGE_SIZE != 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let idx = self.region_of(page).ok_or(AllocError::InvalidParam)?;
+        let region = &self.regions[idx];
+        if page < region.p_pos
+            || region.is_freed(page, PAGE_SIZE)
+            || self.top_spans.overlaps(page, page + 1)
+            || region.frames_pages(PAGE_SIZE).contains(&page)
+        {
+            return Err(AllocError::NotAllocated);
+        }
+        Ok(())
+    }
+
+    /// Watches `range`: any allocation returning memory that overlaps it
+    /// invokes [`Hooks::on_watch_hit`], or panics in debug builds if that hook
+    /// is not installed. Replaces the previously watched range.
+    ///
+    /// With the `no-stats` feature there are no hooks to report a hit to,
+    /// so nothing is watched and this fails with [`AllocError::InvalidParam`].
+    pub fn watch(&mut self, range: Range<usize>) -> AllocResult {
+        #[cfg(not(feature = "no-stats"))]
+        {
+            self.watched = Some(range);
+            Ok(())
+        }
+        #[cfg(feature = "no-stats")]
+        {
+            let _ = range;
+            Err(AllocError::InvalidParam)
+        }
+    }
+
+    /// Stops watching the range set by [`watch`](Self::watch).
+    pub fn unwatch(&mut self) {
+        self.watched = None;
+    }
+
+    /// Reports a freshly handed out range, aligned to `align`, to the hooks
+    /// and the watchpoint.
+    fn on_allocated(&mut self, start: usize, len: usize, align: usize) {
+        self.debug_check_granted(start, len, align);
+        self.hooks.allocated(start, len);
+        if let Some(watched) = &self.watched {
+            if watched.start < start + len && start < watched.end {
+                self.hooks.watch_hit(start, len);
+            }
+        }
+        #[cfg(feature = "axlog")]
+        self.check_low_memory();
+        #[cfg(any(feature = "canary", feature = "sentinel"))]
+        if let Some(idx) = self.region_of(start) {
+            #[cfg(feature = "sentinel")]
+            self.retire_sentinel(idx, start, len);
+            #[cfg(feature = "canary")]
+            self.place_canaries(idx);
+        }
+    }
+
+    /// Checks in debug builds that a granted range is aligned, lies within a
+    /// single region, and stays clear of the free gap between the cursors.
+    fn debug_check_granted(&self, pos: usize, size: usize, align: usize) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        debug_assert!(
+            pos % align == 0,
+            "block at {:#x} not aligned to {:#x}",
+            pos,
+            align
+        );
+        if size == 0 {
+            return;
+        }
+        let idx = self.region_of(pos);
+        debug_assert!(
+            idx.is_some() && idx == self.region_of(pos + size - 1),
+            "block [{:#x}, +{:#x}) not within one region",
+            pos,
+            size
+        );
+        if let Some(idx) = idx {
+            let r = &self.regions[idx];
+            debug_assert!(
+                pos + size <= r.b_pos || pos >= r.p_pos,
+                "block [{:#x}, +{:#x}) overlaps the free gap [{:#x}, {:#x})",
+                pos,
+                size,
+                r.b_pos,
+                r.p_pos
+            );
+        }
+    }
+
+    /// Warns once when less than 1/16 of the memory is left free, and again
+    /// after it has recovered.
+    #[cfg(feature = "axlog")]
+    fn check_low_memory(&mut self) {
+        let total = self.total_bytes();
+        let available = self.available_bytes();
+        let low = available < total / 16;
+        if low && !self.low_memory {
+            early_warn!(
+                "early allocator: nearly exhausted, {} of {} bytes left",
+                available,
+                total
+            );
+        }
+        self.low_memory = low;
+    }
+
+    /// Sets how many freed byte blocks are held back before their memory can
+    /// be reused, to catch use-after-free during bring-up.
+    ///
+    /// The depth is capped at [`QUARANTINE_CAPACITY`]; `0` disables the
+    /// quarantine. Shrinking the depth releases the excess blocks right away.
+    pub fn set_quarantine_depth(&mut self, depth: usize) {
+        self.quarantine.set_depth(depth);
+        while self.quarantine.len() > self.quarantine.depth() {
+            if let Some((pos, size)) = self.quarantine.pop() {
+                self.release_bytes(pos, size);
+            }
+        }
+    }
+
+    /// Releases every block currently held in quarantine.
+    pub fn flush_quarantine(&mut self) {
+        while let Some((pos, size)) = self.quarantine.pop() {
+            self.release_bytes(pos, size);
+        }
+    }
+
+    /// Gives a freed byte block back: either the whole bytes area is reset
+    /// because it was the last live block, the bytes cursor is rolled back
+    /// because it was the most recent one, or the block is recycled.
+    fn release_bytes(&mut self, pos: usize, size: usize) {
+        let Some(idx) = self.region_of(pos) else {
+            return;
+        };
+        let region = &mut self.regions[idx];
+        // Blocks above `b_pos` come from `alloc_from_top` and are never freed.
+        if pos >= region.b_pos {
+            return;
+        }
+        region.dealloc_bytes();
+        if region.count == 0 {
+            self.free_lists.purge(region.start..region.end);
+        } else if pos + size == region.b_pos {
+            region.b_pos = pos;
+        } else {
+            // SAFETY: the block lies in our bytes area and was just freed.
+            unsafe { self.free_lists.push(pos, size) };
+        }
+    }
+
+    /// Returns the region of the recycled block that would be reused for
+    /// `layout`, if there is one and allocations may currently be served
+    /// from that region.
+    fn recyclable(&self, layout: Layout) -> Option<usize> {
+        let (_, pos) = self.free_lists.peek(layout)?;
+        let idx = self.region_of(pos)?;
+        (self.scan_mask() & (1 << idx) != 0).then_some(idx)
+    }
+
+    /// Reuses a recycled block for `layout`, if one is available. A block
+    /// of a region left out of [`scan_order`](Self::scan_order), e.g. a
+    /// disabled one, stays on its list.
+    fn alloc_recycled(&mut self, layout: Layout) -> Option<(usize, usize)> {
+        let idx = self.recyclable(layout)?;
+        let (pos, size) = self.free_lists.pop(layout)?;
+        self.regions[idx].count += 1;
+        Some((pos, size))
+    }
+
+    /// Allocates a byte block, returning its (tagged) address and the number
+    /// of bytes actually granted.
+    #[track_caller]
+    fn alloc_block(&mut self, layout: Layout) -> AllocResult<(usize, usize)> {
+        self.check_initialized()?;
+        self.check_sealed()?;
+        let layout = self.tagged_layout(layout)?;
+        let (pos, granted) = match self.alloc_recycled(layout) {
+            Some(block) => block,
+            None if self.high_align.is_some_and(|align| layout.align() >= align) => {
+                let pos = self
+                    .alloc_high_aligned(layout)
+                    .map_err(|err| self.report_oom(err, OomRequest::Bytes(layout)))?;
+                (pos, layout.size())
+            }
+            None => {
+                let skip = self.jitter();
+                let rebalance = self.rebalance;
+                let pos = self
+                    .alloc_in_regions(|r| r.alloc_bytes(layout, skip, rebalance))
+                    .map_err(|err| self.report_oom(err, OomRequest::Bytes(layout)))?;
+                (pos, layout.size())
+            }
+        };
+        self.on_allocated(pos, layout.size(), layout.align());
+        self.track_alloc(pos, layout);
+        Ok((self.tag(pos, layout.size()), granted))
+    }
+
+    /// Returns the number of pages in use: counted exactly, or estimated from
+    /// the size of the pages areas, top byte blocks left out, with the
+    /// `no-stats` feature.
+    fn live_pages(&self) -> usize {
+        self.counters.used_pages().unwrap_or_else(|| {
+            self.regions()
+                .map(|r| {
+                    let top = self.top_spans.bytes_in(r.p_pos..r.end);
+                    (r.end - r.p_pos - r.freed).saturating_sub(top) / PAGE_SIZE
+                })
+                .sum()
+        })
+    }
+
+    /// Passes a report of the failed `request` to [`Hooks::on_oom`] if `err`
+    /// means the allocator ran out of memory, and returns `err`.
+    fn report_oom(&self, err: AllocError, request: OomRequest) -> AllocError {
+        if matches!(err, AllocError::NoMemory | AllocError::MemoryOverlap) {
+            let report = OomReport {
+                alloc: self,
+                request,
+            };
+            early_warn!("{}", report);
+            self.hooks.oom(&report);
+            #[cfg(feature = "sentinel")]
+            if let Err(_err) = self.check_sentinels() {
+                early_warn!("early allocator: {:?} found on out of memory", _err);
+            }
+        }
+        err
+    }
+
+    /// Serves a highly aligned byte block from the top end of a region, and
+    /// accounts for the padding this saves compared to bumping `b_pos`.
+    fn alloc_high_aligned(&mut self, layout: Layout) -> AllocResult<usize> {
+        let (pos, before) = self.alloc_top_block(layout)?;
+        let bottom_pad = align_up(before.b_pos, layout.align()).map_or(0, |pos| pos - before.b_pos);
+        let top_pad = before.p_pos - layout.size() - pos;
+        self.counters
+            .padding_saved(bottom_pad.saturating_sub(top_pad));
+        Ok(pos)
+    }
+
+    /// Takes a byte block from the top end of a region, and records the
+    /// pages it takes from the pages area so that they are never mistaken
+    /// for a page run. Returns the block and the region as it was before.
+    ///
+    /// Fails with [`AllocError::NoMemory`] if the pages cannot be recorded
+    /// because [`TOP_SPAN_CAPACITY`] separate stretches are in use.
+    fn alloc_top_block(&mut self, layout: Layout) -> AllocResult<(usize, Region)> {
+        let rebalance = self.rebalance;
+        let mut before = Region::EMPTY;
+        let pos = self.alloc_in_regions(|r| {
+            let saved = *r;
+            let pos = r.alloc_top(layout, rebalance)?;
+            before = saved;
+            Ok(pos)
+        })?;
+        let end = align_up(before.p_pos, PAGE_SIZE).unwrap_or(before.end);
+        if !self
+            .top_spans
+            .insert(before.start..before.end, align_down(pos, PAGE_SIZE), end)
+        {
+            if let Some(idx) = self.region_of(pos) {
+                self.regions[idx] = before;
+            }
+            return Err(AllocError::NoMemory);
+        }
+        Ok((pos, before))
+    }
+
+    /// Byte allocations aligned to at least `align` (e.g. page or cache-line
+    /// aligned ones) are served from the top end of a region, next to the
+    /// pages area, instead of bumping the bytes cursor. `None` disables this.
+    ///
+    /// Interleaving such allocations with small ones then stops wasting
+    /// padding; see [`saved_padding`](Self::saved_padding). Like
+    /// [`alloc_from_top`](Self::alloc_from_top) blocks, they are never freed.
+    pub fn set_high_align_threshold(&mut self, align: Option<usize>) {
+        self.high_align = align;
+    }
+
+    /// Sets the minimum alignment of every byte allocation, e.g. 16 or
+    /// [`CACHE_LINE`] bytes, so that DMA descriptors and lock structures
+    /// never share a cache line by accident. It also counts towards the
+    /// [high-alignment threshold](Self::set_high_align_threshold).
+    ///
+    /// Fails with [`AllocError::InvalidParam`] if `align` is not a power of
+    /// two.
+    pub fn set_min_align(&mut self, align: usize) -> AllocResult {
+        if !align.is_power_of_two() {
+            return Err(AllocError::InvalidParam);
+        }
+        self.min_align = align;
+        Ok(())
+    }
+
+    /// Allocates `size` bytes on their own cache lines: the block is
+    /// aligned to [`CACHE_LINE`] and padded to a whole number of lines.
+    ///
+    /// Free it with a layout of `size` bytes aligned to [`CACHE_LINE`].
+    #[track_caller]
+    pub fn alloc_cacheline(&mut self, size: usize) -> AllocResult<NonNull<u8>> {
+        let layout = Layout::from_size_align(size, CACHE_LINE)
+            .map_err(|_| AllocError::InvalidParam)?
+            .pad_to_align();
+        self.alloc(layout)
+    }
+
+    /// Allocates `n` blocks of `layout` in a single bump, e.g. one per CPU,
+    /// and iterates over them in address order.
+    ///
+    /// The blocks are laid out back to back, each padded to the alignment of
+    /// `layout`, and may be freed one by one with `layout`.
+    #[track_caller]
+    pub fn alloc_many(
+        &mut self,
+        layout: Layout,
+        n: usize,
+    ) -> AllocResult<impl Iterator<Item = NonNull<u8>>> {
+        if n == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let one = self.tagged_layout(layout)?.pad_to_align();
+        let stride = one.size();
+        let total = stride
+            .checked_mul(n)
+            .and_then(|size| Layout::from_size_align(size, one.align()).ok())
+            .ok_or(AllocError::InvalidParam)?;
+        let (base, _) = self.alloc_block(total)?;
+        Ok((0..n).filter_map(move |i| NonNull::new((base + i * stride) as *mut u8)))
+    }
+
+    /// Returns how many bytes of alignment padding have been avoided by
+    /// serving highly aligned allocations from the top end.
+    pub fn saved_padding(&self) -> usize {
+        self.counters.saved_padding()
+    }
+
+    /// Like [`ByteAllocator::alloc`], but returns the whole granted block,
+    /// which may be larger than requested (e.g. a recycled block from a
+    /// bigger size class). The caller may use all of it and must pass a
+    /// layout of the requested size, or of the granted one, to `dealloc`.
+    #[track_caller]
+    pub fn alloc_excess(&mut self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let (pos, granted) = self.alloc_block(layout)?;
+        let ptr = NonNull::new(pos as *mut u8).ok_or(AllocError::NoMemory)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, granted))
+    }
+
+    fn jitter(&self) -> usize {
+        #[cfg(feature = "randomize")]
+        if let Some(rng) = self.rng {
+            let rnd = rng() as usize;
+            return match self.max_jitter.checked_add(1) {
+                Some(bound) => rnd % bound,
+                None => rnd,
+            };
+        }
+        0
+    }
 
-impl EarlyAllocator {
+    /// Takes a snapshot of the bookkeeping (cursors and counters of every
+    /// region), to be compared with [`EarlyAllocatorSnapshot::diff`].
+    pub fn snapshot(&self) -> EarlyAllocatorSnapshot {
+        let mut regions = [None; MAX_REGIONS];
+        for idx in self.region_indices() {
+            regions[idx] = Some(RegionSnapshot::from(&self.regions[idx]));
+        }
+        EarlyAllocatorSnapshot {
+            regions,
+            current_region: self.current_region,
+        }
+    }
+
+    /// Marks the current state, so that [`reset_to_epoch`](Self::reset_to_epoch)
+    /// can later free everything allocated after this point.
+    pub fn mark_epoch(&self) -> Epoch {
+        let mut frames = 0;
+        for idx in self.region_indices() {
+            if self.regions[idx].frames.is_some() {
+                frames |= 1 << idx;
+            }
+        }
+        Epoch {
+            snapshot: self.snapshot(),
+            frames,
+            used_pages: self.live_pages(),
+        }
+    }
+
+    /// Frees every allocation made after `epoch` was marked, in all regions,
+    /// e.g. when a boot stage is done and its allocations become garbage.
+    /// Regions added since then are emptied. Quarantined blocks are released
+    /// first.
+    ///
+    /// Allocations made before the marker stay live. Those freed after it
+    /// are leaked rather than reclaimed. Memory owned elsewhere, like the
+    /// buffers of a [`BouncePool`] created since, is freed as well and must
+    /// not be used afterwards, while an emergency reserve or scratch chunks
+    /// taken since are dropped. Fails with [`AllocError::InvalidParam`] if a
+    /// region was resized since the marker.
+    pub fn reset_to_epoch(&mut self, epoch: &Epoch) -> AllocResult {
+        let changed = self.region_indices().any(|idx| {
+            let r = &self.regions[idx];
+            epoch.snapshot.regions[idx].is_some_and(|s| s.start != r.start || s.end != r.end)
+        });
+        if changed {
+            return Err(AllocError::InvalidParam);
+        }
+        self.flush_quarantine();
+        for idx in self.region_indices() {
+            let region = &mut self.regions[idx];
+            match epoch.snapshot.regions[idx] {
+                Some(s) => {
+                    region.b_pos = s.b_pos;
+                    region.p_pos = s.p_pos;
+                    region.count = s.count;
+                    region.boundary = s.boundary;
+                    if epoch.frames & (1 << idx) == 0 {
+                        region.frames = None;
+                    }
+                }
+                None => {
+                    *region = Region {
+                        flags: region.flags,
+                        priority: region.priority,
+                        ..Region::new(region.start, region.end)
+                    };
+                }
+            }
+            region.sync_freed(PAGE_SIZE);
+            self.top_spans
+                .cut_below(region.start..region.end, region.p_pos, PAGE_SIZE);
+            let gap = region.b_pos..region.p_pos;
+            self.free_lists.purge(gap.start..region.end);
+            self.page_refs.retain(|page| !gap.contains(&page));
+            #[cfg(feature = "track")]
+            self.tracker.retain(|r| !gap.contains(&r.addr));
+        }
+        self.counters.set_used_pages(epoch.used_pages);
+        if self.bitmap & (1 << epoch.snapshot.current_region) != 0 {
+            self.current_region = epoch.snapshot.current_region;
+        }
+        self.drop_stale_reserve();
+        self.drop_stale_scratch();
+        Ok(())
+    }
+
+    /// Reports whether `alloc_pages(num_pages, align_pow2)` would currently
+    /// succeed, without allocating anything.
+    ///
+    /// Returns the index of the region the run would come from, or `None` if
+    /// the request cannot be satisfied (or is invalid). Guard pages are
+    /// accounted for, and nothing fits before `init` or once sealed.
+    pub fn contiguous_pages_available(&self, num_pages: usize, align_pow2: usize) -> Option<usize> {
+        if !self.initialized || self.sealed {
+            return None;
+        }
+        Self::check_page_align(align_pow2).ok()?;
+        let size = num_pages.checked_mul(PAGE_SIZE)?;
+        let take = self.page_run_taker(size, align_pow2, self.rebalance).ok()?;
+        self.scan_order().find(|&idx| {
+            let mut region = self.regions[idx];
+            take(&mut region).is_ok()
+        })
+    }
+
+    /// Reports whether `alloc(layout)` would currently succeed, without
+    /// allocating anything.
+    ///
+    /// Returns the index of the region the block would come from, or `None`
+    /// if the request cannot be satisfied (or is invalid), which is always
+    /// the case before `init` and once sealed. With randomized placement a
+    /// block that only just fits may still be refused.
+    pub fn can_allocate(&self, layout: Layout) -> Option<usize> {
+        if !self.initialized || self.sealed {
+            return None;
+        }
+        let layout = self.tagged_layout(layout).ok()?;
+        if let Some(idx) = self.recyclable(layout) {
+            return Some(idx);
+        }
+        let high = self.high_align.is_some_and(|align| layout.align() >= align);
+        self.scan_order().find(|&idx| {
+            let mut region = self.regions[idx];
+            if high {
+                region.alloc_top(layout, self.rebalance).is_ok()
+            } else {
+                region.alloc_bytes(layout, 0, self.rebalance).is_ok()
+            }
+        })
+    }
+
+    /// Returns the largest free gap between the bytes and pages areas over
+    /// all regions, i.e. the biggest unaligned block that can still be served.
+    pub fn largest_gap(&self) -> usize {
+        self.regions()
+            .map(Region::available_bytes)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Iterates over every currently unallocated `[addr, addr + len)` span,
+    /// region by region in address order.
+    ///
+    /// Each region yields its recycled free-list blocks, then the gap between
+    /// its bytes and pages areas if it is not empty, then its freed page runs.
+    /// Quarantined blocks are not free yet and are left out.
+    pub fn free_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.sorted_indices().iter().flat_map(move |&idx| {
+            let r = &self.regions[idx as usize];
+            let recycled = self
+                .free_lists
+                .iter()
+                .filter(|&(pos, _)| r.contains(pos))
+                .map(|(pos, size)| pos..pos + size);
+            let gap = (r.b_pos < r.p_pos).then_some(r.b_pos..r.p_pos);
+            recycled.chain(gap).chain(r.freed_runs(PAGE_SIZE))
+        })
+    }
+
+    /// Iterates over `(region index, used percentage)` for every region.
+    pub fn utilization(&self) -> impl Iterator<Item = (usize, u8)> + '_ {
+        self.region_indices().map(|idx| {
+            let r = &self.regions[idx];
+            (idx, (r.used_bytes() as u128 * 100 / r.size() as u128) as u8)
+        })
+    }
+
+    /// Returns the most utilized region and its used percentage, if any.
+    pub fn worst_region(&self) -> Option<(usize, u8)> {
+        self.utilization().max_by_key(|&(_, percent)| percent)
+    }
+
+    /// Returns the fragmentation of region `idx` in percent, computed as
+    /// `1 - largest_free / total_free` over its free gap and recycled blocks.
+    ///
+    /// `0` means all free memory is one contiguous block; a fully used region
+    /// has no fragmentation either.
+    pub fn region_fragmentation(&self, idx: usize) -> AllocResult<u8> {
+        self.region(idx)?;
+        let (total, largest) = self.free_extents(Some(idx));
+        Ok(fragmentation(total, largest))
+    }
+
+    /// Returns the fragmentation over all regions in percent, like
+    /// [`region_fragmentation`](Self::region_fragmentation). A high figure
+    /// means [`available_bytes`](ByteAllocator::available_bytes) overstates
+    /// the largest request that can succeed.
+    pub fn fragmentation(&self) -> u8 {
+        let (total, largest) = self.free_extents(None);
+        fragmentation(total, largest)
+    }
+
+    /// Checks the bookkeeping of every region, and with the `sentinel`
+    /// feature that nothing wrote past the end of a region.
+    ///
+    /// Returns the first inconsistency found as
+    /// [`EarlyAllocError::Corrupted`].
+    pub fn verify(&self) -> Result<(), EarlyAllocError> {
+        for idx in self.region_indices() {
+            let r = &self.regions[idx];
+            if !(r.start <= r.b_pos && r.b_pos <= r.p_pos && r.p_pos <= r.end) {
+                return Err(EarlyAllocError::Corrupted {
+                    region: idx,
+                    addr: r.b_pos,
+                });
+            }
+        }
+        #[cfg(feature = "sentinel")]
+        self.check_sentinels()?;
+        Ok(())
+    }
+
+    /// Returns the number of bytes handed to `init` and `add_memory`, before
+    /// alignment trimming and exclusions; ranges given back by
+    /// [`split_region`](Self::split_region) or
+    /// [`withdraw_tail`](Self::withdraw_tail) no longer count.
+    pub fn raw_bytes(&self) -> usize {
+        self.raw_bytes
+    }
+
+    /// Returns the number of bytes the regions can actually serve, i.e.
+    /// [`raw_bytes`](Self::raw_bytes) minus what was trimmed or excluded.
+    pub fn usable_bytes(&self) -> usize {
+        self.total_bytes()
+    }
+
+    /// Sums the free memory of region `idx` (or of all regions) and finds its
+    /// largest contiguous block.
+    fn free_extents(&self, idx: Option<usize>) -> (usize, usize) {
+        let mut total = 0;
+        let mut largest = 0;
+        for i in self.region_indices() {
+            if idx.is_some_and(|idx| idx != i) {
+                continue;
+            }
+            let r = &self.regions[i];
+            total += r.available_bytes();
+            largest = largest.max(r.available_bytes());
+            for (pos, size) in self.free_lists.iter() {
+                if r.contains(pos) {
+                    total += size;
+                    largest = largest.max(size);
+                }
+            }
+        }
+        (total, largest)
+    }
+
+    /// Carves a contiguous chunk of `bytes` (rounded up to whole pages) out of
+    /// the page area and returns a child allocator managing it.
+    ///
+    /// The child is fully independent from `self`: dropping it discards all
+    /// of its allocations at once, but the chunk is not given back to the
+    /// parent unless it is freed with `dealloc_pages`.
+    pub fn carve(&mut self, bytes: usize) -> AllocResult<Self> {
+        if bytes == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let num_pages = bytes.div_ceil(PAGE_SIZE);
+        let start = self.alloc_pages(num_pages, PAGE_SIZE)?;
+        let mut child = Self::new();
+        child.init(start, num_pages * PAGE_SIZE);
+        child.hooks = self.hooks;
+        Ok(child)
+    }
+
+    /// Fences off a chunk of `size` bytes (rounded up to whole pages) aligned
+    /// to `align`, e.g. 2 MiB for guest RAM backed by huge pages, so that
+    /// later allocations never grow into it.
+    ///
+    /// The chunk is taken from the pages area of a single region in one step;
+    /// on failure nothing is reserved. It stays allocated until handed back
+    /// with `dealloc_pages`.
+    pub fn carve_guest_memory(&mut self, size: usize, align: usize) -> AllocResult<GuestMemory> {
+        if size == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let num_pages = size.div_ceil(PAGE_SIZE);
+        let start = self.alloc_pages(num_pages, align.max(PAGE_SIZE))?;
+        Ok(GuestMemory {
+            region: self.current_region as u8,
+            range: start..start + num_pages * PAGE_SIZE,
+        })
+    }
+
+    /// Splits region `idx` at the page-aligned address `at`.
+    ///
+    /// `self` keeps `[start, at)` and gives up `[at, end)`, which is returned
+    /// so it can be handed to another allocator. The high half must not have
+    /// been touched yet: neither the byte cursor nor any page allocation may
+    /// have reached `at`.
+    pub fn split_region(&mut self, idx: usize, at: usize) -> AllocResult<Range<usize>> {
+        if at % PAGE_SIZE != 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let region = self.region_mut(idx)?;
+        if at <= region.start || at >= region.end {
+            return Err(AllocError::InvalidParam);
+        }
+        if region.b_pos > at || region.p_pos != region.end {
+            return Err(AllocError::MemoryOverlap);
+        }
+        let high = at..region.end;
+        region.end = at;
+        region.p_pos = at;
+        if region.boundary.is_some_and(|boundary| boundary >= at) {
+            region.boundary = None;
+        }
+        self.raw_bytes = self.raw_bytes.saturating_sub(high.len());
+        #[cfg(feature = "sentinel")]
+        self.place_sentinel(idx);
+        Ok(high)
+    }
+
+    /// Registers the callback offered free page ranges by
+    /// [`reclaim`](Self::reclaim), or removes it with `None`.
+    ///
+    /// The callback returns `true` to take the range, which then counts as
+    /// allocated pages until given back with `dealloc_pages`.
+    pub fn set_reclaimer(&mut self, reclaimer: Option<fn(Range<usize>) -> bool>) {
+        self.reclaimer = reclaimer;
+    }
+
+    /// Offers up to `bytes` (rounded up to whole pages) of free pages to the
+    /// reclaimer, e.g. under memory pressure, and returns how many bytes it
+    /// took.
+    ///
+    /// Each region offers the high end of its free gap, in the order
+    /// allocations scan regions. A declined range is left untouched.
+    pub fn reclaim(&mut self, bytes: usize) -> usize {
+        let Some(reclaimer) = self.reclaimer else {
+            return 0;
+        };
+        let mut wanted = bytes.div_ceil(PAGE_SIZE);
+        let mut taken = 0;
+        for idx in self.scan_order() {
+            let region = &mut self.regions[idx];
+            let num_pages = wanted.min(region.available_pages(PAGE_SIZE));
+            if num_pages == 0 {
+                continue;
+            }
+            let saved = *region;
+            let size = num_pages * PAGE_SIZE;
+            let Ok(pos) = region.alloc_pages(size, PAGE_SIZE, self.rebalance) else {
+                continue;
+            };
+            if !reclaimer(pos..pos + size) {
+                *region = saved;
+                continue;
+            }
+            self.counters.pages_allocated(num_pages, false);
+            self.on_allocated(pos, size, PAGE_SIZE);
+            wanted -= num_pages;
+            taken += size;
+            if wanted == 0 {
+                break;
+            }
+        }
+        taken
+    }
+
+    /// Shrinks region `idx` by `bytes` at its end and returns the released
+    /// range, e.g. to give unneeded boot memory back to a host balloon.
+    ///
+    /// The tail must never have been allocated: no page allocation may have
+    /// been made from the region, and the byte cursor must stay below the new
+    /// end. The region cannot be withdrawn entirely.
+    pub fn withdraw_tail(&mut self, idx: usize, bytes: usize) -> AllocResult<Range<usize>> {
+        let region = self.region_mut(idx)?;
+        if bytes == 0 || bytes >= region.size() {
+            return Err(AllocError::InvalidParam);
+        }
+        let at = region.end - bytes;
+        if region.b_pos > at || region.p_pos != region.end {
+            return Err(AllocError::MemoryOverlap);
+        }
+        let tail = at..region.end;
+        region.end = at;
+        region.p_pos = at;
+        if region.boundary.is_some_and(|boundary| boundary > at) {
+            region.boundary = Some(at);
+        }
+        self.raw_bytes = self.raw_bytes.saturating_sub(tail.len());
+        #[cfg(feature = "sentinel")]
+        self.place_sentinel(idx);
+        Ok(tail)
+    }
+
+    /// Sets the logical boundary between the bytes and pages areas of region
+    /// `idx`, or removes it with `None`.
+    ///
+    /// Without a boundary both areas share the free gap on a first-come basis.
+    /// With one, each area is confined to its side, and the [`Rebalance`]
+    /// policy set by [`set_rebalance`](Self::set_rebalance) decides whether
+    /// an exhausted area may push the boundary into the other one's slack.
+    pub fn set_boundary(&mut self, idx: usize, at: Option<usize>) -> AllocResult {
+        let region = self.region_mut(idx)?;
+        if at.is_some_and(|at| at < region.b_pos || at > region.p_pos) {
+            return Err(AllocError::InvalidParam);
+        }
+        region.boundary = at;
+        Ok(())
+    }
+
+    /// Excludes `range` from every region added from now on, even by a later
+    /// `init`: [`add_memory`](BaseAllocator::add_memory) clips new regions
+    /// against all exclusions, possibly splitting them into several regions.
+    ///
+    /// This may be called before `init`, e.g. for reservations known before
+    /// the memory map is parsed. Up to [`EXCLUDE_CAPACITY`] ranges can be
+    /// excluded.
+    pub fn exclude(&mut self, range: Range<usize>) -> AllocResult {
+        if range.is_empty() {
+            return Err(AllocError::InvalidParam);
+        }
+        if self.num_exclusions >= EXCLUDE_CAPACITY {
+            return Err(AllocError::NoMemory);
+        }
+        self.exclusions[self.num_exclusions] = (range.start, range.end);
+        self.num_exclusions += 1;
+        Ok(())
+    }
+
+    /// Registers `observer` to be notified of every region added from now on.
+    ///
+    /// Fails with [`AllocError::NoMemory`] if [`OBSERVER_CAPACITY`] observers
+    /// are already registered.
+    pub fn register_observer(&mut self, observer: &'static dyn MemoryObserver) -> AllocResult {
+        if self.observers.register(observer) {
+            Ok(())
+        } else {
+            Err(AllocError::NoMemory)
+        }
+    }
+
+    /// Unregisters `observer`. Returns `false` if it was not registered.
+    pub fn unregister_observer(&mut self, observer: &'static dyn MemoryObserver) -> bool {
+        self.observers.unregister(observer)
+    }
+
+    /// Like [`add_memory`](BaseAllocator::add_memory), but tags the new
+    /// regions with `flags`, which are passed on to the observers and can be
+    /// read back with [`region_flags`](Self::region_flags). Their meaning is
+    /// up to the caller.
+    pub fn add_memory_flags(&mut self, start: usize, size: usize, flags: u32) -> AllocResult {
+        if size == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        // A region reaching the very top of the address space cannot have its
+        // exclusive end represented, so it gives up its last byte instead.
+        let end = match start.checked_add(size) {
+            Some(end) => end,
+            None if start.wrapping_add(size) == 0 => usize::MAX,
+            None => return Err(AllocError::InvalidParam),
+        };
+        self.add_clipped(start, end, flags)?;
+        self.raw_bytes = self.raw_bytes.saturating_add(size);
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Adds `[start, start + size)` trimmed inward to multiples of `align`,
+    /// which must be a power of two, and returns the number of bytes
+    /// discarded at both edges. Like with `add_memory`, the range may end at
+    /// the very top of the address space.
+    ///
+    /// Fails with [`AllocError::InvalidParam`] if nothing is left.
+    pub fn add_memory_aligned(
+        &mut self,
+        start: usize,
+        size: usize,
+        align: usize,
+    ) -> AllocResult<usize> {
+        if !align.is_power_of_two() {
+            return Err(AllocError::InvalidParam);
+        }
+        let low = align_up(start, align).ok_or(AllocError::InvalidParam)?;
+        let kept = match start.checked_add(size) {
+            Some(end) => align_down(end, align).saturating_sub(low),
+            // The end wraps to 0, which is aligned to anything.
+            None if start.wrapping_add(size) == 0 => low.wrapping_neg(),
+            None => return Err(AllocError::InvalidParam),
+        };
+        if kept == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        self.add_memory(low, kept)?;
+        let discarded = size - kept;
+        self.raw_bytes = self.raw_bytes.saturating_add(discarded);
+        Ok(discarded)
+    }
+
+    /// Returns the flags region `idx` was added with.
+    pub fn region_flags(&self, idx: usize) -> AllocResult<u32> {
+        Ok(self.region(idx)?.flags)
+    }
+
+    /// Sets the priority of region `idx`. Regions with a lower priority are
+    /// consumed first; higher ones are only used once the lower ones cannot
+    /// serve a request, which preserves e.g. on-chip SRAM. All regions start
+    /// with priority `0`.
+    pub fn set_region_priority(&mut self, idx: usize, prio: u8) -> AllocResult {
+        self.region_mut(idx)?.priority = prio;
+        Ok(())
+    }
+
+    /// Returns the priority of region `idx`.
+    pub fn region_priority(&self, idx: usize) -> AllocResult<u8> {
+        Ok(self.region(idx)?.priority)
+    }
+
+    /// Sets where page allocations leave guard pages. Guard pages are never
+    /// handed out, and are reported to [`Hooks::on_range_freed`] so they can
+    /// be left unmapped.
+    ///
+    /// While guards are on, freed page runs are not reused, as their
+    /// neighbours might not be guards.
+    pub fn set_page_guards(&mut self, guard: PageGuard) {
+        self.page_guard = guard;
+    }
+
+    /// Sets what allocations do when the preferred region is full, e.g.
+    /// [`SpillPolicy::SameZone`] to never fall back from SRAM to DRAM.
+    pub fn set_spill_policy(&mut self, policy: SpillPolicy) {
+        self.spill = policy;
+    }
+
+    /// Sets the thresholds [`pressure`](Self::pressure) is computed from, or
+    /// restores the defaults with `None`: moderate below a quarter and
+    /// critical below a sixteenth of the total bytes or pages.
+    pub fn set_pressure_thresholds(&mut self, thresholds: Option<PressureThresholds>) {
+        self.pressure = thresholds;
+    }
+
+    /// Returns the current memory pressure, the higher of the levels given
+    /// by the available bytes and the available pages.
+    pub fn pressure(&self) -> Pressure {
+        let t = self.pressure.unwrap_or_else(|| {
+            let (bytes, pages) = (self.total_bytes(), self.total_pages());
+            PressureThresholds {
+                moderate_bytes: bytes / 4,
+                critical_bytes: bytes / 16,
+                moderate_pages: pages / 4,
+                critical_pages: pages / 16,
+            }
+        });
+        let level = |available: usize, moderate: usize, critical: usize| {
+            if available < critical {
+                Pressure::Critical
+            } else if available < moderate {
+                Pressure::Moderate
+            } else {
+                Pressure::None
+            }
+        };
+        let bytes = level(self.available_bytes(), t.moderate_bytes, t.critical_bytes);
+        let pages = level(self.available_pages(), t.moderate_pages, t.critical_pages);
+        bytes.max(pages)
+    }
+
+    /// Temporarily takes region `idx` out of allocation, e.g. while it is
+    /// being scrubbed or remapped. Its blocks can still be freed and it is
+    /// still accounted for in the statistics.
+    pub fn disable_region(&mut self, idx: usize) -> AllocResult {
+        self.region(idx)?;
+        self.disabled |= 1 << idx;
+        Ok(())
+    }
+
+    /// Puts region `idx` back into allocation after
+    /// [`disable_region`](Self::disable_region).
+    pub fn enable_region(&mut self, idx: usize) -> AllocResult {
+        self.region(idx)?;
+        self.disabled &= !(1 << idx);
+        Ok(())
+    }
+
+    /// Returns `true` if region `idx` is in use and not disabled.
+    pub fn is_region_enabled(&self, idx: usize) -> bool {
+        self.region(idx).is_ok() && self.disabled & (1 << idx) == 0
+    }
+
+    /// Makes region `idx` the preferred one, which the next allocation is
+    /// tried in first.
+    pub fn set_preferred_region(&mut self, idx: usize) -> AllocResult {
+        self.region(idx)?;
+        self.current_region = idx;
+        Ok(())
+    }
+
+    /// Dedicates region `idx` to [`alloc_pages_exec`](Self::alloc_pages_exec),
+    /// or stops doing so with `None`. All other allocations then avoid it.
+    ///
+    /// Without an exec region, exec runs come from the pages area of any
+    /// region. They never share a page with data either way, but a dedicated
+    /// region keeps them together for the early mapper.
+    pub fn set_exec_region(&mut self, idx: Option<usize>) -> AllocResult {
+        if let Some(idx) = idx {
+            self.region(idx)?;
+        }
+        self.exec_region = idx;
+        Ok(())
+    }
+
+    /// Allocates a page run meant to hold code, so that it can be mapped
+    /// X-only while data pages are mapped W-only.
+    ///
+    /// See [`set_exec_region`](Self::set_exec_region).
+    pub fn alloc_pages_exec(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        self.alloc_page_run(num_pages, align_pow2, true, None)
+    }
+
+    /// Frees a page run allocated by [`alloc_pages_exec`](Self::alloc_pages_exec).
+    ///
+    /// A run outside the exec region is ignored, and so is, without an exec
+    /// region, a run larger than the exec pages in use. The `no-stats`
+    /// feature does not count exec pages, so it ignores every run unless an
+    /// exec region is set.
+    pub fn dealloc_pages_exec(&mut self, pos: usize, num_pages: usize) {
+        let exec = match self.exec_region {
+            Some(idx) => self.region_of(pos) == Some(idx),
+            None => self.counters.covers_exec(num_pages).unwrap_or(false),
+        };
+        if !exec || !self.free_page_run(pos, num_pages, true) {
+            early_warn!(
+                "early allocator: ignoring exec free of {} pages at {:#x}, not allocated",
+                num_pages,
+                pos
+            );
+        }
+    }
+
+    /// Returns the number of exec pages in use. The other
+    /// [`used_pages`](PageAllocator::used_pages) hold data.
+    pub fn exec_pages(&self) -> usize {
+        self.counters.exec_pages()
+    }
+
+    /// Sets the policy applied when an area reaches its region boundary.
+    pub fn set_rebalance(&mut self, policy: Rebalance) {
+        self.rebalance = policy;
+    }
+
+    fn region(&self, idx: usize) -> AllocResult<&Region> {
+        if idx >= MAX_REGIONS || self.bitmap & (1 << idx) == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        Ok(&self.regions[idx])
+    }
+
+    fn region_mut(&mut self, idx: usize) -> AllocResult<&mut Region> {
+        if idx >= MAX_REGIONS || self.bitmap & (1 << idx) == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        Ok(&mut self.regions[idx])
+    }
+
+    /// Allocates a byte block that is deallocated when the returned guard is
+    /// dropped.
+    ///
+    /// The allocator stays reachable through [`AllocGuard::allocator`], so
+    /// guards can be nested; dropped in reverse order, they roll the bytes
+    /// cursor back block by block (padding inserted for alignment between
+    /// two blocks stops the rollback, leaving the rest to be recycled).
+    #[track_caller]
+    pub fn alloc_guarded(&mut self, layout: Layout) -> AllocResult<AllocGuard<'_, PAGE_SIZE>> {
+        let ptr = ByteAllocator::alloc(self, layout)?;
+        Ok(AllocGuard::new(self, ptr, layout))
+    }
+
+    /// Allocates room for a `T`, initializes it in place with `f`, and returns
+    /// a reference to it.
+    ///
+    /// The object is never dropped and its memory stays allocated for good,
+    /// since the reference can not be given back to [`ByteAllocator::dealloc`].
+    #[track_caller]
+    pub fn alloc_with<T, F>(&mut self, f: F) -> AllocResult<&'static mut T>
+    where
+        F: FnOnce() -> T,
+    {
+        let ptr = ByteAllocator::alloc(self, Layout::new::<T>())?.cast::<T>();
+        // SAFETY: the block is fresh, properly aligned and large enough for a
+        // `T`, and nobody else can reach it until it is deallocated.
+        unsafe {
+            ptr.as_ptr().write(f());
+            Ok(&mut *ptr.as_ptr())
+        }
+    }
+
+    /// Allocates bytes from the high end of a region, growing downward
+    /// together with the pages area.
+    ///
+    /// Such allocations are never freed, so they suit long-lived objects:
+    /// keeping them away from the bytes area lets the transient allocations
+    /// there drain and reset the bytes area as usual.
+    ///
+    /// The pages they take are not page runs: page calls like
+    /// [`dealloc_pages`](PageAllocator::dealloc_pages) and
+    /// [`put_page`](Self::put_page) reject them. Only
+    /// [`TOP_SPAN_CAPACITY`] stretches of them separated by page runs can
+    /// exist at once; past that this fails with [`AllocError::NoMemory`].
+    #[track_caller]
+    pub fn alloc_from_top(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        let layout = self.tagged_layout(layout)?;
+        let (pos, _) = self.alloc_top_block(layout)?;
+        self.on_allocated(pos, layout.size(), layout.align());
+        self.track_alloc(pos, layout);
+        let pos = self.tag(pos, layout.size());
+        NonNull::new(pos as *mut u8).ok_or(AllocError::NoMemory)
+    }
+
+    /// Allocates a byte block a device with the DMA address mask `mask` can
+    /// reach: every address `addr` of the block satisfies `addr & !mask == 0`.
+    ///
+    /// `mask` must be of the form `2^n - 1`, e.g. `0xFFFF_FFFF` for a 32-bit
+    /// device, otherwise this fails with [`AllocError::InvalidParam`]. Only
+    /// the bytes areas of regions starting below the limit are searched, and
+    /// the block is freed with `dealloc` as usual.
+    #[track_caller]
+    pub fn alloc_dma(&mut self, layout: Layout, mask: u64) -> AllocResult<NonNull<u8>> {
+        if mask.wrapping_add(1) & mask != 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let limit = usize::try_from(mask).unwrap_or(usize::MAX);
+        let layout = self.tagged_layout(layout)?;
+        let rebalance = self.rebalance;
+        let pos = self.alloc_in_regions(|r| {
+            if r.start > limit {
+                return Err(AllocError::NoMemory);
+            }
+            let saved = *r;
+            let pos = r.alloc_bytes(layout, 0, rebalance)?;
+            if layout.size() > 0 && pos + layout.size() - 1 > limit {
+                *r = saved;
+                return Err(AllocError::NoMemory);
+            }
+            Ok(pos)
+        })?;
+        self.on_allocated(pos, layout.size(), layout.align());
+        self.track_alloc(pos, layout);
+        let pos = self.tag(pos, layout.size());
+        NonNull::new(pos as *mut u8).ok_or(AllocError::NoMemory)
+    }
+
+    /// Like [`PageAllocator::alloc_pages`], but the run must lie entirely
+    /// below the limit of the DMA mask `mask`, as for
+    /// [`alloc_dma`](Self::alloc_dma).
+    pub fn alloc_pages_dma(&mut self, num_pages: usize, mask: u64) -> AllocResult<usize> {
+        if mask.wrapping_add(1) & mask != 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let limit = usize::try_from(mask).unwrap_or(usize::MAX);
+        let size = num_pages
+            .checked_mul(PAGE_SIZE)
+            .ok_or(AllocError::InvalidParam)?;
+        let rebalance = self.rebalance;
+        let pos = self.alloc_in_regions(|r| {
+            if r.start > limit {
+                return Err(AllocError::NoMemory);
+            }
+            let saved = *r;
+            let pos = r.alloc_pages(size, PAGE_SIZE, rebalance)?;
+            if size > 0 && pos + size - 1 > limit {
+                *r = saved;
+                return Err(AllocError::NoMemory);
+            }
+            Ok(pos)
+        })?;
+        self.counters.pages_allocated(num_pages, false);
+        self.on_allocated(pos, size, PAGE_SIZE);
+        Ok(pos)
+    }
+
+    /// Like [`ByteAllocator::alloc`], but reports running out of memory as
+    /// [`EarlyAllocError::OutOfMemory`] with the size and alignment asked for,
+    /// so `?` in boot code carries them to the panic message, running into
+    /// the pages area as [`EarlyAllocError::Collision`], and a call before
+    /// any memory was added as [`EarlyAllocError::Uninitialized`].
+    pub fn try_alloc(&mut self, layout: Layout) -> Result<NonNull<u8>, EarlyAllocError> {
+        self.alloc(layout).map_err(|err| match err {
+            AllocError::NoMemory if !self.initialized => EarlyAllocError::Uninitialized,
+            AllocError::NoMemory => EarlyAllocError::OutOfMemory {
+                size: layout.size(),
+                align: layout.align(),
+            },
+            AllocError::MemoryOverlap => EarlyAllocError::Collision {
+                growing: Area::Bytes,
+            },
+            err => err.into(),
+        })
+    }
+
+    /// Like [`PageAllocator::alloc_pages`], but reports
+    /// [`EarlyAllocError::Fragmented`] if the run does not fit in any single
+    /// region though enough pages are free across several of them,
+    /// [`EarlyAllocError::OutOfMemory`] if it does not fit otherwise, e.g.
+    /// for want of a suitably aligned run, [`EarlyAllocError::Collision`] if
+    /// the run would fit in a region but runs into its bytes area, and
+    /// [`EarlyAllocError::Uninitialized`] before any memory was added.
+    pub fn try_alloc_pages(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+    ) -> Result<usize, EarlyAllocError> {
+        match self.alloc_pages(num_pages, align_pow2) {
+            Err(AllocError::NoMemory) if !self.initialized => Err(EarlyAllocError::Uninitialized),
+            Err(AllocError::NoMemory) if self.is_fragmented(num_pages) => {
+                Err(EarlyAllocError::Fragmented)
+            }
+            Err(AllocError::NoMemory) => Err(EarlyAllocError::OutOfMemory {
+                size: num_pages.saturating_mul(PAGE_SIZE),
+                align: align_pow2,
+            }),
+            Err(AllocError::MemoryOverlap) => Err(EarlyAllocError::Collision {
+                growing: Area::Pages,
+            }),
+            res => Ok(res?),
+        }
+    }
+
+    /// Returns `true` if `num_pages` pages are free across the regions page
+    /// runs are taken from, but no single one of them has that many.
+    fn is_fragmented(&self, num_pages: usize) -> bool {
+        let (total, largest) = self
+            .scan_order()
+            .map(|idx| {
+                let r = &self.regions[idx];
+                r.available_pages(PAGE_SIZE) + r.freed / PAGE_SIZE
+            })
+            .fold((0, 0), |(total, largest), free| {
+                (total + free, largest.max(free))
+            });
+        total >= num_pages && largest < num_pages
+    }
+
+    /// Allocates a byte block from region `idx` only, bypassing the free
+    /// lists and the high-alignment path.
+    pub(crate) fn alloc_bytes_in(
+        &mut self,
+        idx: usize,
+        layout: Layout,
+    ) -> AllocResult<NonNull<u8>> {
+        let layout = self.tagged_layout(layout)?;
+        let rebalance = self.rebalance;
+        let pos = self.alloc_in(core::iter::once(idx), |r| {
+            r.alloc_bytes(layout, 0, rebalance)
+        })?;
+        self.on_allocated(pos, layout.size(), layout.align());
+        self.track_alloc(pos, layout);
+        let pos = self.tag(pos, layout.size());
+        NonNull::new(pos as *mut u8).ok_or(AllocError::NoMemory)
+    }
+
+    /// Fences off `bytes` of room for a burst of upcoming byte allocations.
+    ///
+    /// A region with that much room is picked and the allocations made
+    /// through the returned [`Reservation`] are served from it. Since the
+    /// reservation borrows the allocator, nothing else can eat into the room,
+    /// so the burst cannot fail as long as its total size, alignment padding
+    /// included, stays within the reservation; a request beyond it fails.
+    /// Nothing is set aside in the region itself, so once the reservation
+    /// is dropped, whatever is left is open to any allocation again.
+    pub fn try_reserve(&mut self, bytes: usize) -> AllocResult<Reservation<'_, PAGE_SIZE>> {
+        let layout = Layout::from_size_align(bytes, 1).map_err(|_| AllocError::InvalidParam)?;
+        let idx = self
+            .scan_order()
+            .find(|&idx| {
+                let mut region = self.regions[idx];
+                region.alloc_bytes(layout, 0, self.rebalance).is_ok()
+            })
+            .ok_or(AllocError::NoMemory)?;
+        Ok(Reservation::new(self, idx, bytes))
+    }
+
+    /// Like [`try_reserve`](Self::try_reserve), but fences off room for
+    /// `num_pages` page-aligned pages, to be taken with
+    /// [`Reservation::alloc_pages`].
+    pub fn try_reserve_pages(
+        &mut self,
+        num_pages: usize,
+    ) -> AllocResult<Reservation<'_, PAGE_SIZE>> {
+        let size = num_pages
+            .checked_mul(PAGE_SIZE)
+            .ok_or(AllocError::InvalidParam)?;
+        let idx = self
+            .scan_order()
+            .find(|&idx| {
+                let mut region = self.regions[idx];
+                region.alloc_pages(size, PAGE_SIZE, self.rebalance).is_ok()
+            })
+            .ok_or(AllocError::NoMemory)?;
+        Ok(Reservation::new(self, idx, size))
+    }
+
+    /// Sets aside `bytes` (rounded up to whole pages) that ordinary
+    /// allocations cannot touch, so that [`alloc_critical`](Self::alloc_critical)
+    /// can still produce diagnostics, e.g. on a panic path. `0` removes the
+    /// reserve.
+    ///
+    /// A previous reserve is given back first, which fails with
+    /// [`AllocError::MemoryOverlap`] once a block has been taken from it.
+    pub fn set_emergency_reserve(&mut self, bytes: usize) -> AllocResult {
+        if let Some(reserve) = &self.emergency {
+            if !reserve.is_untouched() {
+                return Err(AllocError::MemoryOverlap);
+            }
+            let range = reserve.range();
+            self.free_page_run(range.start, range.len() / PAGE_SIZE, false);
+            self.emergency = None;
+        }
+        if bytes == 0 {
+            return Ok(());
+        }
+        let num_pages = bytes.div_ceil(PAGE_SIZE);
+        let pos = self.alloc_page_run(num_pages, PAGE_SIZE, false, None)?;
+        self.emergency = Some(EmergencyReserve::new(pos..pos + num_pages * PAGE_SIZE));
+        Ok(())
+    }
+
+    /// Forgets the emergency reserve if any of its pages is no longer
+    /// allocated, e.g. freed with `dealloc_pages` or rolled back by
+    /// [`reset_to_epoch`](Self::reset_to_epoch), so that `alloc_critical`
+    /// never hands out memory given out again.
+    fn drop_stale_reserve(&mut self) {
+        let Some(range) = self.emergency.as_ref().map(EmergencyReserve::range) else {
+            return;
+        };
+        if range
+            .step_by(PAGE_SIZE)
+            .any(|page| self.check_page(page).is_err())
+        {
+            self.emergency = None;
+        }
+    }
+
+    /// Returns the number of bytes left in the emergency reserve.
+    pub fn emergency_available(&self) -> usize {
+        self.emergency
+            .as_ref()
+            .map_or(0, EmergencyReserve::available)
+    }
+
+    /// Allocates a byte block like [`alloc`](ByteAllocator::alloc), but dips
+    /// into the emergency reserve when ordinary memory is exhausted.
+    ///
+    /// Blocks taken from the reserve are never reused: freeing them does
+    /// nothing.
+    pub fn alloc_critical(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        self.alloc(layout)
+            .or_else(|err| self.alloc_emergency(layout, usize::MAX, err))
+    }
+
+    /// Takes a block ending at or below `limit` from the emergency reserve
+    /// after an ordinary allocation failed with `err`, which is returned if
+    /// the reserve cannot serve it.
+    fn alloc_emergency(
+        &mut self,
+        layout: Layout,
+        limit: usize,
+        err: AllocError,
+    ) -> AllocResult<NonNull<u8>> {
+        let pos = self
+            .emergency
+            .as_mut()
+            .and_then(|reserve| reserve.alloc(layout, limit))
+            .ok_or(err)?;
+        self.on_allocated(pos, layout.size(), layout.align());
+        NonNull::new(pos as *mut u8).ok_or(AllocError::NoMemory)
+    }
+
+    /// Allocates a byte block with the modifiers of `flags`, one entry point
+    /// for what [`alloc_dma`](Self::alloc_dma),
+    /// [`alloc_from_top`](Self::alloc_from_top) and
+    /// [`alloc_critical`](Self::alloc_critical) do separately.
+    ///
+    /// [`AllocFlags::DMA`] and [`AllocFlags::HIGH`] cannot be combined. With
+    /// [`AllocFlags::DMA`] and [`AllocFlags::CRITICAL`], the emergency
+    /// reserve is only used if the block is within the DMA mask. The block
+    /// is freed with `dealloc` as the entry point it stands for allows.
+    #[track_caller]
+    pub fn alloc_flags(&mut self, layout: Layout, flags: AllocFlags) -> AllocResult<NonNull<u8>> {
+        if flags.contains(AllocFlags::DMA | AllocFlags::HIGH) {
+            return Err(AllocError::InvalidParam);
+        }
+        let spill = self.spill;
+        if flags.contains(AllocFlags::NO_SPILL) {
+            self.spill = SpillPolicy::Never;
+        }
+        let res = if flags.contains(AllocFlags::DMA) {
+            self.alloc_dma(layout, DMA32_MASK)
+        } else if flags.contains(AllocFlags::HIGH) {
+            self.alloc_from_top(layout)
+        } else {
+            self.alloc(layout)
+        };
+        self.spill = spill;
+        let ptr = match res {
+            Err(err) if flags.contains(AllocFlags::CRITICAL) => {
+                let limit = if flags.contains(AllocFlags::DMA) {
+                    usize::try_from(DMA32_MASK).unwrap_or(usize::MAX)
+                } else {
+                    usize::MAX
+                };
+                self.alloc_emergency(layout, limit, err)?
+            }
+            res => res?,
+        };
+        if flags.contains(AllocFlags::ZEROED) {
+            // SAFETY: the block of `layout.size()` bytes was just allocated.
+            unsafe { core::ptr::write_bytes(ptr.as_ptr(), 0, layout.size()) };
+        }
+        Ok(ptr)
+    }
+
+    /// Allocates `total_pages` pages as at most `max_extents` page-aligned
+    /// runs, taking as much as possible from each region in scan order, for
+    /// buffers that need not be contiguous.
+    ///
+    /// Either the whole request is served or nothing is allocated. Each run
+    /// is freed on its own with `dealloc_pages`.
+    pub fn alloc_pages_vectored(
+        &mut self,
+        total_pages: usize,
+        max_extents: usize,
+    ) -> AllocResult<PageExtents> {
+        if total_pages == 0 || max_extents == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let guard_pages = match self.page_guard {
+            PageGuard::Off => 0,
+            PageGuard::After => 1,
+            PageGuard::Around => 2,
+        };
+        let mut extents = PageExtents {
+            runs: [(0, 0); MAX_REGIONS],
+            len: 0,
+        };
+        let mut remaining = total_pages;
+        for idx in self.scan_order() {
+            if remaining == 0 || extents.len == max_extents {
+                break;
+            }
+            let avail = self.regions[idx]
+                .available_pages(PAGE_SIZE)
+                .saturating_sub(guard_pages);
+            let take = remaining.min(avail);
+            if take == 0 {
+                continue;
+            }
+            if let Ok(pos) = self.alloc_page_run(take, PAGE_SIZE, false, Some(idx)) {
+                extents.runs[extents.len] = (pos, take);
+                extents.len += 1;
+                remaining -= take;
+            }
+        }
+        if remaining > 0 {
+            for (pos, num_pages) in extents.iter() {
+                self.free_page_run(pos, num_pages, false);
+            }
+            return Err(AllocError::NoMemory);
+        }
+        Ok(extents)
+    }
+
+    /// Like [`PageAllocator::alloc_pages`], but returns an [`AllocationId`]
+    /// describing the run.
+    pub fn alloc_pages_id(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+    ) -> AllocResult<AllocationId> {
+        let addr = self.alloc_pages(num_pages, align_pow2)?;
+        Ok(AllocationId {
+            region: self.current_region as u8,
+            addr,
+            num_pages,
+        })
+    }
+
+    /// Deallocates the page run identified by `id`.
+    ///
+    /// Fails with [`AllocError::NotAllocated`] if `id` does not describe a run
+    /// in the pages area of its region.
+    pub fn dealloc_pages_id(&mut self, id: AllocationId) -> AllocResult {
+        let region = self.region(id.region())?;
+        let end = id
+            .num_pages
+            .checked_mul(PAGE_SIZE)
+            .and_then(|size| id.addr.checked_add(size))
+            .ok_or(AllocError::InvalidParam)?;
+        if id.addr < region.p_pos || end > region.end {
+            return Err(AllocError::NotAllocated);
+        }
+        self.dealloc_pages(id.addr, id.num_pages);
+        Ok(())
+    }
+
+    /// Returns the trailing pages of the run of `num_pages` pages at `pos`
+    /// to the free pool, keeping its first `new_num_pages` pages.
+    ///
+    /// Fails with [`AllocError::InvalidParam`] if `new_num_pages` is zero
+    /// or larger than `num_pages`, with [`AllocError::NotAllocated`] if the
+    /// run is not allocated from the pages area, and with
+    /// [`AllocError::NoMemory`] if no room is left to mark the freed pages.
+    pub fn shrink_pages(
+        &mut self,
+        pos: usize,
+        num_pages: usize,
+        new_num_pages: usize,
+    ) -> AllocResult {
+        if new_num_pages == 0 || new_num_pages > num_pages {
+            return Err(AllocError::InvalidParam);
+        }
+        let idx = self.region_of(pos).ok_or(AllocError::NotAllocated)?;
+        let region = &self.regions[idx];
+        let end = num_pages
+            .checked_mul(PAGE_SIZE)
+            .and_then(|size| pos.checked_add(size))
+            .ok_or(AllocError::InvalidParam)?;
+        let tail = pos + new_num_pages * PAGE_SIZE;
+        if pos < region.p_pos
+            || end > region.end
+            || (tail..end)
+                .step_by(PAGE_SIZE)
+                .any(|page| region.is_freed(page, PAGE_SIZE))
+        {
+            return Err(AllocError::NotAllocated);
+        }
+        if tail == end {
+            return Ok(());
+        }
+        if !self.free_page_run(tail, num_pages - new_num_pages, false) {
+            return Err(AllocError::NoMemory);
+        }
+        Ok(())
+    }
+
+    /// Like [`shrink_pages`](Self::shrink_pages) for the run identified by
+    /// `id`, which is updated to describe what is left of it.
+    pub fn shrink_pages_id(&mut self, id: &mut AllocationId, new_num_pages: usize) -> AllocResult {
+        if id.region() != self.region_of(id.addr).ok_or(AllocError::NotAllocated)? {
+            return Err(AllocError::NotAllocated);
+        }
+        self.shrink_pages(id.addr, id.num_pages, new_num_pages)?;
+        id.num_pages = new_num_pages;
+        Ok(())
+    }
+
+    /// Allocates a stack of `num_pages` pages from the page area, with
+    /// `guard_pages` unallocated pages left directly below it.
+    ///
+    /// See [`StackDesc`] for how to free it.
+    pub fn alloc_stack(&mut self, num_pages: usize, guard_pages: usize) -> AllocResult<StackDesc> {
+        if num_pages == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        let total = num_pages
+            .checked_add(guard_pages)
+            .ok_or(AllocError::InvalidParam)?;
+        let base = self.alloc_pages(total, PAGE_SIZE)?;
+        let bottom = base + guard_pages * PAGE_SIZE;
+        if guard_pages > 0 {
+            self.hooks.freed(base, bottom - base);
+        }
+        Ok(StackDesc {
+            bottom,
+            top: bottom + num_pages * PAGE_SIZE,
+            guard: base..bottom,
+        })
+    }
+
+    /// Returns the index of the region containing `addr`, if any.
+    pub fn region_of(&self, addr: usize) -> Option<usize> {
+        let sorted = self.sorted_indices();
+        let pos = sorted.partition_point(|&idx| self.regions[idx as usize].start <= addr);
+        let idx = *sorted.get(pos.checked_sub(1)?)? as usize;
+        self.regions[idx].contains(addr).then_some(idx)
+    }
+
+    /// Returns `true` if `addr` lies in one of the managed regions.
+    pub fn owns(&self, addr: usize) -> bool {
+        self.region_of(addr).is_some()
+    }
+
+    fn sorted_indices(&self) -> &[u8] {
+        &self.sorted[..self.bitmap.count_ones() as usize]
+    }
+
+    /// Runs `f` on every region, starting from `current_region`, until it
+    /// succeeds. The successful region becomes the new `current_region`.
+    ///
+    /// If every region fails, [`AllocError::MemoryOverlap`] is preferred over
+    /// [`AllocError::NoMemory`]: the request would have fit somewhere if the
+    /// bytes and pages areas had not collided.
+    fn alloc_in_regions<F>(&mut self, f: F) -> AllocResult<usize>
+    where
+        F: FnMut(&mut Region) -> AllocResult<usize>,
+    {
+        self.alloc_in(self.scan_order(), f)
+    }
+
+    /// Fails until memory was given to the allocator, by `init`,
+    /// `add_memory` or [`with_region`](Self::with_region), so a call made
+    /// too early in boot is reported instead of looking like plain
+    /// exhaustion.
+    fn check_initialized(&self) -> AllocResult {
+        if !self.initialized {
+            early_warn!("early allocator: allocation before init");
+            return Err(AllocError::NoMemory);
+        }
+        Ok(())
+    }
+
+    /// Returns `true` once memory was given to the allocator, even if all
+    /// of it was removed since.
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Fails once the allocator is [sealed](Self::seal).
+    fn check_sealed(&self) -> AllocResult {
+        if self.sealed {
+            early_warn!("early allocator: allocation after seal");
+            return Err(AllocError::NoMemory);
+        }
+        Ok(())
+    }
+
+    /// Like [`alloc_in_regions`](Self::alloc_in_regions), over the regions
+    /// of `order`.
+    fn alloc_in<I, F>(&mut self, order: I, mut f: F) -> AllocResult<usize>
+    where
+        I: Iterator<Item = usize>,
+        F: FnMut(&mut Region) -> AllocResult<usize>,
+    {
+        self.check_initialized()?;
+        self.check_sealed()?;
+        #[cfg(feature = "canary")]
+        self.check_canaries();
+        let mut err = AllocError::NoMemory;
+        let disabled = self.disabled;
+        for idx in order.filter(|&idx| disabled & (1 << idx) == 0) {
+            match f(&mut self.regions[idx]) {
+                Ok(pos) => {
+                    self.current_region = idx;
+                    return Ok(pos);
+                }
+                Err(AllocError::MemoryOverlap) => err = AllocError::MemoryOverlap,
+                Err(_) => {}
+            }
+        }
+        Err(err)
+    }
+
+    /// Checks the `align_pow2` argument of page allocations, which is an
+    /// alignment in bytes (not its log2): a power of two between `PAGE_SIZE`
+    /// and [`MAX_PAGE_ALIGN`].
+    fn check_page_align(align_pow2: usize) -> AllocResult {
+        if !align_pow2.is_power_of_two() || align_pow2 < PAGE_SIZE || align_pow2 > MAX_PAGE_ALIGN {
+            return Err(AllocError::InvalidParam);
+        }
+        Ok(())
+    }
+
+    /// Converts the log2 of an alignment, as some callers compute it, to the
+    /// `align_pow2` in bytes taken by page allocations, checking it the same
+    /// way they do.
+    pub fn page_align_from_log2(log2: u32) -> AllocResult<usize> {
+        let align = 1usize.checked_shl(log2).ok_or(AllocError::InvalidParam)?;
+        Self::check_page_align(align)?;
+        Ok(align)
+    }
+
+    /// Allocates a page run, from the region `pinned` if given, else from the
+    /// exec region if `exec` is set and one has been designated, otherwise
+    /// from the other regions.
+    pub(crate) fn alloc_page_run(
+        &mut self,
+        num_pages: usize,
+        align_pow2: usize,
+        exec: bool,
+        pinned: Option<usize>,
+    ) -> AllocResult<usize> {
+        self.check_initialized()?;
+        self.check_sealed()?;
+        Self::check_page_align(align_pow2)?;
+        let size = num_pages
+            .checked_mul(PAGE_SIZE)
+            .ok_or(AllocError::InvalidParam)?;
+        let (after, before) = self.page_guard_bytes();
+        let alloc = self.page_run_taker(size, align_pow2, self.rebalance)?;
+        let pos = match pinned.or(self.exec_region.filter(|_| exec)) {
+            Some(idx) => self.alloc_in(core::iter::once(idx), alloc),
+            None => self.alloc_in(self.scan_order(), alloc),
+        }
+        .map_err(|err| {
+            let request = OomRequest::Pages {
+                num_pages,
+                align: align_pow2,
+            };
+            self.report_oom(err, request)
+        })?;
+        if after > 0 {
+            self.hooks.freed(pos + size, after);
+        }
+        if before > 0 {
+            self.hooks.freed(pos - before, before);
+        }
+        self.counters.pages_allocated(num_pages, exec);
+        self.on_allocated(pos, size, align_pow2);
+        Ok(pos)
+    }
+
+    /// Frees a page run like `dealloc_pages`, returning `false` if the free
+    /// was ignored.
+    pub(crate) fn dealloc_page_run(&mut self, pos: usize, num_pages: usize) -> bool {
+        #[cfg(feature = "trace")]
+        self.record(TraceOp::DeallocPages, num_pages, PAGE_SIZE, Some(pos));
+        let freed = self.free_page_run(pos, num_pages, false);
+        if !freed {
+            early_warn!(
+                "early allocator: ignoring free of {} pages at {:#x}, not allocated",
+                num_pages,
+                pos
+            );
+        }
+        freed
+    }
+
+    /// Bytes of guard pages left above and below each page run.
+    fn page_guard_bytes(&self) -> (usize, usize) {
+        match self.page_guard {
+            PageGuard::Off => (0, 0),
+            PageGuard::After => (PAGE_SIZE, 0),
+            PageGuard::Around => (PAGE_SIZE, PAGE_SIZE),
+        }
+    }
+
+    /// Returns what takes a run of `size` bytes aligned to `align_pow2`,
+    /// with its guard pages, from the pages area of a region under the
+    /// `rebalance` policy: a freed run if there are no guards, else fresh
+    /// pages.
+    pub(crate) fn page_run_taker(
+        &self,
+        size: usize,
+        align_pow2: usize,
+        rebalance: Rebalance,
+    ) -> AllocResult<impl Fn(&mut Region) -> AllocResult<usize>> {
+        let (after, before) = self.page_guard_bytes();
+        let with_guard = size.checked_add(after).ok_or(AllocError::InvalidParam)?;
+        Ok(move |r: &mut Region| {
+            if after == 0 && before == 0 {
+                if let Some(pos) = r.find_freed(size, align_pow2, PAGE_SIZE) {
+                    r.take_freed(pos, size, PAGE_SIZE);
+                    return Ok(pos);
+                }
+            }
+            let saved = *r;
+            let pos = r.alloc_pages(with_guard, align_pow2, rebalance)?;
+            if before > 0 {
+                // `pos` is page-aligned, so the guard lands right below it.
+                if let Err(err) = r.alloc_pages(before, PAGE_SIZE, rebalance) {
+                    *r = saved;
+                    return Err(err);
+                }
+            }
+            Ok(pos)
+        })
+    }
+
+    /// Frees a page run, returning `false` if it was not allocated.
+    fn free_page_run(&mut self, pos: usize, num_pages: usize, exec: bool) -> bool {
+        let Some(size) = num_pages.checked_mul(PAGE_SIZE) else {
+            return false;
+        };
+        let Some(idx) = self.region_of(pos) else {
+            return false;
+        };
+        if self.top_spans.overlaps(pos, pos.saturating_add(size)) {
+            return false;
+        }
+        let rebalance = self.rebalance;
+        if self.regions[idx].free_pages(pos, size, PAGE_SIZE, rebalance) {
+            self.page_refs
+                .retain(|page| !(pos..pos + size).contains(&page));
+            self.scratch.forget_freed(pos, pos + size);
+            self.counters.pages_freed(num_pages, exec);
+            self.hooks.freed(pos, size);
+            if self
+                .emergency
+                .as_ref()
+                .is_some_and(|e| e.range().start < pos + size && pos < e.range().end)
+            {
+                self.drop_stale_reserve();
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Adds `[start, end)` minus the exclusions, as one region per remaining
+    /// piece. Nothing is added unless every piece can be.
+    fn add_clipped(&mut self, start: usize, end: usize, flags: u32) -> AllocResult {
+        // Each exclusion splits at most one piece in two.
+        let mut pieces = [(0, 0); EXCLUDE_CAPACITY + 1];
+        let mut len = 0;
+        self.clip(start, end, 0, &mut pieces, &mut len);
+        let pieces = &pieces[..len];
+        if pieces
+            .iter()
+            .any(|&(start, end)| self.overlaps_regions(start, end))
+        {
+            return Err(AllocError::MemoryOverlap);
+        }
+        if len > MAX_REGIONS - self.bitmap.count_ones() as usize {
+            return Err(AllocError::NoMemory);
+        }
+        for &(start, end) in pieces {
+            self.insert_region(start, end, flags)?;
+        }
+        Ok(())
+    }
+
+    /// Appends the pieces of `[start, end)` left by the exclusions from
+    /// `from` on to `pieces[len..]`.
+    fn clip(
+        &self,
+        start: usize,
+        end: usize,
+        from: usize,
+        pieces: &mut [(usize, usize)],
+        len: &mut usize,
+    ) {
+        for i in from..self.num_exclusions {
+            let (ex_start, ex_end) = self.exclusions[i];
+            if ex_start < end && start < ex_end {
+                if start < ex_start {
+                    self.clip(start, ex_start, i + 1, pieces, len);
+                }
+                if ex_end < end {
+                    self.clip(ex_end, end, i + 1, pieces, len);
+                }
+                return;
+            }
+        }
+        pieces[*len] = (start, end);
+        *len += 1;
+    }
+
+    /// Returns `true` if `[start, end)` overlaps a region in use.
+    fn overlaps_regions(&self, start: usize, end: usize) -> bool {
+        let num = self.bitmap.count_ones() as usize;
+        let sorted = self.sorted_indices();
+        let pos = sorted.partition_point(|&idx| self.regions[idx as usize].start < start);
+        // Only the neighbours in address order can overlap the new region.
+        let neighbours = pos.saturating_sub(1)..(pos + 1).min(num);
+        sorted[neighbours]
+            .iter()
+            .any(|&idx| self.regions[idx as usize].overlaps(start, end))
+    }
+
+    fn insert_region(&mut self, start: usize, end: usize, flags: u32) -> AllocResult {
+        if self.overlaps_regions(start, end) {
+            return Err(AllocError::MemoryOverlap);
+        }
+        let num = self.bitmap.count_ones() as usize;
+        let pos = self
+            .sorted_indices()
+            .partition_point(|&idx| self.regions[idx as usize].start < start);
+        let idx = (!self.bitmap).trailing_zeros() as usize;
+        if idx >= MAX_REGIONS {
+            return Err(AllocError::NoMemory);
+        }
+        self.regions[idx] = Region::new(start, end);
+        self.regions[idx].flags = flags;
+        self.bitmap |= 1 << idx;
+        self.sorted.copy_within(pos..num, pos + 1);
+        self.sorted[pos] = idx as u8;
+        #[cfg(feature = "sentinel")]
+        self.place_sentinel(idx);
+        self.observers.notify(start..end, flags);
+        Ok(())
+    }
+
+    /// Iterates over the indices of the regions in use in allocation order:
+    /// lowest priority first, and within a priority `current_region` first,
+    /// then the following ones with wraparound. The exec region is skipped.
+    fn scan_order(&self) -> impl Iterator<Item = usize> {
+        let first = self.current_region;
+        let mut order = [0u8; MAX_REGIONS];
+        let mut len = 0;
+        // Rotate the mask so that walking it from bit 0 visits `first` first.
+        for bit in set_bits(self.scan_mask().rotate_right(first as u32)) {
+            order[len] = ((bit + first) % MAX_REGIONS) as u8;
+            len += 1;
+        }
+        order[..len].sort_unstable_by_key(|&idx| {
+            let idx = idx as usize;
+            let rotated = (idx + MAX_REGIONS - first) % MAX_REGIONS;
+            (self.regions[idx].priority, rotated)
+        });
+        order.into_iter().take(len).map(usize::from)
+    }
+
+    /// Mask of the regions [`scan_order`](Self::scan_order) visits: the
+    /// enabled ones other than the exec region, narrowed down by the
+    /// [`SpillPolicy`].
+    fn scan_mask(&self) -> usize {
+        let first = self.current_region;
+        let mut mask = match self.exec_region {
+            Some(idx) => self.bitmap & !(1 << idx),
+            None => self.bitmap,
+        } & !self.disabled;
+        match self.spill {
+            SpillPolicy::Any => {}
+            SpillPolicy::SameZone => {
+                let zone = self.regions[first].flags;
+                for idx in set_bits(mask) {
+                    if self.regions[idx].flags != zone {
+                        mask &= !(1 << idx);
+                    }
+                }
+            }
+            SpillPolicy::Never => mask &= 1 << first,
+        }
+        mask
+    }
+
+    /// Iterates over the indices of the regions in use, lowest first.
+    fn region_indices(&self) -> impl Iterator<Item = usize> {
+        set_bits(self.bitmap)
+    }
+
+    fn regions(&self) -> impl Iterator<Item = &Region> {
+        self.region_indices().map(|idx| &self.regions[idx])
+    }
+}
+
+/// Iterates over the indices of the set bits in `mask`, lowest first.
+fn set_bits(mut mask: usize) -> impl Iterator<Item = usize> {
+    core::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+        let idx = mask.trailing_zeros() as usize;
+        mask &= mask - 1;
+        Some(idx)
+    })
+}
+
+fn fragmentation(total_free: usize, largest_free: usize) -> u8 {
+    if total_free == 0 {
+        return 0;
+    }
+    (100 - (largest_free as u128 * 100 / total_free as u128)) as u8
 }
 
-impl BaseAllocator for EarlyAllocator {
+impl<const PAGE_SIZE: usize> Default for EarlyAllocator<PAGE_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl ByteAllocator for EarlyAllocator {
+impl<const PAGE_SIZE: usize> BaseAllocator for EarlyAllocator<PAGE_SIZE> {
+    fn init(&mut self, start: usize, size: usize) {
+        self.regions = [Region::EMPTY; MAX_REGIONS];
+        self.bitmap = 0;
+        self.raw_bytes = 0;
+        self.disabled = 0;
+        self.counters.reset();
+        #[cfg(feature = "latency")]
+        self.latency.reset();
+        #[cfg(feature = "axlog")]
+        {
+            self.low_memory = false;
+        }
+        self.sorted = [0; MAX_REGIONS];
+        self.current_region = 0;
+        self.quarantine.clear();
+        self.free_lists = FreeLists::new();
+        self.page_refs = PageRefs::new();
+        self.top_spans = TopSpans::new();
+        self.emergency = None;
+        self.initialized = false;
+        self.sealed = false;
+        self.scratch = Scratch::new();
+        #[cfg(feature = "track")]
+        {
+            self.tracker = Tracker::new();
+        }
+        #[cfg(feature = "canary")]
+        {
+            self.canaries = [[None; 2]; MAX_REGIONS];
+        }
+        #[cfg(feature = "sentinel")]
+        {
+            self.sentinels = 0;
+        }
+        // `init` cannot report errors: invalid memory leaves the allocator
+        // empty, so every allocation fails instead of panicking this early.
+        if self.add_memory(start, size).is_err() {
+            early_warn!(
+                "early allocator: ignoring invalid memory [{:#x}, +{:#x})",
+                start,
+                size
+            );
+        }
+    }
+
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        self.add_memory_flags(start, size, 0)
+    }
 }
 
-impl PageAllocator for EarlyAllocator {
+impl<const PAGE_SIZE: usize> ByteAllocator for EarlyAllocator<PAGE_SIZE> {
+    #[track_caller]
+    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        #[cfg(feature = "latency")]
+        let start = self.latency.now();
+        let res = self
+            .alloc_block(layout)
+            .and_then(|(pos, _)| NonNull::new(pos as *mut u8).ok_or(AllocError::NoMemory));
+        #[cfg(feature = "latency")]
+        self.latency.record_bytes(start);
+        #[cfg(feature = "trace")]
+        self.record(
+            TraceOp::Alloc,
+            layout.size(),
+            layout.align(),
+            res.as_ref().ok().map(|p| p.as_ptr() as usize),
+        );
+        res
+    }
+
+    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
+        #[cfg(feature = "trace")]
+        self.record(
+            TraceOp::Dealloc,
+            layout.size(),
+            layout.align(),
+            Some(pos.as_ptr() as usize),
+        );
+        let pos = self.untag(pos.as_ptr() as usize, Some(layout.size()));
+        self.hooks.freed(pos, layout.size());
+        #[cfg(feature = "track")]
+        self.tracker.remove(pos);
+        if let Some((pos, size)) = self.quarantine.push((pos, layout.size())) {
+            self.release_bytes(pos, size);
+        }
+    }
+
+    fn total_bytes(&self) -> usize {
+        self.regions().map(Region::size).sum()
+    }
+
+    fn used_bytes(&self) -> usize {
+        self.regions().map(Region::used_bytes).sum()
+    }
+
+    fn available_bytes(&self) -> usize {
+        self.regions().map(|r| r.available_bytes() + r.freed).sum()
+    }
+}
+
+impl<const PAGE_SIZE: usize> PageAllocator for EarlyAllocator<PAGE_SIZE> {
+    const PAGE_SIZE: usize = PAGE_SIZE;
+
+    fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        #[cfg(feature = "latency")]
+        let start = self.latency.now();
+        let res = self.alloc_page_run(num_pages, align_pow2, false, None);
+        #[cfg(feature = "latency")]
+        self.latency.record_pages(start);
+        #[cfg(feature = "trace")]
+        self.record(
+            TraceOp::AllocPages,
+            num_pages,
+            align_pow2,
+            res.as_ref().ok().copied(),
+        );
+        res
+    }
+
+    fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
+        self.dealloc_page_run(pos, num_pages);
+    }
+
+    fn total_pages(&self) -> usize {
+        self.regions().map(|r| r.total_pages(PAGE_SIZE)).sum()
+    }
+
+    fn used_pages(&self) -> usize {
+        self.live_pages()
+    }
+
+    fn available_pages(&self) -> usize {
+        self.regions()
+            .map(|r| r.available_pages(PAGE_SIZE) + r.freed / PAGE_SIZE)
+            .sum()
+    }
 }
@@ -0,0 +1,326 @@
+use allocator::{AllocResult, BaseAllocator, ByteAllocator, PageAllocator};
+use core::alloc::Layout;
+#[cfg(loom)]
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+#[cfg(loom)]
+use crate::sync::MutPtr;
+use crate::sync::{fence, spin_loop, AtomicBool, AtomicUsize, Ordering, UnsafeCell};
+use crate::{EarlyAllocError, EarlyAllocator, MemoryObserver};
+
+/// An [`EarlyAllocator`] behind a spinlock, so it can be shared between
+/// CPUs before the kernel has proper locks of its own.
+///
+/// Memory can be added at any time, e.g. by a memory controller brought up
+/// late, while other CPUs keep allocating: the new region is published when
+/// the lock is released.
+pub struct LockedEarlyAllocator<const PAGE_SIZE: usize> {
+    locked: AtomicBool,
+    /// Statistics republished on every unlock.
+    stats: SeqStats,
+    inner: UnsafeCell<EarlyAllocator<PAGE_SIZE>>,
+}
+
+/// A consistent set of statistics, see
+/// [`LockedEarlyAllocator::stats_snapshot`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    /// Total bytes managed.
+    pub total_bytes: usize,
+    /// Bytes in use, pages included.
+    pub used_bytes: usize,
+    /// Whole pages managed.
+    pub total_pages: usize,
+    /// Pages in use.
+    pub used_pages: usize,
+    /// The highest `used_bytes` published so far.
+    pub peak_used_bytes: usize,
+}
+
+/// The published statistics, guarded by a sequence counter that is odd
+/// while they are being rewritten.
+///
+/// Only the lock holder writes them, so there is a single writer.
+struct SeqStats {
+    seq: AtomicUsize,
+    fields: [AtomicUsize; 5],
+}
+
+impl SeqStats {
+    #[cfg(not(loom))]
+    const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicUsize = AtomicUsize::new(0);
+        Self {
+            seq: AtomicUsize::new(0),
+            fields: [ZERO; 5],
+        }
+    }
+
+    /// Starts out with `stats` already published.
+    #[cfg(not(loom))]
+    const fn published(stats: StatsSnapshot) -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            fields: [
+                AtomicUsize::new(stats.total_bytes),
+                AtomicUsize::new(stats.used_bytes),
+                AtomicUsize::new(stats.total_pages),
+                AtomicUsize::new(stats.used_pages),
+                AtomicUsize::new(stats.peak_used_bytes),
+            ],
+        }
+    }
+
+    #[cfg(loom)]
+    fn new() -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            fields: core::array::from_fn(|_| AtomicUsize::new(0)),
+        }
+    }
+
+    fn publish(&self, stats: StatsSnapshot) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        let values = [
+            stats.total_bytes,
+            stats.used_bytes,
+            stats.total_pages,
+            stats.used_pages,
+            stats.peak_used_bytes,
+        ];
+        for (field, value) in self.fields.iter().zip(values) {
+            field.store(value, Ordering::Relaxed);
+        }
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    fn read(&self) -> StatsSnapshot {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq % 2 == 1 {
+                spin_loop();
+                continue;
+            }
+            let v = self.fields.each_ref().map(|f| f.load(Ordering::Relaxed));
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return StatsSnapshot {
+                    total_bytes: v[0],
+                    used_bytes: v[1],
+                    total_pages: v[2],
+                    used_pages: v[3],
+                    peak_used_bytes: v[4],
+                };
+            }
+        }
+    }
+}
+
+// SAFETY: the inner allocator is only reached through a guard, and at most
+// one guard exists at a time.
+unsafe impl<const PAGE_SIZE: usize> Sync for LockedEarlyAllocator<PAGE_SIZE> {}
+
+impl<const PAGE_SIZE: usize> LockedEarlyAllocator<PAGE_SIZE> {
+    /// Creates an unlocked, empty allocator.
+    #[cfg(not(loom))]
+    pub const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            stats: SeqStats::new(),
+            inner: UnsafeCell::new(EarlyAllocator::new()),
+        }
+    }
+
+    /// Creates an unlocked allocator managing `[start, start + size)`, see
+    /// [`EarlyAllocator::with_region`].
+    #[cfg(not(loom))]
+    pub const fn with_region(start: usize, size: usize) -> Self {
+        let inner = EarlyAllocator::with_region(start, size);
+        let region = &inner.regions[0];
+        let stats = StatsSnapshot {
+            total_bytes: region.end - region.start,
+            used_bytes: 0,
+            total_pages: region.total_pages(PAGE_SIZE),
+            used_pages: 0,
+            peak_used_bytes: 0,
+        };
+        Self {
+            locked: AtomicBool::new(false),
+            stats: SeqStats::published(stats),
+            inner: UnsafeCell::new(inner),
+        }
+    }
+
+    /// Creates an unlocked, empty allocator.
+    #[cfg(loom)]
+    pub fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            stats: SeqStats::new(),
+            inner: UnsafeCell::new(EarlyAllocator::new()),
+        }
+    }
+
+    /// Adds `[start, start + size)` under the lock, see
+    /// [`add_memory`](BaseAllocator::add_memory). CPUs allocating
+    /// concurrently see the new region from their next allocation on.
+    pub fn add_memory(&self, start: usize, size: usize) -> AllocResult {
+        self.lock().add_memory(start, size)
+    }
+
+    /// Returns the total bytes managed, without taking the lock.
+    ///
+    /// The figure is the one published by the last unlock, so it may lag
+    /// behind a concurrent `add_memory` that has not returned yet.
+    pub fn total_bytes(&self) -> usize {
+        self.stats.fields[0].load(Ordering::Acquire)
+    }
+
+    /// Returns the statistics published by the last unlock, without taking
+    /// the lock: unlike separate reads, they are never torn by a concurrent
+    /// update.
+    ///
+    /// The peak is sampled on unlock, so it misses a higher use that did
+    /// not outlive a single lock section.
+    pub fn stats_snapshot(&self) -> StatsSnapshot {
+        self.stats.read()
+    }
+
+    /// Registers `observer` under the lock, see
+    /// [`EarlyAllocator::register_observer`].
+    pub fn register_observer(&self, observer: &'static dyn MemoryObserver) -> AllocResult {
+        self.lock().register_observer(observer)
+    }
+
+    /// Unregisters `observer` under the lock, see
+    /// [`EarlyAllocator::unregister_observer`].
+    pub fn unregister_observer(&self, observer: &'static dyn MemoryObserver) -> bool {
+        self.lock().unregister_observer(observer)
+    }
+
+    /// Allocates a byte block without ever waiting for the lock, for
+    /// interrupt and exception handlers.
+    ///
+    /// Fails with [`EarlyAllocError::WouldBlock`] if the lock is held: the
+    /// holder may be the very code the handler interrupted, so spinning could
+    /// deadlock. The installed hooks, and the `axlog` warnings with that
+    /// feature, still run under the lock and must be safe in that context.
+    pub fn try_alloc_irq(&self, layout: Layout) -> Result<NonNull<u8>, EarlyAllocError> {
+        let mut guard = self.try_lock().ok_or(EarlyAllocError::WouldBlock)?;
+        Ok(guard.alloc(layout)?)
+    }
+
+    /// Spins until the lock is acquired.
+    pub fn lock(&self) -> EarlyAllocatorGuard<'_, PAGE_SIZE> {
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            while self.locked.load(Ordering::Relaxed) {
+                spin_loop();
+            }
+        }
+    }
+
+    /// Acquires the lock if it is free.
+    pub fn try_lock(&self) -> Option<EarlyAllocatorGuard<'_, PAGE_SIZE>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| {
+                #[allow(unused_mut)]
+                let mut guard = EarlyAllocatorGuard {
+                    lock: self,
+                    #[cfg(loom)]
+                    access: ManuallyDrop::new(self.inner.get_mut()),
+                    #[cfg(feature = "latency")]
+                    acquired: None,
+                };
+                #[cfg(feature = "latency")]
+                {
+                    guard.acquired = guard.latency.now();
+                }
+                guard
+            })
+    }
+}
+
+impl<const PAGE_SIZE: usize> Default for LockedEarlyAllocator<PAGE_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Exclusive access to the allocator of a [`LockedEarlyAllocator`]; the lock
+/// is released on drop.
+pub struct EarlyAllocatorGuard<'a, const PAGE_SIZE: usize> {
+    lock: &'a LockedEarlyAllocator<PAGE_SIZE>,
+    /// The access to the allocator `loom` checks for as long as the guard
+    /// lives. It ends before the lock is released.
+    #[cfg(loom)]
+    access: ManuallyDrop<MutPtr<EarlyAllocator<PAGE_SIZE>>>,
+    #[cfg(feature = "latency")]
+    acquired: Option<u64>,
+}
+
+impl<const PAGE_SIZE: usize> Deref for EarlyAllocatorGuard<'_, PAGE_SIZE> {
+    type Target = EarlyAllocator<PAGE_SIZE>;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the guard holds the lock.
+        #[cfg(not(loom))]
+        unsafe {
+            &*self.lock.inner.get()
+        }
+        // SAFETY: the guard holds the lock.
+        #[cfg(loom)]
+        unsafe {
+            MutPtr::deref(&self.access)
+        }
+    }
+}
+
+impl<const PAGE_SIZE: usize> DerefMut for EarlyAllocatorGuard<'_, PAGE_SIZE> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: the guard holds the lock.
+        #[cfg(not(loom))]
+        unsafe {
+            &mut *self.lock.inner.get()
+        }
+        // SAFETY: the guard holds the lock.
+        #[cfg(loom)]
+        unsafe {
+            MutPtr::deref(&self.access)
+        }
+    }
+}
+
+impl<const PAGE_SIZE: usize> Drop for EarlyAllocatorGuard<'_, PAGE_SIZE> {
+    fn drop(&mut self) {
+        let used_bytes = self.used_bytes();
+        let peak = self.lock.stats.fields[4].load(Ordering::Relaxed);
+        self.lock.stats.publish(StatsSnapshot {
+            total_bytes: self.total_bytes(),
+            used_bytes,
+            total_pages: self.total_pages(),
+            used_pages: self.used_pages(),
+            peak_used_bytes: peak.max(used_bytes),
+        });
+        #[cfg(feature = "latency")]
+        {
+            let acquired = self.acquired;
+            self.latency.record_locked(acquired);
+        }
+        // SAFETY: `access` is not used anymore.
+        #[cfg(loom)]
+        unsafe {
+            ManuallyDrop::drop(&mut self.access)
+        };
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,216 @@
+//! Reference model for fuzz and property testing, enabled by the `model`
+//! feature.
+
+use allocator::{AllocResult, BaseAllocator, ByteAllocator, PageAllocator};
+use core::alloc::Layout;
+use core::ops::Range;
+use core::ptr::NonNull;
+
+use crate::EarlyAllocator;
+
+/// Maximum number of live ranges the [`ShadowModel`] can hold.
+pub const MODEL_CAPACITY: usize = 256;
+
+/// A simple interval set of granted ranges.
+///
+/// It knows nothing about how ranges are placed; it only remembers which
+/// ones are currently handed out.
+pub struct ShadowModel {
+    ranges: [(usize, usize); MODEL_CAPACITY],
+    len: usize,
+}
+
+impl ShadowModel {
+    /// Creates an empty model.
+    pub const fn new() -> Self {
+        Self {
+            ranges: [(0, 0); MODEL_CAPACITY],
+            len: 0,
+        }
+    }
+
+    /// Number of live ranges.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no range is live.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the live ranges, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.ranges[..self.len]
+            .iter()
+            .map(|&(start, end)| start..end)
+    }
+
+    /// Returns a live range overlapping `range`, if any.
+    pub fn overlapping(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        self.iter()
+            .find(|r| r.start < range.end && range.start < r.end)
+    }
+
+    /// Records `range` as granted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the model is full or `range` overlaps a live range.
+    pub fn insert(&mut self, range: Range<usize>) {
+        if let Some(other) = self.overlapping(&range) {
+            panic!("granted {:#x?} overlaps live {:#x?}", range, other);
+        }
+        assert!(self.len < MODEL_CAPACITY, "shadow model is full");
+        self.ranges[self.len] = (range.start, range.end);
+        self.len += 1;
+    }
+
+    /// Forgets the granted `range`, returning `false` if it was not live.
+    pub fn remove(&mut self, range: &Range<usize>) -> bool {
+        let Some(idx) = self.iter().position(|r| r == *range) else {
+            return false;
+        };
+        self.len -= 1;
+        self.ranges[idx] = self.ranges[self.len];
+        true
+    }
+
+    /// Forgets all ranges.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Default for ShadowModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An [`EarlyAllocator`] whose every allocation and deallocation is
+/// cross-checked against a [`ShadowModel`].
+///
+/// Granted blocks must be aligned, lie in a single managed region and not
+/// overlap any live block; freed blocks must be live. Any violation panics,
+/// which is what a fuzz target wants. The checks work on raw addresses, so
+/// no memory tagger should be installed.
+pub struct ModelChecked<const PAGE_SIZE: usize> {
+    inner: EarlyAllocator<PAGE_SIZE>,
+    model: ShadowModel,
+}
+
+impl<const PAGE_SIZE: usize> ModelChecked<PAGE_SIZE> {
+    /// Wraps `inner`, which must not have any live allocation yet.
+    pub const fn new(inner: EarlyAllocator<PAGE_SIZE>) -> Self {
+        Self {
+            inner,
+            model: ShadowModel::new(),
+        }
+    }
+
+    /// Returns the checked allocator.
+    pub fn inner(&self) -> &EarlyAllocator<PAGE_SIZE> {
+        &self.inner
+    }
+
+    /// Returns the shadow model.
+    pub fn model(&self) -> &ShadowModel {
+        &self.model
+    }
+
+    /// Unwraps the checked allocator.
+    pub fn into_inner(self) -> EarlyAllocator<PAGE_SIZE> {
+        self.inner
+    }
+
+    fn granted(&mut self, start: usize, size: usize, align: usize) {
+        let range = start..start + size;
+        assert!(
+            start % align == 0,
+            "{:#x} is not aligned to {:#x}",
+            start,
+            align
+        );
+        let last = range.end.max(start + 1) - 1;
+        let region = self.inner.region_of(start);
+        assert!(
+            region.is_some() && region == self.inner.region_of(last),
+            "granted {:#x?} is out of bounds",
+            range
+        );
+        self.model.insert(range);
+    }
+
+    fn freed(&mut self, start: usize, size: usize) {
+        let range = start..start + size;
+        assert!(
+            self.model.remove(&range),
+            "freed {:#x?} was not granted",
+            range
+        );
+    }
+}
+
+impl<const PAGE_SIZE: usize> BaseAllocator for ModelChecked<PAGE_SIZE> {
+    fn init(&mut self, start: usize, size: usize) {
+        self.model.clear();
+        self.inner.init(start, size);
+    }
+
+    fn add_memory(&mut self, start: usize, size: usize) -> AllocResult {
+        self.inner.add_memory(start, size)
+    }
+}
+
+impl<const PAGE_SIZE: usize> ByteAllocator for ModelChecked<PAGE_SIZE> {
+    fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        let ptr = self.inner.alloc(layout)?;
+        self.granted(ptr.as_ptr() as usize, layout.size(), layout.align());
+        Ok(ptr)
+    }
+
+    fn dealloc(&mut self, pos: NonNull<u8>, layout: Layout) {
+        self.freed(pos.as_ptr() as usize, layout.size());
+        self.inner.dealloc(pos, layout);
+    }
+
+    fn total_bytes(&self) -> usize {
+        self.inner.total_bytes()
+    }
+
+    fn used_bytes(&self) -> usize {
+        self.inner.used_bytes()
+    }
+
+    fn available_bytes(&self) -> usize {
+        self.inner.available_bytes()
+    }
+}
+
+impl<const PAGE_SIZE: usize> PageAllocator for ModelChecked<PAGE_SIZE> {
+    const PAGE_SIZE: usize = PAGE_SIZE;
+
+    fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        let pos = self.inner.alloc_pages(num_pages, align_pow2)?;
+        self.granted(pos, num_pages * PAGE_SIZE, align_pow2);
+        Ok(pos)
+    }
+
+    fn dealloc_pages(&mut self, pos: usize, num_pages: usize) {
+        self.freed(pos, num_pages * PAGE_SIZE);
+        self.inner.dealloc_pages(pos, num_pages);
+    }
+
+    fn total_pages(&self) -> usize {
+        self.inner.total_pages()
+    }
+
+    fn used_pages(&self) -> usize {
+        self.inner.used_pages()
+    }
+
+    fn available_pages(&self) -> usize {
+        self.inner.available_pages()
+    }
+}
@@ -0,0 +1,21 @@
+//! Cooperation with memory tagging (e.g. ARM MTE).
+
+/// Size of a tag granule in bytes. Tagged byte allocations are aligned and
+/// padded to this size.
+pub const TAG_GRANULE: usize = 16;
+
+/// Architecture hooks for memory tagging.
+///
+/// When installed with [`EarlyAllocator::set_tagger`], byte allocations come
+/// back as tagged pointers whose granules carry the same tag. Pages are left
+/// untagged.
+///
+/// [`EarlyAllocator::set_tagger`]: crate::EarlyAllocator::set_tagger
+pub trait MemoryTagger: Sync {
+    /// Picks a fresh tag for `[addr, addr + len)`, tags all of its granules,
+    /// and returns `addr` with the tag baked in.
+    fn tag_range(&self, addr: usize, len: usize) -> usize;
+
+    /// Removes the tag bits from `ptr`.
+    fn untag(&self, ptr: usize) -> usize;
+}
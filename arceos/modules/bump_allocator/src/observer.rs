@@ -0,0 +1,59 @@
+//! Notification of memory hot-add.
+
+use core::ops::Range;
+
+/// Maximum number of observers registered at once.
+pub const OBSERVER_CAPACITY: usize = 4;
+
+/// Gets notified whenever memory is added to an [`EarlyAllocator`], so that
+/// dependent subsystems (a page-table mapper, a zone balancer, ...) can react
+/// without polling.
+///
+/// [`EarlyAllocator`]: crate::EarlyAllocator
+pub trait MemoryObserver: Sync {
+    /// Called after `range` became a new region with the given `flags`.
+    ///
+    /// A range clipped by exclusions is reported once per remaining piece.
+    fn memory_added(&self, range: Range<usize>, flags: u32);
+}
+
+pub(crate) struct Observers {
+    slots: [Option<&'static dyn MemoryObserver>; OBSERVER_CAPACITY],
+}
+
+impl Observers {
+    pub const fn new() -> Self {
+        Self {
+            slots: [None; OBSERVER_CAPACITY],
+        }
+    }
+
+    pub fn register(&mut self, observer: &'static dyn MemoryObserver) -> bool {
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(observer);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn unregister(&mut self, observer: &'static dyn MemoryObserver) -> bool {
+        let slot = self.slots.iter_mut().find(|slot| {
+            slot.is_some_and(|o| core::ptr::addr_eq(o as *const _, observer as *const _))
+        });
+        match slot {
+            Some(slot) => {
+                *slot = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn notify(&self, range: Range<usize>, flags: u32) {
+        for observer in self.slots.iter().flatten() {
+            observer.memory_added(range.clone(), flags);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! The report handed to [`Hooks::on_oom`](crate::Hooks::on_oom).
+
+use allocator::{ByteAllocator, PageAllocator};
+use core::alloc::Layout;
+use core::fmt;
+
+use crate::EarlyAllocator;
+
+/// The request that could not be satisfied.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum OomRequest {
+    Bytes(Layout),
+    Pages { num_pages: usize, align: usize },
+}
+
+/// Describes a failed allocation, the region table and the counters.
+pub(crate) struct OomReport<'a, const PAGE_SIZE: usize> {
+    pub alloc: &'a EarlyAllocator<PAGE_SIZE>,
+    pub request: OomRequest,
+}
+
+impl<const PAGE_SIZE: usize> fmt::Display for OomReport<'_, PAGE_SIZE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let a = self.alloc;
+        match self.request {
+            OomRequest::Bytes(layout) => writeln!(
+                f,
+                "early allocator: out of memory for {} bytes aligned to {:#x}",
+                layout.size(),
+                layout.align()
+            )?,
+            OomRequest::Pages { num_pages, align } => writeln!(
+                f,
+                "early allocator: out of memory for {} pages aligned to {:#x}",
+                num_pages, align
+            )?,
+        }
+        for idx in a.region_indices() {
+            let r = &a.regions[idx];
+            writeln!(
+                f,
+                "  region {:2}: [{:#x}, {:#x}) b_pos {:#x} p_pos {:#x} count {} freed {:#x} flags {:#x}{}",
+                idx,
+                r.start,
+                r.end,
+                r.b_pos,
+                r.p_pos,
+                r.count,
+                r.freed,
+                r.flags,
+                if a.is_region_enabled(idx) { "" } else { " (disabled)" }
+            )?;
+        }
+        write!(
+            f,
+            "  bytes: {} used, {} available; pages: {} used, {} available; fragmentation {}%",
+            a.used_bytes(),
+            a.available_bytes(),
+            a.used_pages(),
+            a.available_pages(),
+            a.fragmentation()
+        )
+    }
+}
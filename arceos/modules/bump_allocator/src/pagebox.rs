@@ -0,0 +1,62 @@
+//! Page runs owned by a handle that frees them on drop.
+
+use allocator::{AllocResult, PageAllocator};
+use core::mem::ManuallyDrop;
+
+use crate::LockedEarlyAllocator;
+
+/// A page run that is given back to its [`LockedEarlyAllocator`] on drop.
+///
+/// Returned by [`LockedEarlyAllocator::alloc_pages_owned`]. The allocator is
+/// only borrowed shared, so a driver can keep its buffers in one of these
+/// across probe and teardown, and an early return frees them on the way out.
+pub struct PageBox<'a, const PAGE_SIZE: usize> {
+    alloc: &'a LockedEarlyAllocator<PAGE_SIZE>,
+    addr: usize,
+    num_pages: usize,
+}
+
+impl<const PAGE_SIZE: usize> PageBox<'_, PAGE_SIZE> {
+    /// Start address of the run.
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    /// Number of pages in the run.
+    pub fn num_pages(&self) -> usize {
+        self.num_pages
+    }
+
+    /// Size of the run in bytes.
+    pub fn size(&self) -> usize {
+        self.num_pages * PAGE_SIZE
+    }
+
+    /// Keeps the run allocated and returns its address without freeing it.
+    pub fn leak(self) -> usize {
+        ManuallyDrop::new(self).addr
+    }
+}
+
+impl<const PAGE_SIZE: usize> Drop for PageBox<'_, PAGE_SIZE> {
+    fn drop(&mut self) {
+        self.alloc.lock().dealloc_pages(self.addr, self.num_pages);
+    }
+}
+
+impl<const PAGE_SIZE: usize> LockedEarlyAllocator<PAGE_SIZE> {
+    /// Like [`PageAllocator::alloc_pages`], but returns the run in a
+    /// [`PageBox`] that frees it when dropped.
+    pub fn alloc_pages_owned(
+        &self,
+        num_pages: usize,
+        align_pow2: usize,
+    ) -> AllocResult<PageBox<'_, PAGE_SIZE>> {
+        let addr = self.lock().alloc_pages(num_pages, align_pow2)?;
+        Ok(PageBox {
+            alloc: self,
+            addr,
+            num_pages,
+        })
+    }
+}
@@ -0,0 +1,53 @@
+//! A dedicated entry point for page-table pages.
+
+use allocator::{AllocResult, PageAllocator};
+
+use crate::EarlyAllocator;
+
+/// A zeroed, page-aligned page allocated to hold a page table.
+///
+/// Returned by [`EarlyAllocator::alloc_page_table`]. It is neither `Copy`
+/// nor `Clone`, and giving it back with
+/// [`dealloc_page_table`](EarlyAllocator::dealloc_page_table) consumes it,
+/// so a table cannot be freed twice. Dropping it keeps the page allocated,
+/// as tables installed for the kernel usually are.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct PageTableFrame {
+    addr: usize,
+}
+
+impl PageTableFrame {
+    /// Start address of the page.
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+}
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Allocates one zeroed page aligned to `PAGE_SIZE` for a page table,
+    /// counted in [`page_table_pages`](Self::page_table_pages).
+    pub fn alloc_page_table(&mut self) -> AllocResult<PageTableFrame> {
+        let addr = self.alloc_pages(1, PAGE_SIZE)?;
+        // SAFETY: the page was just allocated and is not shared yet.
+        unsafe { core::ptr::write_bytes(addr as *mut u8, 0, PAGE_SIZE) };
+        self.counters.page_table_allocated();
+        Ok(PageTableFrame { addr })
+    }
+
+    /// Frees a page allocated by [`alloc_page_table`](Self::alloc_page_table).
+    ///
+    /// If the page was already freed otherwise, the free is ignored and the
+    /// page still counts as a page-table page.
+    pub fn dealloc_page_table(&mut self, frame: PageTableFrame) {
+        if self.dealloc_page_run(frame.addr, 1) {
+            self.counters.page_table_freed();
+        }
+    }
+
+    /// Returns the number of page-table pages in use, or `0` with the
+    /// `no-stats` feature. They are included in
+    /// [`used_pages`](PageAllocator::used_pages).
+    pub fn page_table_pages(&self) -> usize {
+        self.counters.page_table_pages()
+    }
+}
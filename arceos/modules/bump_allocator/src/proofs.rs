@@ -0,0 +1,77 @@
+//! Kani proof harnesses, enabled by the `verify-kani` feature.
+//!
+//! Run with `cargo kani --features verify-kani`.
+
+use core::alloc::Layout;
+
+use crate::region::Region;
+use crate::Rebalance;
+
+fn any_region() -> Region {
+    let start: usize = kani::any();
+    let end: usize = kani::any();
+    kani::assume(start <= end);
+    Region::new(start, end)
+}
+
+fn any_layout() -> Layout {
+    let size: usize = kani::any();
+    let shift: u32 = kani::any();
+    kani::assume(shift < usize::BITS);
+    let layout = Layout::from_size_align(size, 1 << shift);
+    kani::assume(layout.is_ok());
+    layout.unwrap()
+}
+
+fn any_rebalance() -> Rebalance {
+    if kani::any() {
+        Rebalance::Fixed
+    } else {
+        Rebalance::Auto
+    }
+}
+
+fn assert_granted(r: &Region, pos: usize, size: usize, align: usize) {
+    assert!(pos >= r.start);
+    assert!(pos.checked_add(size).is_some_and(|end| end <= r.end));
+    assert!(pos % align == 0);
+    assert!(r.start <= r.b_pos && r.b_pos <= r.p_pos && r.p_pos <= r.end);
+}
+
+#[kani::proof]
+fn alloc_bytes_within_region_and_aligned() {
+    let mut r = any_region();
+    let layout = any_layout();
+    if let Ok(pos) = r.alloc_bytes(layout, kani::any(), any_rebalance()) {
+        assert_granted(&r, pos, layout.size(), layout.align());
+        assert!(pos + layout.size() == r.b_pos);
+    }
+}
+
+#[kani::proof]
+fn alloc_pages_within_region_and_aligned() {
+    let mut r = any_region();
+    let layout = any_layout();
+    if let Ok(pos) = r.alloc_pages(layout.size(), layout.align(), any_rebalance()) {
+        assert_granted(&r, pos, layout.size(), layout.align());
+        assert!(pos == r.p_pos);
+    }
+}
+
+#[kani::proof]
+fn allocations_never_overlap() {
+    let mut r = any_region();
+    let (first, second) = (any_layout(), any_layout());
+    let rebalance = any_rebalance();
+    let Ok(a) = r.alloc_bytes(first, 0, rebalance) else {
+        return;
+    };
+    let b = if kani::any() {
+        r.alloc_bytes(second, kani::any(), rebalance)
+    } else {
+        r.alloc_pages(second.size(), second.align(), rebalance)
+    };
+    if let Ok(b) = b {
+        assert!(a + first.size() <= b || b + second.size() <= a);
+    }
+}
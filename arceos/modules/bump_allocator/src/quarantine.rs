@@ -0,0 +1,66 @@
+/// Maximum number of freed blocks that can be held back at once.
+pub const QUARANTINE_CAPACITY: usize = 16;
+
+/// A FIFO of freed byte blocks whose release is being delayed.
+pub(crate) struct Quarantine {
+    slots: [(usize, usize); QUARANTINE_CAPACITY],
+    head: usize,
+    len: usize,
+    depth: usize,
+}
+
+impl Quarantine {
+    pub const fn new() -> Self {
+        Self {
+            slots: [(0, 0); QUARANTINE_CAPACITY],
+            head: 0,
+            len: 0,
+            depth: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth.min(QUARANTINE_CAPACITY);
+    }
+
+    /// Queues the `(pos, size)` block and returns the block that must be
+    /// released now to keep at most `depth` blocks in quarantine, if any.
+    pub fn push(&mut self, block: (usize, usize)) -> Option<(usize, usize)> {
+        if self.depth == 0 {
+            return Some(block);
+        }
+        let evicted = if self.len >= self.depth {
+            self.pop()
+        } else {
+            None
+        };
+        self.slots[(self.head + self.len) % QUARANTINE_CAPACITY] = block;
+        self.len += 1;
+        evicted
+    }
+
+    /// Forgets every queued block without releasing it.
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Removes the oldest block from quarantine.
+    pub fn pop(&mut self) -> Option<(usize, usize)> {
+        if self.len == 0 {
+            return None;
+        }
+        let block = self.slots[self.head];
+        self.head = (self.head + 1) % QUARANTINE_CAPACITY;
+        self.len -= 1;
+        Some(block)
+    }
+}
@@ -0,0 +1,102 @@
+//! A fixed-layout view of the allocator state for debuggers and crash dump
+//! scripts.
+
+use allocator::ByteAllocator;
+
+use crate::{EarlyAllocator, MAX_REGIONS};
+
+/// Value of [`RawState::magic`], to find the view in a memory dump.
+pub const RAW_STATE_MAGIC: u32 = 0xea11_0c5e;
+
+/// Value of [`RawState::version`]. Fields are only ever appended, and the
+/// version is bumped when they are.
+pub const RAW_STATE_VERSION: u32 = 1;
+
+/// One region of a [`RawState`]. An unused slot is all zeroes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RawRegion {
+    /// Start address of the region.
+    pub start: usize,
+    /// End address of the region, exclusive.
+    pub end: usize,
+    /// The bytes cursor.
+    pub b_pos: usize,
+    /// The pages cursor.
+    pub p_pos: usize,
+    /// Number of live byte allocations.
+    pub count: usize,
+    /// Bytes in freed pages above `p_pos`.
+    pub freed: usize,
+    /// The flags the region was added with.
+    pub flags: u32,
+    /// The allocation priority of the region.
+    pub priority: u8,
+    /// Non-zero if the region is disabled.
+    pub disabled: u8,
+    _reserved: u16,
+}
+
+/// The allocator state with a stable `#[repr(C)]` layout, returned by
+/// [`EarlyAllocator::as_raw_state`].
+///
+/// Words are `usize`, so a decoder must know the pointer width of the
+/// target, as it does for any other structure in the dump.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RawState {
+    /// Always [`RAW_STATE_MAGIC`].
+    pub magic: u32,
+    /// Always [`RAW_STATE_VERSION`].
+    pub version: u32,
+    /// The `PAGE_SIZE` of the allocator.
+    pub page_size: usize,
+    /// Number of slots in `regions`, i.e. [`MAX_REGIONS`].
+    pub max_regions: usize,
+    /// Bit `i` is set if slot `i` of `regions` is in use.
+    pub bitmap: usize,
+    /// The region that served the previous request.
+    pub current_region: usize,
+    /// Bytes in use, pages included.
+    pub used_bytes: usize,
+    /// Pages in use.
+    pub used_pages: usize,
+    /// The region table, indexed like the other region methods.
+    pub regions: [RawRegion; MAX_REGIONS],
+}
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Returns a [`RawState`] view of the allocator.
+    ///
+    /// Copying it into a `#[no_mangle]` static after each change, or at
+    /// panic time, gives debuggers a well-known symbol to decode.
+    pub fn as_raw_state(&self) -> RawState {
+        let mut regions = [RawRegion::default(); MAX_REGIONS];
+        for idx in self.region_indices() {
+            let r = &self.regions[idx];
+            regions[idx] = RawRegion {
+                start: r.start,
+                end: r.end,
+                b_pos: r.b_pos,
+                p_pos: r.p_pos,
+                count: r.count,
+                freed: r.freed,
+                flags: r.flags,
+                priority: r.priority,
+                disabled: u8::from(!self.is_region_enabled(idx)),
+                _reserved: 0,
+            };
+        }
+        RawState {
+            magic: RAW_STATE_MAGIC,
+            version: RAW_STATE_VERSION,
+            page_size: PAGE_SIZE,
+            max_regions: MAX_REGIONS,
+            bitmap: self.bitmap,
+            current_region: self.current_region,
+            used_bytes: self.used_bytes(),
+            used_pages: self.live_pages(),
+            regions,
+        }
+    }
+}
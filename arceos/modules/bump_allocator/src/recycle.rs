@@ -0,0 +1,107 @@
+//! Small per-size-class free lists for recycling freed byte blocks.
+
+use core::alloc::Layout;
+use core::mem::{align_of, size_of};
+use core::ops::Range;
+
+const MIN_SHIFT: u32 = size_of::<usize>().trailing_zeros();
+const MAX_SHIFT: u32 = 11;
+const NUM_CLASSES: usize = (MAX_SHIFT - MIN_SHIFT + 1) as usize;
+
+/// Singly linked lists of freed blocks, one per power-of-two size class.
+///
+/// The link to the next block is stored in the first word of each freed
+/// block, so a block of class `1 << shift` is at least that large. Blocks
+/// are filed under the largest class they can hold, and requests are served
+/// from the smallest class that fits them.
+pub(crate) struct FreeLists {
+    heads: [usize; NUM_CLASSES],
+}
+
+impl FreeLists {
+    pub const fn new() -> Self {
+        Self {
+            heads: [0; NUM_CLASSES],
+        }
+    }
+
+    /// Files the freed block `[pos, pos + size)`. Returns `false` if it is
+    /// too small, too large, or misaligned to be recycled.
+    ///
+    /// # Safety
+    ///
+    /// The block must be owned by the allocator and writable.
+    pub unsafe fn push(&mut self, pos: usize, size: usize) -> bool {
+        if size < (1 << MIN_SHIFT) || pos % align_of::<usize>() != 0 {
+            return false;
+        }
+        let shift = usize::BITS - 1 - size.leading_zeros();
+        if shift > MAX_SHIFT {
+            return false;
+        }
+        let class = (shift - MIN_SHIFT) as usize;
+        (pos as *mut usize).write(self.heads[class]);
+        self.heads[class] = pos;
+        true
+    }
+
+    /// Returns the size class of `layout` and the block [`pop`](Self::pop)
+    /// would hand out for it, without taking it.
+    pub fn peek(&self, layout: Layout) -> Option<(usize, usize)> {
+        let size = layout
+            .size()
+            .max(1 << MIN_SHIFT)
+            .checked_next_power_of_two()?;
+        let shift = size.trailing_zeros();
+        if shift > MAX_SHIFT {
+            return None;
+        }
+        let class = (shift - MIN_SHIFT) as usize;
+        let head = self.heads[class];
+        if head == 0 || head % layout.align() != 0 {
+            return None;
+        }
+        Some((class, head))
+    }
+
+    /// Takes a recycled block that can hold `layout`, if the head of its
+    /// size class happens to be suitably aligned. Returns the block address
+    /// and the class size, which the block is guaranteed to hold.
+    pub fn pop(&mut self, layout: Layout) -> Option<(usize, usize)> {
+        let (class, head) = self.peek(layout)?;
+        let size = 1 << (class as u32 + MIN_SHIFT);
+        // SAFETY: `head` was filed by `push`, which stored the next link there.
+        self.heads[class] = unsafe { (head as *const usize).read() };
+        Some((head, size))
+    }
+
+    /// Iterates over every recycled block as `(pos, class_size)`.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.heads.iter().enumerate().flat_map(|(class, &head)| {
+            let size = 1 << (class as u32 + MIN_SHIFT);
+            core::iter::successors(Some(head).filter(|&p| p != 0), |&p| {
+                // SAFETY: every node was filed by `push` and is still owned by us.
+                Some(unsafe { (p as *const usize).read() }).filter(|&p| p != 0)
+            })
+            .map(move |p| (p, size))
+        })
+    }
+
+    /// Drops every block lying in `range`, e.g. when that bytes area is reset.
+    pub fn purge(&mut self, range: Range<usize>) {
+        for head in self.heads.iter_mut() {
+            let mut link: *mut usize = head;
+            // SAFETY: every node was filed by `push` and is still owned by us.
+            unsafe {
+                while *link != 0 {
+                    let node = *link as *mut usize;
+                    if range.contains(&(node as usize)) {
+                        *link = *node;
+                    } else {
+                        link = node;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+/// Maximum number of pages whose reference count can be tracked at once.
+pub const PAGE_REF_CAPACITY: usize = 64;
+
+/// Reference counts of shared pages, as `(page, count)` entries.
+///
+/// An allocated page without an entry implicitly has a count of one. The
+/// entry of a page is removed when its count drops to zero and the page is
+/// freed.
+pub(crate) struct PageRefs {
+    slots: [(usize, usize); PAGE_REF_CAPACITY],
+    len: usize,
+}
+
+impl PageRefs {
+    pub const fn new() -> Self {
+        Self {
+            slots: [(0, 0); PAGE_REF_CAPACITY],
+            len: 0,
+        }
+    }
+
+    pub fn count(&self, page: usize) -> Option<usize> {
+        self.iter()
+            .find(|&(p, _)| p == page)
+            .map(|(_, count)| count)
+    }
+
+    /// Returns the count of `page`, inserting an entry with a count of one
+    /// if it has none. Returns `None` if the table is full.
+    pub fn entry(&mut self, page: usize) -> Option<&mut usize> {
+        let found = self.iter().position(|(p, _)| p == page);
+        let idx = match found {
+            Some(idx) => idx,
+            None if self.len < PAGE_REF_CAPACITY => {
+                self.slots[self.len] = (page, 1);
+                self.len += 1;
+                self.len - 1
+            }
+            None => return None,
+        };
+        Some(&mut self.slots[idx].1)
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(usize) -> bool) {
+        let mut idx = 0;
+        while idx < self.len {
+            if f(self.slots[idx].0) {
+                idx += 1;
+            } else {
+                self.len -= 1;
+                self.slots[idx] = self.slots[self.len];
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.slots[..self.len].iter().copied()
+    }
+}
@@ -0,0 +1,219 @@
+use allocator::{AllocError, AllocResult};
+use core::alloc::Layout;
+
+#[inline]
+pub(crate) const fn align_down(pos: usize, align: usize) -> usize {
+    pos & !(align - 1)
+}
+
+#[inline]
+pub(crate) const fn align_up(pos: usize, align: usize) -> Option<usize> {
+    match pos.checked_add(align - 1) {
+        Some(pos) => Some(align_down(pos, align)),
+        None => None,
+    }
+}
+
+/// Number of whole pages of `page_size` bytes in `[start, end)`.
+const fn pages_between(start: usize, end: usize, page_size: usize) -> usize {
+    let top = align_down(end, page_size);
+    match align_up(start, page_size) {
+        Some(bottom) if bottom < top => (top - bottom) / page_size,
+        _ => 0,
+    }
+}
+
+/// One of the two areas growing towards each other inside a region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Area {
+    /// The bytes area, growing upward from the region start.
+    Bytes,
+    /// The pages area, growing downward from the region end.
+    Pages,
+}
+
+/// What to do when an area reaches the logical boundary of its region while
+/// the other area still has slack.
+///
+/// See [`EarlyAllocator::set_boundary`](crate::EarlyAllocator::set_boundary).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rebalance {
+    /// The boundary never moves: the growing area is out of memory.
+    Fixed,
+    /// The boundary moves just enough to satisfy the request.
+    Auto,
+    /// Asks the callback, which receives the growing area, the number of
+    /// bytes it needs beyond the boundary, and the slack left on the other
+    /// side of it. The boundary moves if it returns `true`.
+    Custom(fn(Area, usize, usize) -> bool),
+}
+
+impl Rebalance {
+    fn allows(self, area: Area, needed: usize, slack: usize) -> bool {
+        match self {
+            Self::Fixed => false,
+            Self::Auto => true,
+            Self::Custom(f) => f(area, needed, slack),
+        }
+    }
+
+    /// The policy to try an allocation with before making it: a
+    /// [`Custom`](Self::Custom) callback is left to the real allocation,
+    /// so that it is asked once.
+    pub(crate) fn dry_run(self) -> Self {
+        match self {
+            Self::Custom(_) => Self::Auto,
+            policy => policy,
+        }
+    }
+}
+
+/// A double-end memory range managed by [`EarlyAllocator`](crate::EarlyAllocator).
+///
+/// If `boundary` is set, the bytes area stays below it and the pages area
+/// above it, unless the [`Rebalance`] policy agrees to move it.
+#[derive(Clone, Copy)]
+pub(crate) struct Region {
+    pub start: usize,
+    pub end: usize,
+    pub b_pos: usize,
+    pub p_pos: usize,
+    pub count: usize,
+    pub boundary: Option<usize>,
+    /// Bitmap of the freed pages, see `frames.rs`.
+    pub frames: Option<usize>,
+    /// Number of bytes in freed pages.
+    pub freed: usize,
+    pub priority: u8,
+    pub flags: u32,
+}
+
+impl Region {
+    pub const EMPTY: Self = Self::new(0, 0);
+
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end,
+            b_pos: start,
+            p_pos: end,
+            count: 0,
+            boundary: None,
+            frames: None,
+            freed: 0,
+            priority: 0,
+            flags: 0,
+        }
+    }
+
+    pub fn contains(&self, addr: usize) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.start < end && start < self.end
+    }
+
+    pub fn size(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        (self.b_pos - self.start) + (self.end - self.p_pos) - self.freed
+    }
+
+    pub fn available_bytes(&self) -> usize {
+        self.p_pos - self.b_pos
+    }
+
+    /// Number of whole pages in the region.
+    pub const fn total_pages(&self, page_size: usize) -> usize {
+        pages_between(self.start, self.end, page_size)
+    }
+
+    /// Number of whole pages that fit in the free gap.
+    pub fn available_pages(&self, page_size: usize) -> usize {
+        pages_between(self.b_pos, self.p_pos, page_size)
+    }
+
+    /// Bumps the bytes area, skipping `skip` bytes before aligning.
+    ///
+    /// Fails with [`AllocError::MemoryOverlap`] if the block would fit in the
+    /// region but runs into the pages area.
+    pub fn alloc_bytes(
+        &mut self,
+        layout: Layout,
+        skip: usize,
+        rebalance: Rebalance,
+    ) -> AllocResult<usize> {
+        let pos = self
+            .b_pos
+            .checked_add(skip)
+            .and_then(|pos| align_up(pos, layout.align()))
+            .ok_or(AllocError::NoMemory)?;
+        let new_pos = pos.checked_add(layout.size()).ok_or(AllocError::NoMemory)?;
+        if new_pos > self.p_pos {
+            return Err(if new_pos <= self.end && self.p_pos < self.end {
+                AllocError::MemoryOverlap
+            } else {
+                AllocError::NoMemory
+            });
+        }
+        if let Some(boundary) = self.boundary {
+            if new_pos > boundary {
+                if !rebalance.allows(Area::Bytes, new_pos - boundary, self.p_pos - boundary) {
+                    return Err(AllocError::NoMemory);
+                }
+                self.boundary = Some(new_pos);
+            }
+        }
+        self.b_pos = new_pos;
+        self.count += 1;
+        Ok(pos)
+    }
+
+    pub fn dealloc_bytes(&mut self) {
+        self.count -= 1;
+        if self.count == 0 {
+            self.b_pos = self.start;
+        }
+    }
+
+    pub fn alloc_top(&mut self, layout: Layout, rebalance: Rebalance) -> AllocResult<usize> {
+        self.alloc_pages(layout.size(), layout.align(), rebalance)
+    }
+
+    /// Bumps the pages area downward.
+    ///
+    /// Fails with [`AllocError::MemoryOverlap`] if the run would fit in the
+    /// region but runs into the bytes area.
+    pub fn alloc_pages(
+        &mut self,
+        size: usize,
+        align: usize,
+        rebalance: Rebalance,
+    ) -> AllocResult<usize> {
+        let pos = self
+            .p_pos
+            .checked_sub(size)
+            .map(|pos| align_down(pos, align))
+            .ok_or(AllocError::NoMemory)?;
+        if pos < self.b_pos {
+            return Err(if pos >= self.start && self.b_pos > self.start {
+                AllocError::MemoryOverlap
+            } else {
+                AllocError::NoMemory
+            });
+        }
+        if let Some(boundary) = self.boundary {
+            if pos < boundary {
+                if !rebalance.allows(Area::Pages, boundary - pos, boundary - self.b_pos) {
+                    return Err(AllocError::NoMemory);
+                }
+                self.boundary = Some(pos);
+            }
+        }
+        self.p_pos = pos;
+        Ok(pos)
+    }
+}
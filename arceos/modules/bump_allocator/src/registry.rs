@@ -0,0 +1,124 @@
+//! A global list of named allocators for debug shells and panic handlers,
+//! enabled by the `registry` feature.
+//!
+//! Systems with one allocator per memory controller register each of them
+//! once, and [`dump_allocators`] then shows them all. Entries are never
+//! removed: the allocators are `'static` and live as long as the list.
+
+use allocator::{AllocError, AllocResult};
+use core::cell::UnsafeCell;
+use core::fmt;
+
+use crate::sync::{AtomicBool, AtomicUsize, Ordering};
+use crate::{LockedEarlyAllocator, RawState};
+
+/// Maximum number of allocators registered at once.
+pub const REGISTRY_CAPACITY: usize = 8;
+
+/// An allocator of any page size, as seen by the registry.
+pub trait RegisteredAllocator: Sync {
+    /// The page size of the allocator.
+    fn page_size(&self) -> usize;
+
+    /// Returns the state of the allocator, or `None` if it is locked: a
+    /// panic handler may have interrupted the holder, so it never waits.
+    fn raw_state(&self) -> Option<RawState>;
+}
+
+impl<const PAGE_SIZE: usize> RegisteredAllocator for LockedEarlyAllocator<PAGE_SIZE> {
+    fn page_size(&self) -> usize {
+        PAGE_SIZE
+    }
+
+    fn raw_state(&self) -> Option<RawState> {
+        self.try_lock().map(|guard| guard.as_raw_state())
+    }
+}
+
+type Entry = (&'static str, &'static dyn RegisteredAllocator);
+
+struct Slot {
+    ready: AtomicBool,
+    entry: UnsafeCell<Option<Entry>>,
+}
+
+struct Registry {
+    claimed: AtomicUsize,
+    slots: [Slot; REGISTRY_CAPACITY],
+}
+
+// SAFETY: a slot is written once, by the thread that claimed it, before
+// `ready` is set, and only read after `ready` is seen set.
+unsafe impl Sync for Registry {}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: Slot = Slot {
+    ready: AtomicBool::new(false),
+    entry: UnsafeCell::new(None),
+};
+
+static REGISTRY: Registry = Registry {
+    claimed: AtomicUsize::new(0),
+    slots: [EMPTY_SLOT; REGISTRY_CAPACITY],
+};
+
+/// Adds `alloc` to the registry under `name`. Fails with
+/// [`AllocError::NoMemory`] once [`REGISTRY_CAPACITY`] allocators are
+/// registered.
+pub fn register_allocator(
+    name: &'static str,
+    alloc: &'static dyn RegisteredAllocator,
+) -> AllocResult {
+    let idx = REGISTRY.claimed.fetch_add(1, Ordering::Relaxed);
+    let Some(slot) = REGISTRY.slots.get(idx) else {
+        REGISTRY.claimed.fetch_sub(1, Ordering::Relaxed);
+        return Err(AllocError::NoMemory);
+    };
+    // SAFETY: `idx` was claimed by this call only, and `ready` is not set.
+    unsafe { *slot.entry.get() = Some((name, alloc)) };
+    slot.ready.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Returns the registered allocators with their names, in registration
+/// order. One being registered concurrently may be missed.
+pub fn registered_allocators() -> impl Iterator<Item = Entry> {
+    REGISTRY.slots.iter().map_while(|slot| {
+        // SAFETY: the slot is no longer written once `ready` is set.
+        slot.ready
+            .load(Ordering::Acquire)
+            .then(|| unsafe { *slot.entry.get() })
+            .flatten()
+    })
+}
+
+/// Writes one line per registered allocator to `out`, with its usage, or
+/// a note that it is locked.
+pub fn dump_allocators(out: &mut dyn fmt::Write) -> fmt::Result {
+    for (name, alloc) in registered_allocators() {
+        let Some(state) = alloc.raw_state() else {
+            writeln!(out, "{name}: locked")?;
+            continue;
+        };
+        let regions = state.regions.iter().filter(|r| r.end != 0);
+        let total: usize = regions.clone().map(|r| r.end - r.start).sum();
+        writeln!(
+            out,
+            "{name}: {} regions, {}/{} bytes, {} pages of {:#x} used",
+            regions.count(),
+            state.used_bytes,
+            total,
+            state.used_pages,
+            alloc.page_size()
+        )?;
+    }
+    Ok(())
+}
+
+impl<const PAGE_SIZE: usize> LockedEarlyAllocator<PAGE_SIZE> {
+    /// Adds the allocator to the registry under `name`, see
+    /// [`register_allocator`].
+    pub fn register(&'static self, name: &'static str) -> AllocResult {
+        register_allocator(name, self)
+    }
+}
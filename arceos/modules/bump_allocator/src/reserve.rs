@@ -0,0 +1,96 @@
+//! Room checked up front for a burst of allocations from one region.
+
+use allocator::{AllocError, AllocResult};
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use crate::region::Region;
+use crate::EarlyAllocator;
+
+/// Room known to be free in one region for a burst of allocations.
+///
+/// Returned by [`EarlyAllocator::try_reserve`] and
+/// [`EarlyAllocator::try_reserve_pages`]. The room is only kept free by
+/// the exclusive borrow of the allocator: nothing is set aside in the
+/// region, so there is nothing to release on drop.
+pub struct Reservation<'a, const PAGE_SIZE: usize> {
+    alloc: &'a mut EarlyAllocator<PAGE_SIZE>,
+    region: usize,
+    remaining: usize,
+}
+
+impl<'a, const PAGE_SIZE: usize> Reservation<'a, PAGE_SIZE> {
+    pub(crate) fn new(
+        alloc: &'a mut EarlyAllocator<PAGE_SIZE>,
+        region: usize,
+        bytes: usize,
+    ) -> Self {
+        Self {
+            alloc,
+            region,
+            remaining: bytes,
+        }
+    }
+
+    /// Index of the region the room is fenced off in.
+    pub fn region(&self) -> usize {
+        self.region
+    }
+
+    /// Bytes of room left, alignment padding counting as used.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Allocates a byte block from the reserved room. Fails with
+    /// [`AllocError::NoMemory`] if the block, padding included, does not fit
+    /// in what [`remaining`](Self::remaining) says is left.
+    pub fn alloc(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        let mut region = self.alloc.regions[self.region];
+        let tagged = self.alloc.tagged_layout(layout)?;
+        region.alloc_bytes(tagged, 0, self.alloc.rebalance.dry_run())?;
+        self.check_room(&region)?;
+        let before = self.gap();
+        let ptr = self.alloc.alloc_bytes_in(self.region, layout)?;
+        self.consume(before);
+        Ok(ptr)
+    }
+
+    /// Allocates a page run from the reserved room. Fails with
+    /// [`AllocError::NoMemory`] if the run, padding and guard pages
+    /// included, does not fit in what [`remaining`](Self::remaining) says is
+    /// left.
+    pub fn alloc_pages(&mut self, num_pages: usize, align_pow2: usize) -> AllocResult<usize> {
+        EarlyAllocator::<PAGE_SIZE>::check_page_align(align_pow2)?;
+        let size = num_pages
+            .checked_mul(PAGE_SIZE)
+            .ok_or(AllocError::InvalidParam)?;
+        let mut region = self.alloc.regions[self.region];
+        let rebalance = self.alloc.rebalance.dry_run();
+        self.alloc.page_run_taker(size, align_pow2, rebalance)?(&mut region)?;
+        self.check_room(&region)?;
+        let before = self.gap();
+        let pos = self
+            .alloc
+            .alloc_page_run(num_pages, align_pow2, false, Some(self.region))?;
+        self.consume(before);
+        Ok(pos)
+    }
+
+    /// Fails if getting the region to `after` would take more than the
+    /// room left.
+    fn check_room(&self, after: &Region) -> AllocResult {
+        if self.gap() - after.available_bytes() > self.remaining {
+            return Err(AllocError::NoMemory);
+        }
+        Ok(())
+    }
+
+    fn gap(&self) -> usize {
+        self.alloc.regions[self.region].available_bytes()
+    }
+
+    fn consume(&mut self, before: usize) {
+        self.remaining = self.remaining.saturating_sub(before - self.gap());
+    }
+}
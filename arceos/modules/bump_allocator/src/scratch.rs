@@ -0,0 +1,143 @@
+//! Short-lived byte allocations, bumped downward and freed all at once.
+//!
+//! Scratch blocks are carved from chunks of whole pages taken from the pages
+//! area, top down within each chunk. The chunks are recorded in a table kept
+//! by the allocator rather than in the chunks themselves, and a chunk freed
+//! by other means is dropped from it, so releasing never frees pages that
+//! have been handed out to someone else since.
+
+use allocator::{AllocError, AllocResult};
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use crate::region::align_down;
+use crate::EarlyAllocator;
+
+/// Maximum number of scratch chunks held at once.
+pub const SCRATCH_CAPACITY: usize = 16;
+
+/// The scratch chunks, as `(start, end)` spans, oldest first.
+pub(crate) struct Scratch {
+    chunks: [(usize, usize); SCRATCH_CAPACITY],
+    len: usize,
+    /// Where the next block is bumped down from in the newest chunk, or
+    /// `None` once nothing more may be bumped from it.
+    pos: Option<usize>,
+}
+
+impl Scratch {
+    pub const fn new() -> Self {
+        Self {
+            chunks: [(0, 0); SCRATCH_CAPACITY],
+            len: 0,
+            pos: None,
+        }
+    }
+
+    /// Bumps the newest chunk down for `layout`.
+    fn bump(&mut self, layout: Layout) -> Option<usize> {
+        let (start, _) = self.chunks[..self.len].last()?;
+        let pos = align_down(self.pos?.checked_sub(layout.size())?, layout.align());
+        if pos < *start {
+            return None;
+        }
+        self.pos = Some(pos);
+        Some(pos)
+    }
+
+    /// Forgets the chunks for which `stale` returns `true`. Nothing more is
+    /// bumped from an older chunk that becomes the newest.
+    fn forget(&mut self, mut stale: impl FnMut(usize, usize) -> bool) {
+        let newest = self.len.checked_sub(1).map(|idx| self.chunks[idx]);
+        let mut kept = 0;
+        for idx in 0..self.len {
+            let (start, end) = self.chunks[idx];
+            if !stale(start, end) {
+                self.chunks[kept] = (start, end);
+                kept += 1;
+            }
+        }
+        self.len = kept;
+        if self.chunks[..kept].last().copied() != newest {
+            self.pos = None;
+        }
+    }
+
+    /// Forgets the chunks overlapping `[start, end)`, which was just freed.
+    pub fn forget_freed(&mut self, start: usize, end: usize) {
+        self.forget(|s, e| s < end && start < e);
+    }
+}
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Allocates a short-lived block, e.g. a buffer to parse firmware tables
+    /// into, that is only freed by
+    /// [`release_scratch_all`](Self::release_scratch_all).
+    ///
+    /// Scratch memory comes in whole pages from the pages area and counts in
+    /// [`used_pages`](allocator::PageAllocator::used_pages), so it never
+    /// pins the bytes area the long-lived structures live in. Up to
+    /// [`SCRATCH_CAPACITY`] chunks of pages are taken before a release.
+    pub fn alloc_scratch(&mut self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        self.check_sealed()?;
+        let pos = match self.scratch.bump(layout) {
+            Some(pos) => pos,
+            None => {
+                if self.scratch.len == SCRATCH_CAPACITY {
+                    return Err(AllocError::NoMemory);
+                }
+                let num_pages = layout
+                    .size()
+                    .checked_add(layout.align())
+                    .ok_or(AllocError::InvalidParam)?
+                    .div_ceil(PAGE_SIZE);
+                let chunk = self.alloc_page_run(num_pages, PAGE_SIZE, false, None)?;
+                let end = chunk + num_pages * PAGE_SIZE;
+                let scratch = &mut self.scratch;
+                scratch.chunks[scratch.len] = (chunk, end);
+                scratch.len += 1;
+                scratch.pos = Some(end);
+                scratch.bump(layout).ok_or(AllocError::NoMemory)?
+            }
+        };
+        NonNull::new(pos as *mut u8).ok_or(AllocError::NoMemory)
+    }
+
+    /// Forgets the scratch chunks that are no longer allocated in full,
+    /// after [`reset_to_epoch`](Self::reset_to_epoch) rolled them back. An
+    /// older chunk still allocated is kept for `release_scratch_all`, but
+    /// nothing more is bumped from it.
+    pub(crate) fn drop_stale_scratch(&mut self) {
+        let mut scratch = core::mem::replace(&mut self.scratch, Scratch::new());
+        scratch.forget(|start, end| {
+            (start..end)
+                .step_by(PAGE_SIZE)
+                .any(|page| self.check_page(page).is_err())
+        });
+        self.scratch = scratch;
+    }
+
+    /// Frees every block from [`alloc_scratch`](Self::alloc_scratch) at
+    /// once, and returns the number of pages given back.
+    ///
+    /// A chunk freed otherwise, e.g. by a stray `dealloc_pages`, was
+    /// forgotten then and is not freed again.
+    pub fn release_scratch_all(&mut self) -> usize {
+        let scratch = core::mem::replace(&mut self.scratch, Scratch::new());
+        let mut released = 0;
+        // Newest first, so that each chunk is at the bottom of the pages
+        // area when it is freed.
+        for &(start, end) in scratch.chunks[..scratch.len].iter().rev() {
+            let num_pages = (end - start) / PAGE_SIZE;
+            if self.free_page_run(start, num_pages, false) {
+                released += num_pages;
+            } else {
+                early_warn!(
+                    "early allocator: scratch chunk at {:#x} was not allocated",
+                    start
+                );
+            }
+        }
+        released
+    }
+}
@@ -0,0 +1,107 @@
+//! Sealing the allocator when the real heap takes over.
+
+use allocator::{ByteAllocator, PageAllocator};
+use core::fmt;
+#[cfg(feature = "track")]
+use core::panic::Location;
+
+use crate::EarlyAllocator;
+
+/// What was still allocated when the allocator was sealed, returned by
+/// [`EarlyAllocator::seal`].
+///
+/// Memory left allocated here is memory the real heap never gets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SealSummary {
+    /// Bytes still allocated, pages included.
+    pub used_bytes: usize,
+    /// Pages still allocated.
+    pub used_pages: usize,
+    /// Live byte allocations known to the tracker; `0` without the `track`
+    /// feature.
+    pub tracked: usize,
+    /// Live byte allocations that did not fit in the tracker.
+    pub untracked: usize,
+}
+
+impl fmt::Display for SealSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "early allocator sealed with {} bytes and {} pages still allocated",
+            self.used_bytes, self.used_pages
+        )?;
+        if self.tracked > 0 || self.untracked > 0 {
+            write!(
+                f,
+                ", {} tracked byte allocations ({} untracked)",
+                self.tracked, self.untracked
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Seals the allocator at handoff to the real heap. Later allocations
+    /// fail with [`AllocError::NoMemory`](allocator::AllocError::NoMemory)
+    /// and a warning, while frees keep working, until
+    /// [`init`](allocator::BaseAllocator::init) is called again.
+    ///
+    /// With `report`, the summary is logged through `axlog`, followed with
+    /// the `track` feature by one line per
+    /// [outstanding allocation](Self::outstanding_allocations).
+    pub fn seal(&mut self, report: bool) -> SealSummary {
+        self.sealed = true;
+        let summary = SealSummary {
+            used_bytes: self.used_bytes(),
+            used_pages: self.used_pages(),
+            #[cfg(feature = "track")]
+            tracked: self.tracker.iter().count(),
+            #[cfg(not(feature = "track"))]
+            tracked: 0,
+            #[cfg(feature = "track")]
+            untracked: self.tracker.untracked(),
+            #[cfg(not(feature = "track"))]
+            untracked: 0,
+        };
+        if report {
+            early_warn!("{}", summary);
+            #[cfg(feature = "track")]
+            for (_addr, _size, _tag, _caller) in self.outstanding_allocations() {
+                early_warn!(
+                    "  {} bytes at {:#x} [{}] from {}",
+                    _size,
+                    _addr,
+                    _tag.unwrap_or("-"),
+                    _caller
+                );
+            }
+        }
+        summary
+    }
+
+    /// Returns `true` once [`seal`](Self::seal) has been called.
+    pub fn is_sealed(&self) -> bool {
+        self.sealed
+    }
+
+    /// Returns `(addr, size, tag, caller)` for every tracked byte allocation
+    /// still live, in no particular order, for leak reports at handoff.
+    /// [`live_allocations`](Self::live_allocations) has the full records.
+    #[cfg(feature = "track")]
+    pub fn outstanding_allocations(
+        &self,
+    ) -> impl Iterator<
+        Item = (
+            usize,
+            usize,
+            Option<&'static str>,
+            &'static Location<'static>,
+        ),
+    > + '_ {
+        self.tracker
+            .iter()
+            .map(|r| (r.addr, r.size, r.tag, r.caller))
+    }
+}
@@ -0,0 +1,60 @@
+//! End-of-region sentinels, enabled by the `sentinel` feature.
+//!
+//! When a region is added, a sentinel word is written at its last word. It
+//! is checked by [`verify`](EarlyAllocator::verify) and when an allocation
+//! runs out of memory, which catches firmware or DMA writing past the
+//! advertised end of the region. Once the top of a region is handed out the
+//! word belongs to its owner, and the sentinel is retired.
+//!
+//! The sentinel is written as soon as the region is added, so the memory
+//! of a region must be writable by then, up to the top of the address
+//! space for a region that reaches it.
+
+use core::mem::size_of;
+
+use crate::{EarlyAllocError, EarlyAllocator};
+
+// The pattern of the gap canaries, so a canary placed on the last word of a
+// region leaves its sentinel intact.
+const SENTINEL: usize = 0x5afe_c0de_5afe_c0de_u64 as usize;
+const WORD: usize = size_of::<usize>();
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Writes the sentinel at the last word of region `idx`, which must
+    /// still be free.
+    pub(crate) fn place_sentinel(&mut self, idx: usize) {
+        let region = &self.regions[idx];
+        self.sentinels &= !(1 << idx);
+        if region.p_pos != region.end || region.available_bytes() < WORD {
+            return;
+        }
+        // SAFETY: the last word lies in the free gap, which nothing but the
+        // allocator may touch.
+        unsafe { ((region.end - WORD) as *mut usize).write_unaligned(SENTINEL) };
+        self.sentinels |= 1 << idx;
+    }
+
+    /// Retires the sentinel of region `idx` if `[start, start + len)` covers
+    /// it.
+    pub(crate) fn retire_sentinel(&mut self, idx: usize, start: usize, len: usize) {
+        if start + len > self.regions[idx].end - WORD {
+            self.sentinels &= !(1 << idx);
+        }
+    }
+
+    /// Checks the sentinel of every region that still has one.
+    pub(crate) fn check_sentinels(&self) -> Result<(), EarlyAllocError> {
+        for idx in self.region_indices() {
+            if self.sentinels & (1 << idx) == 0 {
+                continue;
+            }
+            let addr = self.regions[idx].end - WORD;
+            // SAFETY: see `place_sentinel`; the word has not been handed out.
+            let value = unsafe { (addr as *const usize).read_unaligned() };
+            if value != SENTINEL {
+                return Err(EarlyAllocError::Corrupted { region: idx, addr });
+            }
+        }
+        Ok(())
+    }
+}
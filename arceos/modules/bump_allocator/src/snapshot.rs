@@ -0,0 +1,88 @@
+//! Copies of the allocator bookkeeping for differential testing.
+
+use crate::region::Region;
+use crate::MAX_REGIONS;
+
+/// The cursors and counters of one region at the time of a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionSnapshot {
+    /// Start address of the region.
+    pub start: usize,
+    /// End address (exclusive) of the region.
+    pub end: usize,
+    /// Bytes cursor.
+    pub b_pos: usize,
+    /// Pages cursor.
+    pub p_pos: usize,
+    /// Number of live allocations in the bytes area.
+    pub count: usize,
+    /// Logical boundary between the bytes and pages areas, if any.
+    pub boundary: Option<usize>,
+}
+
+impl From<&Region> for RegionSnapshot {
+    fn from(r: &Region) -> Self {
+        Self {
+            start: r.start,
+            end: r.end,
+            b_pos: r.b_pos,
+            p_pos: r.p_pos,
+            count: r.count,
+            boundary: r.boundary,
+        }
+    }
+}
+
+/// A region that differs between two snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionDiff {
+    /// Region index.
+    pub idx: usize,
+    /// State in the older snapshot, `None` if the region did not exist.
+    pub before: Option<RegionSnapshot>,
+    /// State in the newer snapshot, `None` if the region does not exist.
+    pub after: Option<RegionSnapshot>,
+}
+
+/// A cheap copy of the [`EarlyAllocator`](crate::EarlyAllocator)
+/// bookkeeping, not of the managed memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EarlyAllocatorSnapshot {
+    pub(crate) regions: [Option<RegionSnapshot>; MAX_REGIONS],
+    pub(crate) current_region: usize,
+}
+
+impl EarlyAllocatorSnapshot {
+    /// Returns the state of region `idx`, if it exists.
+    pub fn region(&self, idx: usize) -> Option<&RegionSnapshot> {
+        self.regions.get(idx)?.as_ref()
+    }
+
+    /// Returns the region the next allocation starts from.
+    pub fn current_region(&self) -> usize {
+        self.current_region
+    }
+
+    /// Lists the regions that changed from `self` to `newer`.
+    pub fn diff<'a>(&'a self, newer: &'a Self) -> impl Iterator<Item = RegionDiff> + 'a {
+        (0..MAX_REGIONS)
+            .filter(|&idx| self.regions[idx] != newer.regions[idx])
+            .map(|idx| RegionDiff {
+                idx,
+                before: self.regions[idx],
+                after: newer.regions[idx],
+            })
+    }
+}
+
+/// A marker returned by [`EarlyAllocator::mark_epoch`](crate::EarlyAllocator::mark_epoch).
+///
+/// [`EarlyAllocator::reset_to_epoch`](crate::EarlyAllocator::reset_to_epoch)
+/// frees every allocation made after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Epoch {
+    pub(crate) snapshot: EarlyAllocatorSnapshot,
+    /// Regions that had a freed-pages bitmap.
+    pub(crate) frames: usize,
+    pub(crate) used_pages: usize,
+}
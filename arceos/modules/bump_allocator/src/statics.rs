@@ -0,0 +1,129 @@
+//! Global allocators over memory delimited by linker symbols, declared with
+//! [`early_allocator!`](crate::early_allocator).
+
+use allocator::{AllocError, AllocResult, BaseAllocator};
+use core::mem::align_of;
+use core::ops::{Deref, Range};
+
+use crate::{EarlyAllocator, LockedEarlyAllocator};
+
+/// Checks the addresses of two linker symbols delimiting memory: `start`
+/// must be non-null and below `end`, and both word-aligned, as a linker
+/// script aligns them. Anything else is most likely a wrong or swapped
+/// symbol.
+fn symbol_range(start: usize, end: usize) -> AllocResult<Range<usize>> {
+    let align = align_of::<usize>();
+    if start == 0 || start >= end || start % align != 0 || end % align != 0 {
+        return Err(AllocError::InvalidParam);
+    }
+    Ok(start..end)
+}
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Initializes the allocator, see [`init`](BaseAllocator::init), with
+    /// the memory between the linker symbols `start_sym` and `end_sym`,
+    /// typically `addr_of!(__heap_start)` and `addr_of!(__heap_end)`.
+    ///
+    /// Unlike `init`, fails with [`AllocError::InvalidParam`], leaving the
+    /// allocator untouched, if `start_sym` is null or not below `end_sym`,
+    /// or either is not aligned to a word.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be valid and unused by anything else.
+    pub unsafe fn init_from_linker_symbols(
+        &mut self,
+        start_sym: *const u8,
+        end_sym: *const u8,
+    ) -> AllocResult {
+        let range = symbol_range(start_sym as usize, end_sym as usize)?;
+        self.init(range.start, range.len());
+        if self.bitmap == 0 {
+            return Err(AllocError::InvalidParam);
+        }
+        Ok(())
+    }
+}
+
+/// A [`LockedEarlyAllocator`] in a `static` that knows where its memory is,
+/// usually from linker symbols, but is only handed it by [`init`](Self::init).
+///
+/// It dereferences to the locked allocator, so it is used like one once
+/// initialized.
+pub struct StaticEarlyAllocator<const PAGE_SIZE: usize> {
+    locked: LockedEarlyAllocator<PAGE_SIZE>,
+    bounds: fn() -> Range<usize>,
+}
+
+impl<const PAGE_SIZE: usize> StaticEarlyAllocator<PAGE_SIZE> {
+    /// Creates an empty allocator for the memory returned by `bounds`.
+    pub const fn new(bounds: fn() -> Range<usize>) -> Self {
+        Self {
+            locked: LockedEarlyAllocator::new(),
+            bounds,
+        }
+    }
+
+    /// Returns the memory the allocator is for.
+    pub fn bounds(&self) -> Range<usize> {
+        (self.bounds)()
+    }
+
+    /// Adds the memory returned by `bounds` to the allocator. Fails with
+    /// [`AllocError::InvalidParam`] if the bounds fail the checks of
+    /// [`EarlyAllocator::init_from_linker_symbols`], and with
+    /// [`AllocError::MemoryOverlap`] if the memory was already added.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be valid and unused by anything else.
+    pub unsafe fn init(&self) -> AllocResult {
+        let Range { start, end } = self.bounds();
+        let range = symbol_range(start, end)?;
+        self.locked.add_memory(range.start, range.len())
+    }
+}
+
+impl<const PAGE_SIZE: usize> Deref for StaticEarlyAllocator<PAGE_SIZE> {
+    type Target = LockedEarlyAllocator<PAGE_SIZE>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.locked
+    }
+}
+
+/// Declares [`StaticEarlyAllocator`] statics over the memory between two
+/// linker symbols, so a port only has to call `init` early in boot:
+///
+/// ```ignore
+/// bump_allocator::early_allocator! {
+///     /// The heap reserved by the linker script.
+///     pub static BOOT_HEAP: 4096 => __boot_heap_start..__boot_heap_end;
+/// }
+///
+/// unsafe { BOOT_HEAP.init() }.expect("boot heap");
+/// let page = BOOT_HEAP.lock().alloc_pages(1, 4096);
+/// ```
+///
+/// The number is the page size, and the symbols are resolved by the linker
+/// like any `extern "C"` static.
+#[macro_export]
+macro_rules! early_allocator {
+    ($(
+        $(#[$attr:meta])*
+        $vis:vis static $name:ident: $page_size:expr => $start:ident..$end:ident;
+    )*) => {$(
+        $(#[$attr])*
+        $vis static $name: $crate::StaticEarlyAllocator<{ $page_size }> =
+            $crate::StaticEarlyAllocator::new(|| {
+                extern "C" {
+                    static $start: u8;
+                    static $end: u8;
+                }
+                // SAFETY: only the addresses of the symbols are taken.
+                unsafe {
+                    ::core::ptr::addr_of!($start) as usize..::core::ptr::addr_of!($end) as usize
+                }
+            });
+    )*};
+}
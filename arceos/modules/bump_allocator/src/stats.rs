@@ -0,0 +1,286 @@
+/// Number of power-of-two size classes, from 1 byte up to `1 << usize::BITS`.
+pub const SIZE_CLASSES: usize = usize::BITS as usize + 1;
+
+/// Byte allocation counts grouped by power-of-two size class.
+///
+/// A block of `size` bytes falls in the smallest class holding it, so class
+/// `n` covers sizes in `(1 << (n - 1), 1 << n]`. Zero-sized blocks fall in
+/// class 0. The figures are cumulative: freeing a block does not undo it.
+#[derive(Debug, Clone)]
+pub struct SizeClassStats {
+    counts: [usize; SIZE_CLASSES],
+    bytes: [usize; SIZE_CLASSES],
+}
+
+impl SizeClassStats {
+    pub(crate) const fn new() -> Self {
+        Self {
+            counts: [0; SIZE_CLASSES],
+            bytes: [0; SIZE_CLASSES],
+        }
+    }
+
+    #[cfg(not(feature = "no-stats"))]
+    pub(crate) fn record(&mut self, size: usize) {
+        let class = Self::class_of(size);
+        self.counts[class] += 1;
+        self.bytes[class] = self.bytes[class].saturating_add(size);
+    }
+
+    /// Returns the class a block of `size` bytes falls in.
+    pub fn class_of(size: usize) -> usize {
+        match size {
+            0 | 1 => 0,
+            _ => (usize::BITS - (size - 1).leading_zeros()) as usize,
+        }
+    }
+
+    /// Number of blocks allocated in class `class`.
+    pub fn count(&self, class: usize) -> usize {
+        self.counts.get(class).copied().unwrap_or(0)
+    }
+
+    /// Total bytes requested by the blocks of class `class`.
+    pub fn bytes(&self, class: usize) -> usize {
+        self.bytes.get(class).copied().unwrap_or(0)
+    }
+
+    /// Iterates over the non-empty classes as `(class, count, bytes)`,
+    /// smallest first.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        (0..SIZE_CLASSES)
+            .filter(|&class| self.counts[class] > 0)
+            .map(|class| (class, self.counts[class], self.bytes[class]))
+    }
+}
+
+/// Number of CPUs whose allocations are counted separately.
+pub const MAX_CPUS: usize = 8;
+
+/// Allocations made by one CPU, see
+/// [`EarlyAllocator::cpu_stats`](crate::EarlyAllocator::cpu_stats). The
+/// figures are cumulative: frees do not undo them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuStats {
+    /// Number of byte blocks allocated.
+    pub allocs: usize,
+    /// Bytes requested by those blocks.
+    pub bytes: usize,
+    /// Number of page runs allocated.
+    pub page_allocs: usize,
+    /// Pages in those runs.
+    pub pages: usize,
+}
+
+impl CpuStats {
+    const ZERO: Self = Self {
+        allocs: 0,
+        bytes: 0,
+        page_allocs: 0,
+        pages: 0,
+    };
+
+    #[cfg(not(feature = "no-stats"))]
+    fn add(&mut self, other: &Self) {
+        self.allocs += other.allocs;
+        self.bytes = self.bytes.saturating_add(other.bytes);
+        self.page_allocs += other.page_allocs;
+        self.pages += other.pages;
+    }
+}
+
+/// The counters kept by [`EarlyAllocator`](crate::EarlyAllocator). With the
+/// `no-stats` feature they are compiled out and every update is dropped.
+#[derive(Clone)]
+pub(crate) struct Counters {
+    /// Number of pages handed out by `alloc_pages` and not freed yet.
+    #[cfg(not(feature = "no-stats"))]
+    used_pages: usize,
+    #[cfg(not(feature = "no-stats"))]
+    exec_pages: usize,
+    #[cfg(not(feature = "no-stats"))]
+    page_table_pages: usize,
+    #[cfg(not(feature = "no-stats"))]
+    saved_padding: usize,
+    #[cfg(not(feature = "no-stats"))]
+    size_classes: SizeClassStats,
+    #[cfg(not(feature = "no-stats"))]
+    cpu_id: Option<fn() -> usize>,
+    #[cfg(not(feature = "no-stats"))]
+    per_cpu: [CpuStats; MAX_CPUS],
+}
+
+#[cfg(not(feature = "no-stats"))]
+impl Counters {
+    pub const fn new() -> Self {
+        Self {
+            used_pages: 0,
+            exec_pages: 0,
+            page_table_pages: 0,
+            saved_padding: 0,
+            size_classes: SizeClassStats::new(),
+            cpu_id: None,
+            per_cpu: [CpuStats::ZERO; MAX_CPUS],
+        }
+    }
+
+    /// Clears the counters, keeping the CPU id source.
+    pub fn reset(&mut self) {
+        *self = Self {
+            cpu_id: self.cpu_id,
+            ..Self::new()
+        };
+    }
+
+    pub fn set_cpu_id(&mut self, cpu_id: Option<fn() -> usize>) {
+        self.cpu_id = cpu_id;
+    }
+
+    /// The counters of the calling CPU, if it is counted.
+    fn this_cpu(&mut self) -> Option<&mut CpuStats> {
+        let cpu = self.cpu_id.map_or(0, |cpu_id| cpu_id());
+        self.per_cpu.get_mut(cpu)
+    }
+
+    pub fn pages_allocated(&mut self, num_pages: usize, exec: bool) {
+        self.used_pages += num_pages;
+        if exec {
+            self.exec_pages += num_pages;
+        }
+        if let Some(stats) = self.this_cpu() {
+            stats.page_allocs += 1;
+            stats.pages += num_pages;
+        }
+    }
+
+    /// Counts a freed run. Pages the counters never saw, e.g. from
+    /// `alloc_from_top`, can be freed as pages too, so the counts saturate.
+    pub fn pages_freed(&mut self, num_pages: usize, exec: bool) {
+        self.used_pages = self.used_pages.saturating_sub(num_pages);
+        if exec {
+            self.exec_pages = self.exec_pages.saturating_sub(num_pages);
+        }
+    }
+
+    /// Returns the number of pages in use, if counted.
+    pub fn used_pages(&self) -> Option<usize> {
+        Some(self.used_pages)
+    }
+
+    pub fn set_used_pages(&mut self, num_pages: usize) {
+        self.used_pages = num_pages;
+    }
+
+    pub fn exec_pages(&self) -> usize {
+        self.exec_pages
+    }
+
+    /// Returns whether `num_pages` exec pages can be in use, or `None` if
+    /// exec pages are not counted.
+    pub fn covers_exec(&self, num_pages: usize) -> Option<bool> {
+        Some(num_pages <= self.exec_pages)
+    }
+
+    pub fn page_table_allocated(&mut self) {
+        self.page_table_pages += 1;
+    }
+
+    pub fn page_table_freed(&mut self) {
+        self.page_table_pages = self.page_table_pages.saturating_sub(1);
+    }
+
+    pub fn page_table_pages(&self) -> usize {
+        self.page_table_pages
+    }
+
+    pub fn padding_saved(&mut self, bytes: usize) {
+        self.saved_padding += bytes;
+    }
+
+    pub fn saved_padding(&self) -> usize {
+        self.saved_padding
+    }
+
+    pub fn byte_block(&mut self, size: usize) {
+        self.size_classes.record(size);
+        if let Some(stats) = self.this_cpu() {
+            stats.allocs += 1;
+            stats.bytes = stats.bytes.saturating_add(size);
+        }
+    }
+
+    pub fn size_classes(&self) -> &SizeClassStats {
+        &self.size_classes
+    }
+
+    pub fn cpu_stats(&self, cpu: usize) -> Option<CpuStats> {
+        self.per_cpu.get(cpu).copied()
+    }
+
+    pub fn cpu_stats_total(&self) -> CpuStats {
+        let mut total = CpuStats::ZERO;
+        for stats in &self.per_cpu {
+            total.add(stats);
+        }
+        total
+    }
+}
+
+#[cfg(feature = "no-stats")]
+impl Counters {
+    const EMPTY_CLASSES: SizeClassStats = SizeClassStats::new();
+
+    pub const fn new() -> Self {
+        Self {}
+    }
+
+    pub fn reset(&mut self) {}
+
+    pub fn set_cpu_id(&mut self, _cpu_id: Option<fn() -> usize>) {}
+
+    pub fn pages_allocated(&mut self, _num_pages: usize, _exec: bool) {}
+
+    pub fn pages_freed(&mut self, _num_pages: usize, _exec: bool) {}
+
+    pub fn used_pages(&self) -> Option<usize> {
+        None
+    }
+
+    pub fn set_used_pages(&mut self, _num_pages: usize) {}
+
+    pub fn exec_pages(&self) -> usize {
+        0
+    }
+
+    pub fn covers_exec(&self, _num_pages: usize) -> Option<bool> {
+        None
+    }
+
+    pub fn page_table_allocated(&mut self) {}
+
+    pub fn page_table_freed(&mut self) {}
+
+    pub fn page_table_pages(&self) -> usize {
+        0
+    }
+
+    pub fn padding_saved(&mut self, _bytes: usize) {}
+
+    pub fn saved_padding(&self) -> usize {
+        0
+    }
+
+    pub fn byte_block(&mut self, _size: usize) {}
+
+    pub fn size_classes(&self) -> &SizeClassStats {
+        &Self::EMPTY_CLASSES
+    }
+
+    pub fn cpu_stats(&self, _cpu: usize) -> Option<CpuStats> {
+        None
+    }
+
+    pub fn cpu_stats_total(&self) -> CpuStats {
+        CpuStats::ZERO
+    }
+}
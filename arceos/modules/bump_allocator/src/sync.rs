@@ -0,0 +1,17 @@
+//! Synchronization primitives used by the locked wrappers.
+//!
+//! Built with `RUSTFLAGS="--cfg loom"`, they are swapped for their `loom`
+//! counterparts so the wrappers can be model-checked.
+
+#[cfg(not(loom))]
+pub(crate) use core::{
+    cell::UnsafeCell,
+    hint::spin_loop,
+    sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering},
+};
+#[cfg(loom)]
+pub(crate) use loom::{
+    cell::{MutPtr, UnsafeCell},
+    hint::spin_loop,
+    sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering},
+};
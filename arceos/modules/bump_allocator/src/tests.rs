@@ -0,0 +1,2673 @@
+use core::alloc::Layout;
+
+use allocator::{AllocError, AllocResult, BaseAllocator, ByteAllocator, PageAllocator};
+
+use crate::EarlyAllocator;
+
+const PAGE_SIZE: usize = 0x1000;
+
+/// Leaks a page-aligned heap buffer of `size` bytes for the allocator to manage.
+fn arena(size: usize) -> usize {
+    let layout = Layout::from_size_align(size, PAGE_SIZE).unwrap();
+    let ptr = unsafe { std::alloc::alloc_zeroed(layout) };
+    assert!(!ptr.is_null());
+    ptr as usize
+}
+
+fn allocator(size: usize) -> (EarlyAllocator<PAGE_SIZE>, usize) {
+    let start = arena(size);
+    let mut a = EarlyAllocator::new();
+    a.init(start, size);
+    (a, start)
+}
+
+#[test]
+fn test_double_end() {
+    let (mut a, start) = allocator(16 * PAGE_SIZE);
+    let p = a.alloc(Layout::from_size_align(24, 8).unwrap()).unwrap();
+    assert_eq!(p.as_ptr() as usize, start);
+    let pages = a.alloc_pages(2, PAGE_SIZE).unwrap();
+    assert_eq!(pages, start + 14 * PAGE_SIZE);
+    assert_eq!(a.available_bytes(), 14 * PAGE_SIZE - 24);
+
+    a.dealloc(p, Layout::from_size_align(24, 8).unwrap());
+    assert_eq!(a.used_bytes(), 2 * PAGE_SIZE);
+    assert!(matches!(
+        a.alloc_pages(15, PAGE_SIZE),
+        Err(AllocError::NoMemory)
+    ));
+}
+
+#[test]
+fn test_multi_region() {
+    let (mut a, start) = allocator(PAGE_SIZE);
+    let second = arena(4 * PAGE_SIZE);
+    assert!(matches!(
+        a.add_memory(start + 8, 16),
+        Err(AllocError::MemoryOverlap)
+    ));
+    a.add_memory(second, 4 * PAGE_SIZE).unwrap();
+    assert_eq!(a.total_bytes(), 5 * PAGE_SIZE);
+
+    let p = a.alloc_pages(2, PAGE_SIZE).unwrap();
+    assert_eq!(p, second + 2 * PAGE_SIZE);
+}
+
+#[test]
+fn test_carve() {
+    let (mut a, start) = allocator(16 * PAGE_SIZE);
+    let mut child = a.carve(PAGE_SIZE + 1).unwrap();
+    assert_eq!(child.total_bytes(), 2 * PAGE_SIZE);
+    assert_eq!(a.available_bytes(), 14 * PAGE_SIZE);
+
+    let p = child.alloc(Layout::new::<u64>()).unwrap();
+    assert_eq!(p.as_ptr() as usize, start + 14 * PAGE_SIZE);
+    assert_eq!(a.used_bytes(), 2 * PAGE_SIZE);
+    assert!(a.carve(0).is_err());
+}
+
+#[test]
+fn test_split_region() {
+    let (mut a, start) = allocator(8 * PAGE_SIZE);
+    a.alloc(Layout::from_size_align(PAGE_SIZE + 8, 8).unwrap())
+        .unwrap();
+    assert!(matches!(
+        a.split_region(0, start + PAGE_SIZE),
+        Err(AllocError::MemoryOverlap)
+    ));
+    assert!(a.split_region(0, start + PAGE_SIZE + 8).is_err());
+
+    let high = a.split_region(0, start + 4 * PAGE_SIZE).unwrap();
+    assert_eq!(high, start + 4 * PAGE_SIZE..start + 8 * PAGE_SIZE);
+    assert_eq!(a.total_bytes(), 4 * PAGE_SIZE);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), start + 3 * PAGE_SIZE);
+}
+
+#[test]
+fn test_alloc_stack() {
+    let (mut a, start) = allocator(16 * PAGE_SIZE);
+    let stack = a.alloc_stack(3, 2).unwrap();
+    assert_eq!(stack.bottom % PAGE_SIZE, 0);
+    assert_eq!(stack.top % PAGE_SIZE, 0);
+    assert_eq!(stack.top - stack.bottom, 3 * PAGE_SIZE);
+    assert_eq!(stack.guard, stack.bottom - 2 * PAGE_SIZE..stack.bottom);
+    assert!(stack.guard.start >= start);
+
+    // Nothing else is ever handed out inside the guard hole.
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    while let Ok(page) = a.alloc_pages(1, PAGE_SIZE) {
+        assert!(page + PAGE_SIZE <= stack.guard.start || page >= stack.top);
+    }
+    while let Ok(ptr) = a.alloc(layout) {
+        let pos = ptr.as_ptr() as usize;
+        assert!(pos + 64 <= stack.guard.start || pos >= stack.top);
+    }
+
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let stack = a.alloc_stack(1, 0).unwrap();
+    assert!(stack.guard.is_empty());
+    assert_eq!(stack.guard.end, stack.bottom);
+
+    // A stack is freed as one run with its guard.
+    let guarded = a.alloc_stack(1, 1).unwrap();
+    for s in [guarded, stack] {
+        a.dealloc_pages(s.guard.start, (s.top - s.guard.start) / PAGE_SIZE);
+    }
+    assert_eq!(a.used_pages(), 0);
+    assert_eq!(a.available_pages(), 4);
+}
+
+#[test]
+fn test_alloc_from_top() {
+    use crate::TOP_SPAN_CAPACITY;
+
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(40, 16).unwrap();
+    let top = a.alloc_from_top(layout).unwrap();
+    assert_eq!(top.as_ptr() as usize, start + 4 * PAGE_SIZE - 48);
+
+    let low = a.alloc(layout).unwrap();
+    a.dealloc(top, layout);
+    a.dealloc(low, layout);
+    assert_eq!(a.used_bytes(), 48);
+
+    // The pages holding top blocks are not page runs.
+    let page = start + 3 * PAGE_SIZE;
+    let run = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(run, start + 2 * PAGE_SIZE);
+    let pages = a.used_pages();
+    assert!(matches!(a.get_page(page), Err(AllocError::NotAllocated)));
+    assert!(matches!(a.put_page(page), Err(AllocError::NotAllocated)));
+    a.dealloc_pages(page, 1);
+    a.dealloc_pages(run, 2);
+    assert_eq!(a.used_pages(), pages);
+    assert!(!a.free_ranges().any(|r| r.contains(&page)));
+    a.dealloc_pages(run, 1);
+    assert_eq!(a.used_pages(), pages - 1);
+    assert_eq!(a.available_pages(), 3);
+
+    // Stretches of top blocks between page runs are recorded up to the
+    // capacity.
+    let (mut a, _) = allocator((2 * TOP_SPAN_CAPACITY + 4) * PAGE_SIZE);
+    for _ in 0..TOP_SPAN_CAPACITY {
+        a.alloc_from_top(layout).unwrap();
+        a.alloc_pages(1, PAGE_SIZE).unwrap();
+    }
+    a.alloc_from_top(layout).unwrap_err();
+    let used = a.used_bytes();
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(a.used_bytes(), used + PAGE_SIZE);
+}
+
+#[cfg(feature = "randomize")]
+#[test]
+fn test_randomize() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    a.set_randomizer(Some(|| 100), 64);
+    let p = a.alloc(Layout::from_size_align(8, 8).unwrap()).unwrap();
+    assert_eq!(p.as_ptr() as usize, start + 40);
+}
+
+#[test]
+fn test_quarantine() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    a.set_quarantine_depth(2);
+    let p1 = a.alloc(layout).unwrap();
+    let p2 = a.alloc(layout).unwrap();
+    a.dealloc(p1, layout);
+    a.dealloc(p2, layout);
+    assert_eq!(a.used_bytes(), 32);
+
+    a.flush_quarantine();
+    assert_eq!(a.used_bytes(), 0);
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_hooks() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    a.set_hooks(crate::Hooks {
+        on_range_allocated: Some(|_, len| {
+            ALLOCATED.fetch_add(len, Ordering::Relaxed);
+        }),
+        on_range_freed: Some(|_, len| {
+            FREED.fetch_add(len, Ordering::Relaxed);
+        }),
+        ..crate::Hooks::NONE
+    });
+    let layout = Layout::from_size_align(24, 8).unwrap();
+    let p = a.alloc(layout).unwrap();
+    a.dealloc(p, layout);
+    a.alloc_stack(2, 1).unwrap();
+    assert_eq!(ALLOCATED.load(Ordering::Relaxed), 24 + 3 * PAGE_SIZE);
+    assert_eq!(FREED.load(Ordering::Relaxed), 24 + PAGE_SIZE);
+}
+
+#[cfg(feature = "mte")]
+#[test]
+fn test_mte_tagging() {
+    struct TopByte;
+    impl crate::MemoryTagger for TopByte {
+        fn tag_range(&self, addr: usize, _len: usize) -> usize {
+            addr | (0xa << 56)
+        }
+        fn untag(&self, ptr: usize) -> usize {
+            ptr & !(0xff << 56)
+        }
+    }
+
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    a.set_tagger(Some(&TopByte));
+    let layout = Layout::from_size_align(20, 4).unwrap();
+    let p = a.alloc(layout).unwrap();
+    assert_eq!(p.as_ptr() as usize, start | (0xa << 56));
+    assert_eq!(a.used_bytes(), 32);
+    a.dealloc(p, layout);
+    assert_eq!(a.used_bytes(), 0);
+}
+
+#[cfg(feature = "track")]
+#[test]
+fn test_track_caller() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(24, 8).unwrap();
+    let p1 = a.alloc(layout).unwrap();
+    let line = line!() - 1;
+    let p2 = a.alloc(layout).unwrap();
+    a.dealloc(p2, layout);
+
+    let live: Vec<_> = a.live_allocations().collect();
+    assert_eq!(live.len(), 1);
+    assert_eq!(live[0].addr, p1.as_ptr() as usize);
+    assert_eq!(live[0].caller.file(), file!());
+    assert_eq!(live[0].caller.line(), line);
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_watch() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static HITS: AtomicUsize = AtomicUsize::new(0);
+
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    a.set_hooks(crate::Hooks {
+        on_watch_hit: Some(|_, _| {
+            HITS.fetch_add(1, Ordering::Relaxed);
+        }),
+        ..crate::Hooks::NONE
+    });
+    a.watch(start + 16..start + 17).unwrap();
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    a.alloc(layout).unwrap();
+    assert_eq!(HITS.load(Ordering::Relaxed), 0);
+    a.alloc(layout).unwrap();
+    assert_eq!(HITS.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_recycle() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let small = Layout::from_size_align(24, 8).unwrap();
+    let p1 = a.alloc(small).unwrap();
+    let p2 = a.alloc(small).unwrap();
+    a.dealloc(p1, small);
+    let used = a.used_bytes();
+
+    // A 24-byte block is filed under the 16-byte class.
+    let p3 = a.alloc(Layout::from_size_align(16, 8).unwrap()).unwrap();
+    assert_eq!(p3, p1);
+    assert_eq!(a.used_bytes(), used);
+    let p4 = a.alloc(small).unwrap();
+    assert_ne!(p4, p1);
+
+    // Freeing the last block resets the bytes area and drops the free lists.
+    a.dealloc(p2, small);
+    a.dealloc(p3, small);
+    a.dealloc(p4, small);
+    assert_eq!(a.used_bytes(), 0);
+    assert_eq!(a.alloc(small).unwrap(), p1);
+    assert_eq!(a.used_bytes(), 24);
+}
+
+#[test]
+fn test_collision() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    a.alloc_pages(2, PAGE_SIZE).unwrap();
+    a.alloc(Layout::from_size_align(PAGE_SIZE, 8).unwrap())
+        .unwrap();
+    assert_eq!(a.largest_gap(), PAGE_SIZE);
+    assert!(matches!(
+        a.alloc(Layout::from_size_align(PAGE_SIZE + 1, 8).unwrap()),
+        Err(AllocError::MemoryOverlap)
+    ));
+    assert!(matches!(
+        a.alloc_pages(2, PAGE_SIZE),
+        Err(AllocError::MemoryOverlap)
+    ));
+    assert!(matches!(
+        a.alloc_pages(5, PAGE_SIZE),
+        Err(AllocError::NoMemory)
+    ));
+}
+
+#[test]
+fn test_rebalance() {
+    use crate::{Area, Rebalance};
+
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    a.set_boundary(0, Some(start + PAGE_SIZE)).unwrap();
+    let big = Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap();
+    assert!(matches!(a.alloc(big), Err(AllocError::NoMemory)));
+
+    a.set_rebalance(Rebalance::Custom(|area, needed, slack| {
+        area == Area::Bytes && needed <= slack / 2
+    }));
+    assert!(a.alloc(big).is_ok());
+
+    a.set_boundary(0, Some(start + 3 * PAGE_SIZE)).unwrap();
+    assert!(a.alloc_pages(2, PAGE_SIZE).is_err());
+    a.set_rebalance(Rebalance::Auto);
+    assert_eq!(a.alloc_pages(2, PAGE_SIZE).unwrap(), start + 2 * PAGE_SIZE);
+}
+
+#[test]
+fn test_alloc_excess() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(40, 8).unwrap();
+    let block = a.alloc_excess(layout).unwrap();
+    assert_eq!(block.len(), 40);
+    let _keep = a.alloc(layout).unwrap();
+
+    a.dealloc(block.cast(), layout);
+    let reused = a
+        .alloc_excess(Layout::from_size_align(20, 8).unwrap())
+        .unwrap();
+    assert_eq!(reused.cast::<u8>(), block.cast::<u8>());
+    assert_eq!(reused.len(), 32);
+}
+
+#[test]
+fn test_alloc_with() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    a.alloc(Layout::new::<u8>()).unwrap();
+    let v: &mut [u64; 4] = a.alloc_with(|| [1, 2, 3, 4]).unwrap();
+    assert_eq!(v as *mut _ as usize, start + 8);
+    v[0] = 5;
+    assert_eq!(*v, [5, 2, 3, 4]);
+}
+
+#[test]
+fn test_alloc_guarded() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let keep = a.alloc(Layout::from_size_align(8, 8).unwrap()).unwrap();
+    {
+        let mut outer = a
+            .alloc_guarded(Layout::from_size_align(100, 8).unwrap())
+            .unwrap();
+        let inner = outer
+            .allocator()
+            .alloc_guarded(Layout::from_size_align(200, 4).unwrap())
+            .unwrap();
+        assert_eq!(inner.as_ptr().as_ptr() as usize, start + 108);
+    }
+    assert_eq!(a.used_bytes(), 8);
+
+    let leaked = a.alloc_guarded(Layout::new::<u64>()).unwrap().leak();
+    assert_eq!(leaked.as_ptr() as usize, start + 8);
+    assert_eq!(a.used_bytes(), 16);
+    assert_ne!(keep, leaked);
+}
+
+#[cfg(feature = "containers")]
+#[test]
+fn test_containers() {
+    use crate::{EarlyBox, EarlyVec};
+    use core::cell::RefCell;
+
+    let (a, _) = allocator(4 * PAGE_SIZE);
+    let a = RefCell::new(a);
+    {
+        let mut b = EarlyBox::new(7u32, &a).unwrap();
+        *b += 1;
+        assert_eq!(*b, 8);
+
+        let mut v = EarlyVec::with_capacity(3, &a).unwrap();
+        for i in 0..3 {
+            v.push(i).unwrap();
+        }
+        assert_eq!(v.push(3), Err(3));
+        assert_eq!(&v[..], &[0, 1, 2]);
+        assert_eq!(v.pop(), Some(2));
+    }
+    assert_eq!(a.borrow().used_bytes(), 0);
+}
+
+#[test]
+fn test_snapshot_diff() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let before = a.snapshot();
+    assert_eq!(before, a.snapshot());
+
+    a.alloc(Layout::from_size_align(16, 8).unwrap()).unwrap();
+    let after = a.snapshot();
+    let diff: Vec<_> = before.diff(&after).collect();
+    assert_eq!(diff.len(), 1);
+    assert_eq!(diff[0].idx, 0);
+    let (old, new) = (diff[0].before.unwrap(), diff[0].after.unwrap());
+    assert_eq!((old.b_pos, new.b_pos), (start, start + 16));
+    assert_eq!((old.count, new.count), (0, 1));
+    assert_eq!(old.p_pos, new.p_pos);
+}
+
+#[test]
+fn test_fragmentation() {
+    let (mut a, _) = allocator(PAGE_SIZE);
+    let second = arena(PAGE_SIZE);
+    a.add_memory(second, PAGE_SIZE).unwrap();
+    assert_eq!(a.fragmentation(), 50);
+    assert_eq!(a.region_fragmentation(0).unwrap(), 0);
+
+    let block = Layout::from_size_align(PAGE_SIZE / 2, 8).unwrap();
+    let p = a.alloc(block).unwrap();
+    a.alloc(Layout::from_size_align(PAGE_SIZE / 4, 8).unwrap())
+        .unwrap();
+    a.dealloc(p, block);
+    // Region 0: a 2 KiB recycled block plus a 1 KiB gap.
+    assert_eq!(a.region_fragmentation(0).unwrap(), 34);
+    assert!(a.region_fragmentation(1).is_ok());
+    assert!(a.region_fragmentation(2).is_err());
+}
+
+#[test]
+fn test_utilization() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let second = arena(2 * PAGE_SIZE);
+    a.add_memory(second, 2 * PAGE_SIZE).unwrap();
+    a.alloc(Layout::from_size_align(PAGE_SIZE, 8).unwrap())
+        .unwrap();
+
+    let usage: Vec<_> = a.utilization().collect();
+    assert_eq!(usage, [(0, 25), (1, 0)]);
+    assert_eq!(a.worst_region(), Some((0, 25)));
+    a.alloc_pages(3, PAGE_SIZE).unwrap();
+    let usage: Vec<_> = a.utilization().collect();
+    assert_eq!(usage, [(0, 100), (1, 0)]);
+    assert_eq!(a.worst_region(), Some((0, 100)));
+}
+
+#[test]
+fn test_region_scan_wraps() {
+    let (mut a, start) = allocator(PAGE_SIZE);
+    let others: Vec<_> = (0..3).map(|_| arena(PAGE_SIZE)).collect();
+    for &r in &others {
+        a.add_memory(r, PAGE_SIZE).unwrap();
+    }
+    let half = Layout::from_size_align(PAGE_SIZE / 2, 8).unwrap();
+    let p = a.alloc(half).unwrap();
+    for &r in &others {
+        assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), r);
+    }
+    a.dealloc(p, half);
+
+    // The scan starts at the last region and wraps around to region 0.
+    assert_eq!(a.alloc(half).unwrap().as_ptr() as usize, start);
+    assert!(a.alloc_pages(1, PAGE_SIZE).is_err());
+}
+
+#[test]
+fn test_region_lookup() {
+    let base = arena(0x60_0000);
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    for start in [0x50_0000, 0x10_0000, 0x30_0000] {
+        a.add_memory(base + start, 0x1_0000).unwrap();
+    }
+    assert!(matches!(
+        a.add_memory(base + 0x2f_0000, 0x1_0001),
+        Err(AllocError::MemoryOverlap)
+    ));
+    a.add_memory(base + 0x2f_0000, 0x1_0000).unwrap();
+
+    assert_eq!(a.region_of(base + 0x10_0000), Some(1));
+    assert_eq!(a.region_of(base + 0x30_ffff), Some(2));
+    assert_eq!(a.region_of(base + 0x2f_8000), Some(3));
+    assert_eq!(a.region_of(base + 0x5f_0000), None);
+    assert!(!a.owns(base + 0x0f_ffff));
+    assert!(a.owns(base + 0x50_0000));
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_high_align_threshold() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    a.set_high_align_threshold(Some(64));
+    let small = Layout::from_size_align(8, 8).unwrap();
+    let line = Layout::from_size_align(64, 64).unwrap();
+
+    assert_eq!(a.alloc(small).unwrap().as_ptr() as usize, start);
+    let p = a.alloc(line).unwrap().as_ptr() as usize;
+    assert_eq!(p, start + 4 * PAGE_SIZE - 64);
+    assert_eq!(a.alloc(small).unwrap().as_ptr() as usize, start + 8);
+    a.alloc(line).unwrap();
+    assert_eq!(a.saved_padding(), 56 + 48);
+    assert_eq!(a.used_bytes(), 16 + 128);
+}
+
+#[cfg(feature = "model")]
+#[test]
+fn test_model_checked() {
+    let (inner, start) = allocator(4 * PAGE_SIZE);
+    let mut a = crate::ModelChecked::new(inner);
+    let small = Layout::from_size_align(24, 8).unwrap();
+    let p1 = a.alloc(small).unwrap();
+    let p2 = a.alloc(small).unwrap();
+    let page = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(a.model().len(), 3);
+    assert!(a.model().overlapping(&(start..start + 1)).is_some());
+
+    a.dealloc(p1, small);
+    a.dealloc(p2, small);
+    a.dealloc_pages(page, 1);
+    assert!(a.model().is_empty());
+}
+
+#[cfg(feature = "model")]
+#[test]
+#[should_panic(expected = "was not granted")]
+fn test_model_checked_double_free() {
+    let (inner, _) = allocator(PAGE_SIZE);
+    let mut a = crate::ModelChecked::new(inner);
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    let p = a.alloc(layout).unwrap();
+    a.dealloc(p, layout);
+    a.dealloc(p, layout);
+}
+
+#[cfg(not(loom))]
+#[test]
+fn test_locked() {
+    use crate::LockedEarlyAllocator;
+
+    static LOCKED: LockedEarlyAllocator<PAGE_SIZE> = LockedEarlyAllocator::new();
+    LOCKED.lock().init(arena(PAGE_SIZE), PAGE_SIZE);
+    let layout = Layout::from_size_align(16, 8).unwrap();
+
+    let mut guard = LOCKED.lock();
+    assert!(LOCKED.try_lock().is_none());
+    let ptr = guard.alloc(layout).unwrap();
+    drop(guard);
+
+    let ptr2 = std::thread::spawn(move || LOCKED.lock().alloc(layout).unwrap().as_ptr() as usize)
+        .join()
+        .unwrap();
+    assert_ne!(ptr.as_ptr() as usize, ptr2);
+    assert_eq!(LOCKED.try_lock().unwrap().used_bytes(), 32);
+}
+
+#[cfg(not(loom))]
+#[test]
+fn test_locked_hot_add() {
+    use crate::LockedEarlyAllocator;
+
+    static LOCKED: LockedEarlyAllocator<PAGE_SIZE> = LockedEarlyAllocator::new();
+    LOCKED.lock().init(arena(PAGE_SIZE), PAGE_SIZE);
+    assert_eq!(LOCKED.total_bytes(), PAGE_SIZE);
+    let layout = Layout::from_size_align(PAGE_SIZE, 8).unwrap();
+    {
+        let _ = LOCKED.lock().alloc(layout).unwrap();
+    }
+
+    let worker = std::thread::spawn(move || loop {
+        if let Ok(ptr) = LOCKED.lock().alloc(layout) {
+            break ptr.as_ptr() as usize;
+        }
+        std::thread::yield_now();
+    });
+    let more = arena(2 * PAGE_SIZE);
+    LOCKED.add_memory(more, 2 * PAGE_SIZE).unwrap();
+    let ptr = worker.join().unwrap();
+    assert!((more..more + 2 * PAGE_SIZE).contains(&ptr));
+    assert_eq!(LOCKED.total_bytes(), 3 * PAGE_SIZE);
+}
+
+#[cfg(not(loom))]
+#[test]
+fn test_stats_snapshot() {
+    use crate::LockedEarlyAllocator;
+
+    static LOCKED: LockedEarlyAllocator<PAGE_SIZE> = LockedEarlyAllocator::new();
+    LOCKED.lock().init(arena(4 * PAGE_SIZE), 4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let writer = std::thread::spawn(move || {
+        for _ in 0..1000 {
+            let page = LOCKED.lock().alloc_pages(1, PAGE_SIZE).unwrap();
+            let ptr = LOCKED.lock().alloc(layout).unwrap();
+            LOCKED.lock().dealloc(ptr, layout);
+            LOCKED.lock().dealloc_pages(page, 1);
+        }
+    });
+    while !writer.is_finished() {
+        let stats = LOCKED.stats_snapshot();
+        // A torn read could pair a page-less `used_bytes` with `used_pages`.
+        assert!(stats.used_bytes >= stats.used_pages * PAGE_SIZE);
+        assert!(stats.peak_used_bytes >= stats.used_bytes);
+    }
+    writer.join().unwrap();
+    let stats = LOCKED.stats_snapshot();
+    assert_eq!(stats.total_bytes, 4 * PAGE_SIZE);
+    assert_eq!((stats.used_bytes, stats.used_pages), (0, 0));
+    assert_eq!(stats.peak_used_bytes, PAGE_SIZE + 64);
+}
+
+#[cfg(not(loom))]
+#[test]
+fn test_try_alloc_irq() {
+    use crate::{EarlyAllocError, LockedEarlyAllocator};
+
+    static LOCKED: LockedEarlyAllocator<PAGE_SIZE> = LockedEarlyAllocator::new();
+    LOCKED.lock().init(arena(PAGE_SIZE), PAGE_SIZE);
+    let layout = Layout::from_size_align(16, 8).unwrap();
+
+    {
+        let _guard = LOCKED.lock();
+        assert!(matches!(
+            LOCKED.try_alloc_irq(layout),
+            Err(EarlyAllocError::WouldBlock)
+        ));
+    }
+    assert!(LOCKED.try_alloc_irq(layout).is_ok());
+    let huge = Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap();
+    assert!(matches!(
+        LOCKED.try_alloc_irq(huge),
+        Err(EarlyAllocError::Alloc(AllocError::NoMemory))
+    ));
+}
+
+#[cfg(loom)]
+#[test]
+fn test_locked_loom() {
+    use crate::LockedEarlyAllocator;
+    use loom::sync::Arc;
+
+    loom::model(|| {
+        let locked = Arc::new(LockedEarlyAllocator::<PAGE_SIZE>::new());
+        locked.lock().init(arena(PAGE_SIZE), PAGE_SIZE);
+        let layout = Layout::from_size_align(16, 8).unwrap();
+
+        let other = locked.clone();
+        let thread =
+            loom::thread::spawn(move || other.lock().alloc(layout).unwrap().as_ptr() as usize);
+        let ptr = locked.lock().alloc(layout).unwrap().as_ptr() as usize;
+        assert_ne!(ptr, thread.join().unwrap());
+        assert_eq!(locked.lock().used_bytes(), 32);
+    });
+}
+
+#[test]
+#[cfg(not(any(feature = "canary", feature = "sentinel")))]
+fn test_top_of_address_space() {
+    // Nothing below touches the memory, so the addresses can be made up.
+    // Canaries and sentinels are written into the region, and a test
+    // process has no memory at the top of the address space to back it.
+    let start = 0usize.wrapping_sub(4 * PAGE_SIZE);
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    a.init(start, 4 * PAGE_SIZE);
+    assert_eq!(a.total_bytes(), 4 * PAGE_SIZE - 1);
+
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), start + 2 * PAGE_SIZE);
+    let p = a.alloc(Layout::from_size_align(8, 8).unwrap()).unwrap();
+    assert_eq!(p.as_ptr() as usize, start);
+    let huge = Layout::from_size_align(usize::MAX / 4, 8).unwrap();
+    assert!(matches!(a.alloc(huge), Err(AllocError::NoMemory)));
+    assert!(matches!(
+        a.alloc_pages(usize::MAX / PAGE_SIZE, PAGE_SIZE),
+        Err(AllocError::InvalidParam) | Err(AllocError::NoMemory)
+    ));
+    assert!(matches!(
+        a.add_memory(usize::MAX - 10, 100),
+        Err(AllocError::InvalidParam)
+    ));
+
+    let mut b = EarlyAllocator::<PAGE_SIZE>::new();
+    let top = 0usize.wrapping_sub(2 * PAGE_SIZE);
+    assert_eq!(
+        b.add_memory_aligned(top - 0x10, 2 * PAGE_SIZE + 0x10, PAGE_SIZE)
+            .unwrap(),
+        0x10
+    );
+    assert_eq!(b.total_bytes(), 2 * PAGE_SIZE - 1);
+}
+
+#[cfg(target_pointer_width = "32")]
+#[test]
+fn test_32bit_regions() {
+    assert_eq!(crate::MAX_REGIONS, 32);
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    a.init(0xC000_0000, 0x4000_0000);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), 0xFFFF_E000);
+    for idx in 1..32 {
+        a.add_memory(idx * 0x10_0000, PAGE_SIZE).unwrap();
+    }
+    assert!(matches!(
+        a.add_memory(0x8000_0000, PAGE_SIZE),
+        Err(AllocError::NoMemory)
+    ));
+}
+
+#[test]
+fn test_contiguous_pages_available() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let other = arena(16 * PAGE_SIZE);
+    a.add_memory(other, 16 * PAGE_SIZE).unwrap();
+
+    assert_eq!(a.contiguous_pages_available(2, PAGE_SIZE), Some(0));
+    assert_eq!(a.contiguous_pages_available(8, PAGE_SIZE), Some(1));
+    assert_eq!(a.contiguous_pages_available(32, PAGE_SIZE), None);
+    assert_eq!(a.contiguous_pages_available(1, 3 * PAGE_SIZE), None);
+    assert_eq!(a.available_bytes(), 20 * PAGE_SIZE);
+
+    let pos = a.alloc_pages(8, PAGE_SIZE).unwrap();
+    assert!(pos >= other && pos != start);
+}
+
+#[test]
+fn test_contiguous_pages_available_matches_alloc() {
+    use crate::PageGuard;
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    assert_eq!(a.contiguous_pages_available(4, PAGE_SIZE), Some(0));
+
+    // The guard page makes a run of the whole region fail.
+    a.set_page_guards(PageGuard::After);
+    assert_eq!(a.contiguous_pages_available(4, PAGE_SIZE), None);
+    assert!(a.alloc_pages(4, PAGE_SIZE).is_err());
+    assert_eq!(a.contiguous_pages_available(3, PAGE_SIZE), Some(0));
+
+    a.seal(false);
+    assert_eq!(a.contiguous_pages_available(1, PAGE_SIZE), None);
+    assert!(a.alloc_pages(1, PAGE_SIZE).is_err());
+}
+
+#[test]
+fn test_free_ranges() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let other = arena(PAGE_SIZE);
+    a.add_memory(other, PAGE_SIZE).unwrap();
+    let layout = Layout::from_size_align(32, 8).unwrap();
+    let p1 = a.alloc(layout).unwrap();
+    a.alloc(layout).unwrap();
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    a.dealloc(p1, layout);
+
+    let mut expected = vec![start..start + 32, start + 64..start + 3 * PAGE_SIZE];
+    let other_gap = other..other + PAGE_SIZE;
+    if other < start {
+        expected.insert(0, other_gap);
+    } else {
+        expected.push(other_gap);
+    }
+    assert_eq!(a.free_ranges().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn test_page_refcount() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let page = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(a.page_refcount(page).unwrap(), 1);
+    assert_eq!(a.get_page(page).unwrap(), 2);
+    assert_eq!(a.put_page(page).unwrap(), 1);
+    assert_eq!(a.put_page(page).unwrap(), 0);
+
+    // The last reference freed the page, which is handed out again.
+    assert!(matches!(a.get_page(page), Err(AllocError::NotAllocated)));
+    assert!(matches!(a.put_page(page), Err(AllocError::NotAllocated)));
+    assert!(matches!(a.put_page(start), Err(AllocError::NotAllocated)));
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), page);
+    assert_eq!(a.page_refcount(page).unwrap(), 1);
+    assert!(matches!(
+        a.get_page(page + 8),
+        Err(AllocError::InvalidParam)
+    ));
+
+    // Freeing `page` out of order steals the bitmap of freed pages right
+    // below `other`. Once the page below it is freed too, the bitmap page
+    // lies at the bottom of the pages area but still cannot be freed.
+    let other = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(a.put_page(page).unwrap(), 0);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), page);
+    let bitmap = other - PAGE_SIZE;
+    let below = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(below, bitmap - PAGE_SIZE);
+    a.dealloc_pages(below, 1);
+    assert!(matches!(a.put_page(bitmap), Err(AllocError::NotAllocated)));
+    let available = a.available_pages();
+    a.dealloc_pages(bitmap, 1);
+    assert_eq!(a.available_pages(), available);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), below);
+}
+
+#[test]
+fn test_allocation_id() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let id = a.alloc_pages_id(2, PAGE_SIZE).unwrap();
+    assert_eq!(id.addr(), start + 2 * PAGE_SIZE);
+    assert_eq!(id.num_pages(), 2);
+    assert_eq!(id.region(), 0);
+    a.dealloc_pages_id(id).unwrap();
+
+    let other = a.alloc_pages_id(1, PAGE_SIZE).unwrap();
+    assert_ne!(other, id);
+}
+
+#[test]
+fn test_free_pages() {
+    let (mut a, start) = allocator(16 * PAGE_SIZE);
+    let p1 = a.alloc_pages(2, PAGE_SIZE).unwrap();
+    let p2 = a.alloc_pages(2, PAGE_SIZE).unwrap();
+    let p3 = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(p1, start + 14 * PAGE_SIZE);
+
+    // The lowest run just moves `p_pos` back.
+    a.dealloc_pages(p3, 1);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), p3);
+
+    // Other runs are marked freed and reused first.
+    a.dealloc_pages(p2, 2);
+    assert!(a.free_ranges().any(|r| r == (p2..p2 + 2 * PAGE_SIZE)));
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), p2);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), p2 + PAGE_SIZE);
+
+    // The bitmap took the page below `p3`. Freeing the lowest run coalesces
+    // with the freed runs right above it.
+    let p4 = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    let p5 = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(p4, start + 9 * PAGE_SIZE);
+    a.dealloc_pages(p4, 1);
+    a.dealloc_pages(p5, 1);
+    assert_eq!(a.alloc_pages(2, PAGE_SIZE).unwrap(), p5);
+
+    // Double frees are ignored.
+    a.dealloc_pages(p1, 2);
+    let used = a.used_bytes();
+    a.dealloc_pages(p1, 2);
+    assert_eq!(a.used_bytes(), used);
+    assert_eq!(a.available_bytes(), 16 * PAGE_SIZE - used);
+
+    // So are frees of misaligned addresses.
+    let used_pages = a.used_pages();
+    a.dealloc_pages(p5 + 1, 1);
+    a.dealloc_pages(p5 + PAGE_SIZE / 2, 2);
+    assert_eq!((a.used_bytes(), a.used_pages()), (used, used_pages));
+    assert!(!a.free_ranges().any(|r| r.contains(&p5)));
+}
+
+#[test]
+fn test_page_counters() {
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    a.alloc(Layout::from_size_align(8, 8).unwrap()).unwrap();
+    assert_eq!(a.used_pages(), 0);
+    assert_eq!(a.available_pages(), 7);
+
+    let p = a.alloc_pages(2, PAGE_SIZE).unwrap();
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(a.used_pages(), 3);
+    assert_eq!(a.available_pages(), 4);
+
+    a.dealloc_pages(p, 2);
+    assert_eq!(a.used_pages(), 1);
+    assert_eq!(a.available_pages(), 5);
+}
+
+#[test]
+fn test_total_pages_unaligned_regions() {
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    let first = arena(4 * PAGE_SIZE);
+    let second = arena(4 * PAGE_SIZE);
+    // Each region has a sub-page remainder at both ends.
+    a.init(first + 0x800, 2 * PAGE_SIZE + 0x400);
+    a.add_memory(second + 0x800, 2 * PAGE_SIZE + 0x400).unwrap();
+    assert_eq!(a.total_bytes(), 4 * PAGE_SIZE + 0x800);
+    assert_eq!(a.total_pages(), 2);
+    assert_eq!(a.available_pages(), 2);
+}
+
+#[test]
+fn test_region_priority() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let dram = arena(4 * PAGE_SIZE);
+    a.add_memory(dram, 4 * PAGE_SIZE).unwrap();
+    a.set_region_priority(0, 1).unwrap();
+    assert_eq!(a.region_priority(0).unwrap(), 1);
+    assert!(matches!(
+        a.set_region_priority(5, 1),
+        Err(AllocError::InvalidParam)
+    ));
+
+    // Region 1 is consumed first, region 0 only once it is exhausted.
+    assert_eq!(a.alloc_pages(4, PAGE_SIZE).unwrap(), dram);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), start + 3 * PAGE_SIZE);
+    let p = a.alloc(Layout::from_size_align(8, 8).unwrap()).unwrap();
+    assert_eq!(p.as_ptr() as usize, start);
+}
+
+#[test]
+fn test_exclude() {
+    let start = arena(8 * PAGE_SIZE);
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    a.exclude(start + 2 * PAGE_SIZE..start + 3 * PAGE_SIZE)
+        .unwrap();
+    a.exclude(start + 7 * PAGE_SIZE..start + 9 * PAGE_SIZE)
+        .unwrap();
+    assert!(matches!(
+        a.exclude(start..start),
+        Err(AllocError::InvalidParam)
+    ));
+    a.init(start, 8 * PAGE_SIZE);
+
+    assert_eq!(a.total_bytes(), 6 * PAGE_SIZE);
+    assert!(a.owns(start + PAGE_SIZE));
+    assert!(!a.owns(start + 2 * PAGE_SIZE));
+    assert!(a.owns(start + 3 * PAGE_SIZE));
+    assert!(!a.owns(start + 7 * PAGE_SIZE));
+    assert_eq!(a.region_of(start + 6 * PAGE_SIZE), Some(1));
+}
+
+#[test]
+fn test_exclude_split_all_or_nothing() {
+    use crate::MAX_REGIONS;
+    let (mut a, _) = allocator(PAGE_SIZE);
+    let buf = arena(MAX_REGIONS * PAGE_SIZE);
+    let split = buf + (MAX_REGIONS - 2) * PAGE_SIZE;
+    a.exclude(split + PAGE_SIZE / 2..split + PAGE_SIZE).unwrap();
+    for idx in 0..MAX_REGIONS - 2 {
+        a.add_memory(buf + idx * PAGE_SIZE, PAGE_SIZE).unwrap();
+    }
+
+    // Two pieces but a single free slot: neither piece is added.
+    let total = a.total_bytes();
+    assert!(matches!(
+        a.add_memory(split, 2 * PAGE_SIZE),
+        Err(AllocError::NoMemory)
+    ));
+    assert_eq!(a.total_bytes(), total);
+    assert!(!a.owns(split));
+    a.add_memory(split, PAGE_SIZE / 2).unwrap();
+}
+
+#[test]
+fn test_memory_observer() {
+    use crate::MemoryObserver;
+    use core::ops::Range;
+    use std::sync::Mutex;
+
+    struct Log(Mutex<Vec<(Range<usize>, u32)>>);
+
+    impl MemoryObserver for Log {
+        fn memory_added(&self, range: Range<usize>, flags: u32) {
+            self.0.lock().unwrap().push((range, flags));
+        }
+    }
+
+    static LOG: Log = Log(Mutex::new(Vec::new()));
+    let (mut a, _) = allocator(PAGE_SIZE);
+    a.register_observer(&LOG).unwrap();
+    let sram = arena(2 * PAGE_SIZE);
+    a.exclude(sram + PAGE_SIZE / 2..sram + PAGE_SIZE).unwrap();
+    a.add_memory_flags(sram, 2 * PAGE_SIZE, 1).unwrap();
+    assert_eq!(a.region_flags(1).unwrap(), 1);
+
+    assert!(a.unregister_observer(&LOG));
+    assert!(!a.unregister_observer(&LOG));
+    a.add_memory(arena(PAGE_SIZE), PAGE_SIZE).unwrap();
+    assert_eq!(
+        *LOG.0.lock().unwrap(),
+        [
+            (sram..sram + PAGE_SIZE / 2, 1),
+            (sram + PAGE_SIZE..sram + 2 * PAGE_SIZE, 1)
+        ]
+    );
+}
+
+#[test]
+fn test_withdraw_tail() {
+    let (mut a, start) = allocator(8 * PAGE_SIZE);
+    a.alloc(Layout::from_size_align(PAGE_SIZE, 8).unwrap())
+        .unwrap();
+    assert_eq!(
+        a.withdraw_tail(0, 4 * PAGE_SIZE).unwrap(),
+        start + 4 * PAGE_SIZE..start + 8 * PAGE_SIZE
+    );
+    assert_eq!(a.total_bytes(), 4 * PAGE_SIZE);
+    assert!(matches!(
+        a.withdraw_tail(0, 4 * PAGE_SIZE),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        a.withdraw_tail(0, 3 * PAGE_SIZE + 1),
+        Err(AllocError::MemoryOverlap)
+    ));
+
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert!(matches!(
+        a.withdraw_tail(0, PAGE_SIZE),
+        Err(AllocError::MemoryOverlap)
+    ));
+}
+
+#[test]
+fn test_reclaim() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static OFFERED: AtomicUsize = AtomicUsize::new(0);
+    fn balloon(range: core::ops::Range<usize>) -> bool {
+        // Only take the first range offered.
+        OFFERED.fetch_add(range.len(), Ordering::Relaxed) == 0
+    }
+
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let other = arena(4 * PAGE_SIZE);
+    a.add_memory(other, 4 * PAGE_SIZE).unwrap();
+    assert_eq!(a.reclaim(PAGE_SIZE), 0);
+
+    a.set_reclaimer(Some(balloon));
+    a.alloc(Layout::from_size_align(8, 8).unwrap()).unwrap();
+    assert_eq!(a.reclaim(5 * PAGE_SIZE), 3 * PAGE_SIZE);
+    assert_eq!(OFFERED.load(Ordering::Relaxed), 5 * PAGE_SIZE);
+    assert_eq!(a.used_pages(), 3);
+    assert_eq!(a.available_bytes(), 5 * PAGE_SIZE - 8);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), other + 3 * PAGE_SIZE);
+}
+
+#[cfg(feature = "track")]
+#[test]
+fn test_compact() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let p1 = a.alloc(layout).unwrap();
+    a.alloc(Layout::from_size_align(24, 8).unwrap()).unwrap();
+    let p3 = a.alloc(layout).unwrap();
+    let p4 = a.alloc(layout).unwrap();
+    unsafe { p3.as_ptr().write_bytes(0xAB, 64) };
+    a.dealloc(p1, layout);
+    a.dealloc(p4, layout);
+
+    let mut moved = Vec::new();
+    let reclaimed = unsafe { a.compact(|old, new, len| moved.push((old, new, len))) }.unwrap();
+    assert_eq!(reclaimed, 64);
+    assert_eq!(
+        moved,
+        [(start + 64, start, 24), (start + 88, start + 24, 64)]
+    );
+    let p3 = (start + 24) as *const u8;
+    assert!((0..64).all(|i| unsafe { *p3.add(i) } == 0xAB));
+    assert_eq!(a.used_bytes(), 88);
+}
+
+#[test]
+fn test_epoch_reset() {
+    let (mut a, start) = allocator(8 * PAGE_SIZE);
+    let layout = Layout::from_size_align(32, 8).unwrap();
+    let kept = a.alloc(layout).unwrap();
+    let page = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    let epoch = a.mark_epoch();
+    let used = a.used_bytes();
+
+    a.alloc(layout).unwrap();
+    a.alloc_pages(2, PAGE_SIZE).unwrap();
+    a.add_memory(arena(PAGE_SIZE), PAGE_SIZE).unwrap();
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    a.reset_to_epoch(&epoch).unwrap();
+
+    assert_eq!(a.used_bytes(), used);
+    assert_eq!(a.used_pages(), 1);
+    assert_eq!(a.alloc(layout).unwrap().as_ptr() as usize, start + 32);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), page - PAGE_SIZE);
+    a.dealloc(kept, layout);
+}
+
+#[test]
+fn test_epoch_reset_drops_reserve() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let epoch = a.mark_epoch();
+    a.set_emergency_reserve(PAGE_SIZE).unwrap();
+    let reserve = a.emergency_available();
+    assert_eq!(reserve, PAGE_SIZE);
+    a.reset_to_epoch(&epoch).unwrap();
+    assert_eq!(a.emergency_available(), 0);
+
+    // The reserve page is handed out again, and never from the reserve.
+    let page = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    while a.alloc(layout).is_ok() {}
+    assert!(a.alloc_critical(layout).is_err());
+    assert!(a.owns(page));
+}
+
+#[test]
+fn test_epoch_reset_drops_scratch() {
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    let small = Layout::from_size_align(100, 8).unwrap();
+    a.alloc_scratch(small).unwrap();
+    let epoch = a.mark_epoch();
+    let big = Layout::from_size_align(PAGE_SIZE, 64).unwrap();
+    a.alloc_scratch(big).unwrap();
+    a.reset_to_epoch(&epoch).unwrap();
+
+    // The chunk rolled back is not bumped into after being handed out.
+    let page = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    let p = a.alloc_scratch(small).unwrap().as_ptr() as usize;
+    assert!(!(page..page + PAGE_SIZE).contains(&p));
+    // The chunk from before the epoch and the new one are given back.
+    assert_eq!(a.release_scratch_all(), 2);
+    a.dealloc_pages(page, 1);
+}
+
+#[test]
+fn test_release_scratch_stale_chain() {
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    // Each block takes a two-page chunk of its own, and lies in its
+    // lower page.
+    let big = Layout::from_size_align(PAGE_SIZE + 1, 8).unwrap();
+    let old = a.alloc_scratch(big).unwrap().as_ptr() as usize & !(PAGE_SIZE - 1);
+    a.alloc_scratch(big).unwrap();
+
+    // The older chunk is freed behind the allocator's back and handed out
+    // again, so releasing must leave it to its new owner.
+    a.dealloc_pages(old, 2);
+    assert_eq!(a.alloc_pages(2, PAGE_SIZE).unwrap(), old);
+    assert_eq!(a.release_scratch_all(), 2);
+    assert_eq!(a.release_scratch_all(), 0);
+    assert_eq!(a.page_refcount(old).unwrap(), 1);
+    a.dealloc_pages(old, 2);
+}
+
+#[test]
+fn test_scratch_capacity() {
+    use crate::SCRATCH_CAPACITY;
+    let (mut a, _) = allocator((2 * SCRATCH_CAPACITY + 2) * PAGE_SIZE);
+    let big = Layout::from_size_align(PAGE_SIZE + 1, 8).unwrap();
+    for _ in 0..SCRATCH_CAPACITY {
+        a.alloc_scratch(big).unwrap();
+    }
+    assert!(matches!(a.alloc_scratch(big), Err(AllocError::NoMemory)));
+    assert_eq!(a.release_scratch_all(), 2 * SCRATCH_CAPACITY);
+    a.alloc_scratch(big).unwrap();
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_page_guards() {
+    use crate::PageGuard;
+
+    let (mut a, start) = allocator(16 * PAGE_SIZE);
+    a.set_page_guards(PageGuard::After);
+    assert_eq!(a.alloc_pages(2, PAGE_SIZE).unwrap(), start + 13 * PAGE_SIZE);
+    a.set_page_guards(PageGuard::Around);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), start + 11 * PAGE_SIZE);
+    a.set_page_guards(PageGuard::Off);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), start + 9 * PAGE_SIZE);
+    assert_eq!(a.used_pages(), 4);
+
+    // The guard below must fit too, or nothing is allocated.
+    a.set_page_guards(PageGuard::Around);
+    assert!(a.alloc_pages(8, PAGE_SIZE).is_err());
+    assert_eq!(a.available_bytes(), 9 * PAGE_SIZE);
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_exec_pages() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let text = arena(4 * PAGE_SIZE);
+    a.add_memory(text, 4 * PAGE_SIZE).unwrap();
+    a.set_exec_region(Some(1)).unwrap();
+
+    assert_eq!(
+        a.alloc_pages_exec(1, PAGE_SIZE).unwrap(),
+        text + 3 * PAGE_SIZE
+    );
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), start + 3 * PAGE_SIZE);
+    assert!(a.alloc_pages(4, PAGE_SIZE).is_err());
+    let p = a.alloc(Layout::from_size_align(8, 8).unwrap()).unwrap();
+    assert_eq!(p.as_ptr() as usize, start);
+    assert_eq!((a.used_pages(), a.exec_pages()), (2, 1));
+
+    // Data runs are not freed as exec runs.
+    a.dealloc_pages_exec(start + 3 * PAGE_SIZE, 1);
+    assert_eq!((a.used_pages(), a.exec_pages()), (2, 1));
+
+    a.dealloc_pages_exec(text + 3 * PAGE_SIZE, 1);
+    assert_eq!((a.used_pages(), a.exec_pages()), (1, 0));
+
+    a.set_exec_region(None).unwrap();
+    a.dealloc_pages_exec(start + 3 * PAGE_SIZE, 1);
+    assert_eq!((a.used_pages(), a.exec_pages()), (1, 0));
+    let code = a.alloc_pages_exec(2, PAGE_SIZE).unwrap();
+    a.dealloc_pages_exec(code, 3);
+    assert_eq!((a.used_pages(), a.exec_pages()), (3, 2));
+    a.dealloc_pages_exec(code, 2);
+    assert_eq!((a.used_pages(), a.exec_pages()), (1, 0));
+}
+
+#[test]
+fn test_cacheline_and_min_align() {
+    use crate::CACHE_LINE;
+
+    let (mut a, start) = allocator(PAGE_SIZE);
+    a.alloc(Layout::from_size_align(4, 4).unwrap()).unwrap();
+    let line = a.alloc_cacheline(72).unwrap().as_ptr() as usize;
+    assert_eq!(line, start + CACHE_LINE);
+    let next = a.alloc(Layout::from_size_align(4, 4).unwrap()).unwrap();
+    assert_eq!(next.as_ptr() as usize, start + 3 * CACHE_LINE);
+
+    assert!(matches!(a.set_min_align(24), Err(AllocError::InvalidParam)));
+    a.set_min_align(16).unwrap();
+    let p = a.alloc(Layout::from_size_align(4, 4).unwrap()).unwrap();
+    assert_eq!(p.as_ptr() as usize, start + 3 * CACHE_LINE + 16);
+}
+
+#[test]
+fn test_alloc_dma() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    assert!(matches!(
+        a.alloc_dma(layout, 0xFFF0),
+        Err(AllocError::InvalidParam)
+    ));
+
+    let p = a.alloc_dma(layout, u64::MAX).unwrap();
+    assert_eq!(p.as_ptr() as usize, start);
+    // The arena lives far above 64 KiB, so nothing can satisfy this mask.
+    assert!(a.alloc_dma(layout, 0xFFFF).is_err());
+    assert_eq!(a.used_bytes(), 64);
+    a.dealloc(p, layout);
+    assert_eq!(a.used_bytes(), 0);
+}
+
+#[test]
+fn test_bounce_pool() {
+    use crate::BouncePool;
+
+    let (mut a, start) = allocator(8 * PAGE_SIZE);
+    let mut pool = BouncePool::new(&mut a, 2, 100, u64::MAX).unwrap();
+    assert_eq!(pool.buf_size(), PAGE_SIZE);
+    assert_eq!(a.used_pages(), 2);
+    assert!(BouncePool::new(&mut a, 1, PAGE_SIZE, 0xFFFF).is_err());
+
+    let b1 = pool.grab(0x1234, 64).unwrap();
+    let b2 = pool.grab(0x5678, PAGE_SIZE).unwrap();
+    assert_eq!(b1.addr, start + 7 * PAGE_SIZE);
+    assert!(pool.grab(0x9abc, 8).is_none());
+    assert_eq!(pool.lookup(b2.addr), Some(b2));
+
+    assert_eq!(pool.release(b1.addr), Some(b1));
+    assert_eq!(pool.release(b1.addr), None);
+    assert_eq!(pool.available(), 1);
+    assert!(pool.grab(0, PAGE_SIZE + 1).is_none());
+}
+
+#[test]
+fn test_fragmented_across_regions() {
+    use crate::EarlyAllocError;
+
+    let (mut a, _) = allocator(2 * PAGE_SIZE);
+    a.add_memory(arena(2 * PAGE_SIZE), 2 * PAGE_SIZE).unwrap();
+    assert_eq!(a.available_pages(), 4);
+
+    assert!(matches!(
+        a.try_alloc_pages(3, PAGE_SIZE),
+        Err(EarlyAllocError::Fragmented)
+    ));
+    assert!(matches!(
+        a.try_alloc_pages(5, PAGE_SIZE),
+        Err(EarlyAllocError::OutOfMemory { size, align: PAGE_SIZE }) if size == 5 * PAGE_SIZE
+    ));
+    let pos = a.try_alloc_pages(2, PAGE_SIZE).unwrap();
+    assert_eq!(a.region_of(pos), a.region_of(pos + 2 * PAGE_SIZE - 1));
+    assert!(matches!(
+        AllocError::from(EarlyAllocError::Fragmented),
+        AllocError::NoMemory
+    ));
+}
+
+#[test]
+fn test_misaligned_run_is_not_fragmented() {
+    use crate::EarlyAllocError;
+
+    // Enough pages in a single region, but no run aligned to `align`.
+    let align = 16 * PAGE_SIZE;
+    let start = arena(2 * align).next_multiple_of(align) + PAGE_SIZE;
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    a.init(start, 4 * PAGE_SIZE);
+    assert_eq!(a.available_pages(), 4);
+    assert!(matches!(
+        a.try_alloc_pages(2, align),
+        Err(EarlyAllocError::OutOfMemory {
+            size: 0x2000,
+            align: 0x10000
+        })
+    ));
+}
+
+#[test]
+fn test_shrink_pages() {
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    let mut id = a.alloc_pages_id(4, PAGE_SIZE).unwrap();
+    assert!(matches!(
+        a.shrink_pages_id(&mut id, 5),
+        Err(AllocError::InvalidParam)
+    ));
+    a.shrink_pages_id(&mut id, 1).unwrap();
+    assert_eq!(id.num_pages(), 1);
+    assert_eq!(a.used_pages(), 1);
+
+    // The trailing pages are reused by the next run that fits in them.
+    let pos = a.alloc_pages(3, PAGE_SIZE).unwrap();
+    assert_eq!(pos, id.addr() + PAGE_SIZE);
+    a.shrink_pages(pos, 3, 2).unwrap();
+    assert!(matches!(
+        a.shrink_pages(pos, 3, 2),
+        Err(AllocError::NotAllocated)
+    ));
+    a.dealloc_pages(pos, 2);
+    a.dealloc_pages_id(id).unwrap();
+    assert_eq!(a.used_pages(), 0);
+}
+
+#[test]
+fn test_page_box() {
+    use crate::LockedEarlyAllocator;
+
+    let locked = LockedEarlyAllocator::<PAGE_SIZE>::new();
+    locked.lock().init(arena(4 * PAGE_SIZE), 4 * PAGE_SIZE);
+    let probe = |fail: bool| -> AllocResult<usize> {
+        let ring = locked.alloc_pages_owned(2, PAGE_SIZE)?;
+        assert_eq!(ring.size(), 2 * PAGE_SIZE);
+        assert_eq!(locked.lock().used_pages(), 2);
+        if fail {
+            return Err(AllocError::NoMemory);
+        }
+        Ok(ring.leak())
+    };
+    assert!(probe(true).is_err());
+    assert_eq!(locked.lock().used_pages(), 0);
+    let addr = probe(false).unwrap();
+    assert_eq!(locked.lock().used_pages(), 2);
+    locked.lock().dealloc_pages(addr, 2);
+    assert!(locked.alloc_pages_owned(5, PAGE_SIZE).is_err());
+}
+
+#[test]
+fn test_tracked_frames() {
+    use crate::TrackedFrame;
+
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    let first = a.alloc_pages(2, PAGE_SIZE).unwrap();
+    let middle = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    let last = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    a.dealloc_pages(middle, 1);
+
+    // The bitmap marking `middle` as freed is stolen from below `last`, so
+    // their frames are reported together.
+    let frames: Vec<_> = a.tracked_frames(PAGE_SIZE).collect();
+    assert_eq!(
+        frames,
+        [
+            TrackedFrame {
+                paddr: last - 2 * PAGE_SIZE,
+                pages: 2,
+                source_region: 0,
+            },
+            TrackedFrame {
+                paddr: first - PAGE_SIZE,
+                pages: 2,
+                source_region: 0,
+            },
+        ]
+    );
+    a.dealloc_pages(last, 1);
+    a.dealloc_pages(first, 2);
+    assert_eq!(a.tracked_frames(0).count(), 1);
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_page_table_page() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let page = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    // SAFETY: the page is allocated and unused.
+    unsafe { core::ptr::write_bytes(page as *mut u8, 0xa5, PAGE_SIZE) };
+    a.dealloc_pages(page, 1);
+
+    let table = a.alloc_page_table().unwrap();
+    assert_eq!(table.addr() % PAGE_SIZE, 0);
+    // SAFETY: the table page is allocated.
+    let bytes = unsafe { core::slice::from_raw_parts(table.addr() as *const u8, PAGE_SIZE) };
+    assert!(bytes.iter().all(|&b| b == 0));
+    assert_eq!(a.page_table_pages(), 1);
+    assert_eq!(a.used_pages(), 1);
+    a.dealloc_page_table(table);
+    assert_eq!(a.page_table_pages(), 0);
+    assert_eq!(a.used_pages(), 0);
+
+    // A table freed behind the allocator's back is not uncounted twice.
+    let table = a.alloc_page_table().unwrap();
+    a.dealloc_pages(table.addr(), 1);
+    a.dealloc_page_table(table);
+    assert_eq!(a.page_table_pages(), 1);
+    assert_eq!(a.used_pages(), 0);
+}
+
+#[test]
+#[cfg(feature = "latency")]
+fn test_alloc_latency() {
+    use crate::{CycleCounter, LockedEarlyAllocator};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    struct Ticks(AtomicU64);
+
+    impl CycleCounter for Ticks {
+        fn cycles(&self) -> u64 {
+            self.0.fetch_add(10, Ordering::Relaxed)
+        }
+    }
+
+    static TICKS: Ticks = Ticks(AtomicU64::new(0));
+    let locked = LockedEarlyAllocator::<PAGE_SIZE>::new();
+    locked.lock().init(arena(4 * PAGE_SIZE), 4 * PAGE_SIZE);
+    // The guard taken before the counter was set is not timed.
+    locked.lock().set_cycle_counter(Some(&TICKS));
+    let mut a = locked.lock();
+    a.alloc(Layout::new::<u64>()).unwrap();
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert!(a.alloc_pages(8, PAGE_SIZE).is_err());
+    drop(a);
+
+    let a = locked.lock();
+    let bytes = a.alloc_latency();
+    assert_eq!(
+        (bytes.count, bytes.min, bytes.max, bytes.avg()),
+        (1, 10, 10, 10)
+    );
+    assert_eq!(a.alloc_pages_latency().count, 2);
+    let held = a.lock_hold_latency();
+    assert_eq!(held.count, 1);
+    assert!(held.min > bytes.max);
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_seal_leak_report() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(32, 8).unwrap();
+    #[cfg(feature = "track")]
+    a.set_alloc_tag(Some("pci"));
+    let leaked = a.alloc(layout).unwrap();
+    let freed = a.alloc(layout).unwrap();
+    let page = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    a.dealloc(freed, layout);
+
+    let summary = a.seal(true);
+    assert!(a.is_sealed());
+    assert_eq!(summary.used_pages, 1);
+    assert_eq!(summary.used_bytes, a.used_bytes());
+    #[cfg(feature = "track")]
+    {
+        assert_eq!(summary.tracked, 1);
+        let leaks: Vec<_> = a.outstanding_allocations().collect();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].0, leaked.as_ptr() as usize);
+        assert_eq!((leaks[0].1, leaks[0].2), (32, Some("pci")));
+        assert!(summary
+            .to_string()
+            .ends_with("1 tracked byte allocations (0 untracked)"));
+    }
+    assert!(matches!(a.alloc(layout), Err(AllocError::NoMemory)));
+    assert!(matches!(
+        a.alloc_pages(1, PAGE_SIZE),
+        Err(AllocError::NoMemory)
+    ));
+    a.dealloc_pages(page, 1);
+    a.dealloc(leaked, layout);
+    assert_eq!(a.used_pages(), 0);
+}
+
+#[test]
+#[cfg(feature = "header")]
+fn test_header_dealloc() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let used = a.used_bytes();
+    let small = Layout::from_size_align(24, 8).unwrap();
+    let wide = Layout::from_size_align(100, 256).unwrap();
+    let p = a.alloc_with_header(small).unwrap();
+    let q = a.alloc_with_header(wide).unwrap();
+    assert_eq!(q.as_ptr() as usize % 256, 0);
+    unsafe {
+        assert_eq!(a.header_layout(p), small);
+        assert_eq!(a.header_layout(q), wide);
+        a.dealloc_with_header(q);
+        a.dealloc_with_header(p);
+    }
+    assert_eq!(a.used_bytes(), used);
+}
+
+#[test]
+#[cfg(feature = "track")]
+fn test_iter_live() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    let first = a.alloc(layout).unwrap().as_ptr() as usize;
+    a.set_alloc_tag(Some("net"));
+    let second = a.alloc(layout).unwrap();
+    a.alloc(layout).unwrap();
+    a.dealloc(second, layout);
+
+    let mut live: Vec<_> = a.iter_live().collect();
+    live.sort_by_key(|(_, _, seq)| *seq);
+    assert_eq!(live.len(), 2);
+    assert_eq!(live[0], (first..first + 16, None, 0));
+    assert_eq!((live[1].1, live[1].2), (Some("net"), 2));
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_scratch() {
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    let keep = a.alloc(Layout::new::<u64>()).unwrap();
+    let small = Layout::from_size_align(100, 8).unwrap();
+    let p = a.alloc_scratch(small).unwrap().as_ptr() as usize;
+    let q = a.alloc_scratch(small).unwrap().as_ptr() as usize;
+    assert_eq!(q % 8, 0);
+    assert!(q + 100 <= p);
+    assert_eq!(a.used_pages(), 1);
+
+    // A block larger than what the chunk has left takes a new chunk.
+    let page = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    let big = Layout::from_size_align(PAGE_SIZE, 64).unwrap();
+    let r = a.alloc_scratch(big).unwrap().as_ptr() as usize;
+    assert_eq!(r % 64, 0);
+    assert!(r + PAGE_SIZE <= page);
+    assert_eq!(a.used_pages(), 4);
+
+    assert_eq!(a.release_scratch_all(), 3);
+    assert_eq!(a.release_scratch_all(), 0);
+    assert_eq!(a.used_pages(), 1);
+    a.dealloc_pages(page, 1);
+    a.dealloc(keep, Layout::new::<u64>());
+    assert_eq!(a.used_pages(), 0);
+}
+
+#[test]
+fn test_scratch_arena() {
+    use crate::LockedEarlyAllocator;
+
+    let locked = LockedEarlyAllocator::<PAGE_SIZE>::new();
+    locked.lock().init(arena(4 * PAGE_SIZE), 4 * PAGE_SIZE);
+    let mut scratch = locked.scratch_arena(PAGE_SIZE + 1).unwrap();
+    assert_eq!(scratch.capacity(), 2 * PAGE_SIZE);
+    assert_eq!(locked.lock().used_pages(), 2);
+
+    let header = scratch
+        .alloc(Layout::from_size_align(3, 1).unwrap())
+        .unwrap();
+    let table = scratch.alloc(Layout::new::<u64>()).unwrap();
+    assert_eq!(table.as_ptr() as usize, header.as_ptr() as usize + 8);
+    assert_eq!(scratch.used(), 16);
+    let whole = Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap();
+    assert!(matches!(scratch.alloc(whole), Err(AllocError::NoMemory)));
+    scratch.reset();
+    assert_eq!(scratch.alloc(whole).unwrap(), header);
+    assert_eq!(scratch.remaining(), 0);
+    drop(scratch);
+    assert_eq!(locked.lock().used_pages(), 0);
+}
+
+#[test]
+fn test_cursor_collision() {
+    use crate::{Area, EarlyAllocError};
+
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    a.try_alloc_pages(2, PAGE_SIZE).unwrap();
+    a.try_alloc(Layout::from_size_align(PAGE_SIZE, 8).unwrap())
+        .unwrap();
+    let err = a
+        .try_alloc(Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        EarlyAllocError::Collision {
+            growing: Area::Bytes
+        }
+    ));
+    assert_eq!(err.to_string(), "bytes area ran into the pages area");
+    assert!(matches!(
+        a.try_alloc_pages(2, PAGE_SIZE),
+        Err(EarlyAllocError::Collision {
+            growing: Area::Pages
+        })
+    ));
+    assert!(matches!(
+        a.try_alloc_pages(8, PAGE_SIZE),
+        Err(EarlyAllocError::OutOfMemory { .. })
+    ));
+}
+
+#[test]
+fn test_region_handle() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let extra = arena(4 * PAGE_SIZE);
+    let handle = a.add_memory_handle(extra, 4 * PAGE_SIZE, 0).unwrap();
+    assert_eq!(a.region_of(extra), Some(handle.index()));
+
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let p = a.alloc_in_region(handle, layout).unwrap();
+    let page = a.alloc_pages_in_region(handle, 1, PAGE_SIZE).unwrap();
+    assert_eq!(a.region_of(p.as_ptr() as usize), Some(handle.index()));
+    assert_eq!(a.region_of(page), Some(handle.index()));
+    let stats = a.region_stats(handle).unwrap();
+    assert_eq!((stats.start, stats.count), (extra, 1));
+
+    assert!(matches!(
+        a.remove_region(handle),
+        Err(AllocError::MemoryOverlap)
+    ));
+    a.dealloc(p, layout);
+    a.dealloc_pages(page, 1);
+    assert_eq!(
+        a.remove_region(handle).unwrap(),
+        extra..extra + 4 * PAGE_SIZE
+    );
+    assert_eq!(a.total_bytes(), 4 * PAGE_SIZE);
+    assert!(a.region_of(start).is_some());
+
+    // A stale handle does not name the region reusing its slot.
+    let again = a.add_memory_handle(arena(PAGE_SIZE), PAGE_SIZE, 0).unwrap();
+    assert_eq!(again.index(), handle.index());
+    assert!(matches!(
+        a.region_stats(handle),
+        Err(AllocError::InvalidParam)
+    ));
+}
+
+#[test]
+fn test_region_handle_ops() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let extra = arena(4 * PAGE_SIZE);
+    let bank = a.add_memory_handle(extra, 4 * PAGE_SIZE, 0).unwrap();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    bank.disable(&mut a).unwrap();
+    assert!(!bank.is_enabled(&a));
+    assert!(a.alloc_in_region(bank, layout).is_err());
+    bank.enable(&mut a).unwrap();
+    a.alloc_in_region(bank, layout).unwrap();
+
+    let tail = bank.withdraw_tail(&mut a, PAGE_SIZE).unwrap();
+    assert_eq!(tail, extra + 3 * PAGE_SIZE..extra + 4 * PAGE_SIZE);
+    let stats = bank.stats(&a).unwrap();
+    assert_eq!((stats.end, stats.count), (tail.start, 1));
+}
+
+#[test]
+fn test_const_region() {
+    use crate::LockedEarlyAllocator;
+
+    // Only evaluated: nothing is mapped at this address.
+    static FIXED: LockedEarlyAllocator<PAGE_SIZE> =
+        LockedEarlyAllocator::with_region(0x8000_0000, 16 * PAGE_SIZE);
+    assert_eq!(FIXED.total_bytes(), 16 * PAGE_SIZE);
+    assert_eq!(FIXED.stats_snapshot().total_pages, 16);
+
+    let start = arena(4 * PAGE_SIZE);
+    let mut a = EarlyAllocator::<PAGE_SIZE>::with_region(start, 4 * PAGE_SIZE);
+    assert_eq!(a.region_of(start), Some(0));
+    assert_eq!(a.raw_bytes(), 4 * PAGE_SIZE);
+    let page = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(page, start + 3 * PAGE_SIZE);
+    a.add_memory(arena(PAGE_SIZE), PAGE_SIZE).unwrap();
+    assert_eq!(a.total_bytes(), 5 * PAGE_SIZE);
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn test_early_allocator_macro() {
+    // Stands in for the symbols of a linker script.
+    mod linker_script {
+        core::arch::global_asm!(
+            ".pushsection .bss.test_boot_heap, \"aw\", @nobits",
+            ".balign 4096",
+            ".global test_boot_heap_start",
+            "test_boot_heap_start:",
+            ".skip 16384",
+            ".global test_boot_heap_end",
+            "test_boot_heap_end:",
+            ".popsection",
+        );
+    }
+
+    crate::early_allocator! {
+        static BOOT_HEAP: PAGE_SIZE => test_boot_heap_start..test_boot_heap_end;
+    }
+
+    let bounds = BOOT_HEAP.bounds();
+    assert_eq!(bounds.len(), 4 * PAGE_SIZE);
+    assert_eq!(BOOT_HEAP.total_bytes(), 0);
+    unsafe { BOOT_HEAP.init() }.unwrap();
+    assert_eq!(BOOT_HEAP.total_bytes(), 4 * PAGE_SIZE);
+    assert!(matches!(
+        unsafe { BOOT_HEAP.init() },
+        Err(AllocError::MemoryOverlap)
+    ));
+    let page = BOOT_HEAP.lock().alloc_pages(1, PAGE_SIZE).unwrap();
+    assert!(bounds.contains(&page));
+}
+
+#[test]
+fn test_init_from_linker_symbols() {
+    let start = arena(4 * PAGE_SIZE);
+    let (low, high) = (start as *const u8, (start + 4 * PAGE_SIZE) as *const u8);
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    unsafe {
+        assert!(matches!(
+            a.init_from_linker_symbols(high, low),
+            Err(AllocError::InvalidParam)
+        ));
+        assert!(matches!(
+            a.init_from_linker_symbols(low, low),
+            Err(AllocError::InvalidParam)
+        ));
+        assert!(matches!(
+            a.init_from_linker_symbols(low.wrapping_add(1), high),
+            Err(AllocError::InvalidParam)
+        ));
+        assert!(matches!(
+            a.init_from_linker_symbols(core::ptr::null(), high),
+            Err(AllocError::InvalidParam)
+        ));
+        assert_eq!(a.total_bytes(), 0);
+        a.init_from_linker_symbols(low, high).unwrap();
+    }
+    assert_eq!(a.total_bytes(), 4 * PAGE_SIZE);
+    assert_eq!(a.region_of(start), Some(0));
+}
+
+#[cfg(feature = "registry")]
+#[test]
+fn test_registry() {
+    use crate::{dump_allocators, registered_allocators, LockedEarlyAllocator};
+
+    static DDR0: LockedEarlyAllocator<PAGE_SIZE> = LockedEarlyAllocator::new();
+    static DDR1: LockedEarlyAllocator<0x10000> = LockedEarlyAllocator::new();
+    DDR0.add_memory(arena(4 * PAGE_SIZE), 4 * PAGE_SIZE)
+        .unwrap();
+    DDR0.register("ddr0").unwrap();
+    DDR1.register("ddr1").unwrap();
+    DDR0.lock().alloc_pages(1, PAGE_SIZE).unwrap();
+
+    let names: Vec<_> = registered_allocators().map(|(name, _)| name).collect();
+    assert_eq!(names, ["ddr0", "ddr1"]);
+
+    let mut out = String::new();
+    dump_allocators(&mut out).unwrap();
+    assert_eq!(
+        out,
+        "ddr0: 1 regions, 4096/16384 bytes, 1 pages of 0x1000 used\n\
+         ddr1: 0 regions, 0/0 bytes, 0 pages of 0x10000 used\n"
+    );
+
+    let _guard = DDR1.lock();
+    out.clear();
+    dump_allocators(&mut out).unwrap();
+    assert!(out.ends_with("ddr1: locked\n"));
+}
+
+#[test]
+fn test_uninitialized() {
+    use crate::EarlyAllocError;
+
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    assert!(!a.is_initialized());
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    assert!(matches!(a.alloc(layout), Err(AllocError::NoMemory)));
+    assert!(matches!(
+        a.alloc_pages(1, PAGE_SIZE),
+        Err(AllocError::NoMemory)
+    ));
+    assert!(matches!(
+        a.try_alloc(layout),
+        Err(EarlyAllocError::Uninitialized)
+    ));
+    assert!(matches!(
+        a.try_alloc_pages(1, PAGE_SIZE),
+        Err(EarlyAllocError::Uninitialized)
+    ));
+
+    a.add_memory(arena(4 * PAGE_SIZE), 4 * PAGE_SIZE).unwrap();
+    assert!(a.is_initialized());
+    a.try_alloc(layout).unwrap();
+    a.try_alloc_pages(1, PAGE_SIZE).unwrap();
+
+    // Invalid memory leaves a fresh `init` without any.
+    a.init(0, 0);
+    assert!(!a.is_initialized());
+}
+
+#[cfg(feature = "conformance")]
+#[test]
+fn test_conformance() {
+    crate::conformance::run_all(|| allocator(64 * PAGE_SIZE).0);
+}
+
+#[test]
+fn test_error_display() {
+    use crate::EarlyAllocError;
+
+    let (mut a, _) = allocator(PAGE_SIZE);
+    let layout = Layout::from_size_align(2 * PAGE_SIZE, 64).unwrap();
+    let err = a.try_alloc(layout).unwrap_err();
+    assert!(matches!(
+        err,
+        EarlyAllocError::OutOfMemory { size, align: 64 } if size == 2 * PAGE_SIZE
+    ));
+    assert_eq!(
+        err.to_string(),
+        "out of memory for 8192 bytes aligned to 0x40"
+    );
+    let err: Box<dyn std::error::Error> = Box::new(err);
+    assert!(err.source().is_none());
+    assert_eq!(
+        EarlyAllocError::Corrupted {
+            region: 1,
+            addr: 0x8000
+        }
+        .to_string(),
+        "memory corrupted at 0x8000 in region 1"
+    );
+    assert!(a.try_alloc(Layout::new::<u64>()).is_ok());
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_carve_guest_memory() {
+    const ALIGN: usize = 16 * PAGE_SIZE;
+
+    let size = 64 * PAGE_SIZE;
+    let start = arena(size + ALIGN);
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    a.init(start, size + ALIGN);
+
+    let guest = a.carve_guest_memory(32 * PAGE_SIZE - 1, ALIGN).unwrap();
+    assert_eq!(guest.start() % ALIGN, 0);
+    assert_eq!(guest.size(), 32 * PAGE_SIZE);
+    assert_eq!(guest.region(), 0);
+    assert_eq!(a.used_pages(), 32);
+
+    let p = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert!(!guest.range().contains(&p));
+    let used = a.used_pages();
+    assert!(a.carve_guest_memory(size, ALIGN).is_err());
+    assert_eq!(a.used_pages(), used);
+    assert!(a.carve_guest_memory(0, ALIGN).is_err());
+}
+
+#[test]
+fn test_can_allocate() {
+    let (mut a, _) = allocator(PAGE_SIZE);
+    let second = arena(4 * PAGE_SIZE);
+    a.add_memory(second, 4 * PAGE_SIZE).unwrap();
+
+    let big = Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap();
+    let used = a.used_bytes();
+    assert_eq!(a.can_allocate(big), Some(1));
+    assert_eq!(a.used_bytes(), used);
+    assert_eq!(a.can_allocate(Layout::new::<u64>()), Some(0));
+    assert_eq!(
+        a.can_allocate(Layout::from_size_align(8 * PAGE_SIZE, 8).unwrap()),
+        None
+    );
+
+    let p = a.alloc(big).unwrap();
+    assert_eq!(a.region_of(p.as_ptr() as usize), Some(1));
+    let small = Layout::from_size_align(64, 64).unwrap();
+    let q = a.alloc(small).unwrap();
+    a.dealloc(q, small);
+    assert_eq!(a.can_allocate(small), a.region_of(q.as_ptr() as usize));
+
+    // Once sealed, not even the recycled block can be had.
+    a.seal(false);
+    assert_eq!(a.can_allocate(small), None);
+    assert!(a.alloc(small).is_err());
+}
+
+#[test]
+fn test_try_reserve() {
+    let (mut a, _) = allocator(PAGE_SIZE);
+    let second = arena(8 * PAGE_SIZE);
+    a.add_memory(second, 8 * PAGE_SIZE).unwrap();
+
+    {
+        let mut res = a.try_reserve(2 * PAGE_SIZE).unwrap();
+        assert_eq!(res.region(), 1);
+        let p = res.alloc(Layout::from_size_align(16, 8).unwrap()).unwrap();
+        assert_eq!(p.as_ptr() as usize, second);
+        res.alloc(Layout::from_size_align(8, 64).unwrap()).unwrap();
+        assert_eq!(res.remaining(), 2 * PAGE_SIZE - 72);
+
+        // The region has more room, but the reservation does not.
+        let over = Layout::from_size_align(2 * PAGE_SIZE - 71, 1).unwrap();
+        assert!(matches!(res.alloc(over), Err(AllocError::NoMemory)));
+        assert!(matches!(
+            res.alloc_pages(2, PAGE_SIZE),
+            Err(AllocError::NoMemory)
+        ));
+        assert_eq!(res.remaining(), 2 * PAGE_SIZE - 72);
+    }
+    {
+        let mut res = a.try_reserve_pages(3).unwrap();
+        assert!(matches!(
+            res.alloc_pages(4, PAGE_SIZE),
+            Err(AllocError::NoMemory)
+        ));
+        let pos = res.alloc_pages(3, PAGE_SIZE).unwrap();
+        assert_eq!(pos, second + 5 * PAGE_SIZE);
+        assert_eq!(res.remaining(), 0);
+        assert!(matches!(
+            res.alloc(Layout::new::<u8>()),
+            Err(AllocError::NoMemory)
+        ));
+    }
+    assert_eq!(a.used_pages(), 3);
+    assert!(a.try_reserve_pages(8).is_err());
+    assert!(a.try_reserve(16 * PAGE_SIZE).is_err());
+}
+
+#[test]
+fn test_reservation_asks_rebalance_once() {
+    use crate::Rebalance;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static ASKED: AtomicUsize = AtomicUsize::new(0);
+    let (mut a, start) = allocator(8 * PAGE_SIZE);
+    a.set_boundary(0, Some(start + PAGE_SIZE)).unwrap();
+    a.set_rebalance(Rebalance::Custom(|_, _, _| {
+        ASKED.fetch_add(1, Ordering::Relaxed);
+        true
+    }));
+
+    // Each allocation crosses the boundary, which asks the callback once.
+    {
+        let mut res = a.try_reserve(4 * PAGE_SIZE).unwrap();
+        let asked = ASKED.load(Ordering::Relaxed);
+        res.alloc(Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap())
+            .unwrap();
+        assert_eq!(ASKED.load(Ordering::Relaxed), asked + 1);
+    }
+    a.set_boundary(0, Some(start + 7 * PAGE_SIZE)).unwrap();
+    {
+        let mut res = a.try_reserve_pages(2).unwrap();
+        let asked = ASKED.load(Ordering::Relaxed);
+        res.alloc_pages(2, PAGE_SIZE).unwrap();
+        assert_eq!(ASKED.load(Ordering::Relaxed), asked + 1);
+    }
+}
+
+#[test]
+fn test_alloc_many() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(24, 16).unwrap();
+    let blocks: Vec<_> = a.alloc_many(layout, 4).unwrap().collect();
+    assert_eq!(blocks.len(), 4);
+    for (i, p) in blocks.iter().enumerate() {
+        assert_eq!(p.as_ptr() as usize, start + i * 32);
+    }
+    assert_eq!(a.used_bytes(), 4 * 32);
+
+    let next = a.alloc(Layout::new::<u8>()).unwrap();
+    assert_eq!(next.as_ptr() as usize, start + 4 * 32);
+    assert!(a.alloc_many(layout, 0).is_err());
+    assert!(a.alloc_many(layout, usize::MAX).is_err());
+}
+
+#[test]
+fn test_alloc_pages_vectored() {
+    let (mut a, start) = allocator(2 * PAGE_SIZE);
+    let second = arena(3 * PAGE_SIZE);
+    a.add_memory(second, 3 * PAGE_SIZE).unwrap();
+
+    assert!(a.alloc_pages(4, PAGE_SIZE).is_err());
+    assert!(a.alloc_pages_vectored(4, 1).is_err());
+    assert_eq!(a.used_pages(), 0);
+
+    let extents = a.alloc_pages_vectored(4, 2).unwrap();
+    let runs: Vec<_> = extents.iter().collect();
+    assert_eq!(runs, [(start, 2), (second + PAGE_SIZE, 2)]);
+    assert_eq!(a.used_pages(), 4);
+    assert!(a.alloc_pages_vectored(2, crate::MAX_REGIONS).is_err());
+    assert_eq!(a.used_pages(), 4);
+}
+
+#[test]
+fn test_page_align_validation() {
+    use crate::MAX_PAGE_ALIGN;
+
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    for align in [0, 12, PAGE_SIZE / 2, 3 * PAGE_SIZE, 2 * MAX_PAGE_ALIGN] {
+        assert!(matches!(
+            a.alloc_pages(1, align),
+            Err(AllocError::InvalidParam)
+        ));
+        assert_eq!(a.contiguous_pages_available(1, align), None);
+    }
+    assert!(a.alloc_pages(1, 2 * PAGE_SIZE).is_ok());
+
+    type A = EarlyAllocator<PAGE_SIZE>;
+    assert_eq!(A::page_align_from_log2(13).unwrap(), 2 * PAGE_SIZE);
+    assert!(A::page_align_from_log2(11).is_err());
+    assert!(A::page_align_from_log2(usize::BITS).is_err());
+    assert_eq!(A::page_align_from_log2(30).unwrap(), MAX_PAGE_ALIGN);
+}
+
+#[test]
+fn test_spill_policy() {
+    use crate::SpillPolicy;
+
+    let (mut a, _) = allocator(PAGE_SIZE);
+    a.add_memory_flags(arena(PAGE_SIZE), PAGE_SIZE, 1).unwrap();
+    a.add_memory(arena(4 * PAGE_SIZE), 4 * PAGE_SIZE).unwrap();
+    let big = Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap();
+
+    a.set_spill_policy(SpillPolicy::Never);
+    assert!(a.alloc(big).is_err());
+    a.set_spill_policy(SpillPolicy::SameZone);
+    let p = a.alloc(big).unwrap();
+    assert_eq!(a.region_of(p.as_ptr() as usize), Some(2));
+
+    a.set_preferred_region(1).unwrap();
+    assert!(a.alloc(big).is_err());
+    assert!(a.alloc_pages(1, PAGE_SIZE).is_ok());
+    assert!(a.alloc_pages(1, PAGE_SIZE).is_err());
+    a.set_spill_policy(SpillPolicy::Any);
+    assert!(a.alloc_pages(1, PAGE_SIZE).is_ok());
+    assert!(a.set_preferred_region(5).is_err());
+}
+
+#[test]
+fn test_disable_region() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let second = arena(4 * PAGE_SIZE);
+    a.add_memory(second, 4 * PAGE_SIZE).unwrap();
+    let p = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(p, start + 3 * PAGE_SIZE);
+
+    a.disable_region(0).unwrap();
+    assert!(!a.is_region_enabled(0));
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), second + 3 * PAGE_SIZE);
+    assert_eq!(a.contiguous_pages_available(4, PAGE_SIZE), None);
+    assert_eq!(a.total_pages(), 8);
+    a.dealloc_pages(p, 1);
+    assert_eq!(a.used_pages(), 1);
+
+    a.enable_region(0).unwrap();
+    assert!(a.is_region_enabled(0));
+    assert_eq!(a.contiguous_pages_available(4, PAGE_SIZE), Some(0));
+    assert!(a.disable_region(2).is_err());
+
+    // A block freed in a disabled region is not recycled from it.
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let freed = a.alloc(layout).unwrap();
+    a.alloc(layout).unwrap();
+    let idx = a.region_of(freed.as_ptr() as usize).unwrap();
+    a.dealloc(freed, layout);
+    a.disable_region(idx).unwrap();
+    assert_eq!(a.can_allocate(layout), Some(1 - idx));
+    let p = a.alloc(layout).unwrap();
+    assert_ne!(p, freed);
+    assert_eq!(a.region_of(p.as_ptr() as usize), Some(1 - idx));
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_size_class_stats() {
+    use crate::SizeClassStats;
+
+    assert_eq!(SizeClassStats::class_of(1), 0);
+    assert_eq!(SizeClassStats::class_of(16), 4);
+    assert_eq!(SizeClassStats::class_of(17), 5);
+    assert_eq!(SizeClassStats::class_of(usize::MAX), usize::BITS as usize);
+
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    for _ in 0..3 {
+        a.alloc(Layout::from_size_align(12, 4).unwrap()).unwrap();
+    }
+    a.alloc(Layout::from_size_align(PAGE_SIZE, 8).unwrap())
+        .unwrap();
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    let classes: Vec<_> = a.size_class_stats().iter().collect();
+    assert_eq!(classes, [(4, 3, 36), (12, 1, PAGE_SIZE)]);
+
+    a.init(start, 4 * PAGE_SIZE);
+    assert_eq!(a.size_class_stats().iter().count(), 0);
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_oom_dump() {
+    use std::sync::Mutex;
+    static DUMP: Mutex<String> = Mutex::new(String::new());
+
+    let (mut a, start) = allocator(2 * PAGE_SIZE);
+    a.set_oom_dump(Some(|report| {
+        *DUMP.lock().unwrap() = report.to_string();
+    }));
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert!(DUMP.lock().unwrap().is_empty());
+
+    assert!(a
+        .alloc(Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap())
+        .is_err());
+    let dump = DUMP.lock().unwrap().clone();
+    assert!(dump.contains("out of memory for 8192 bytes aligned to 0x8"));
+    assert!(dump.contains(&format!(
+        "region  0: [{:#x}, {:#x})",
+        start,
+        start + 2 * PAGE_SIZE
+    )));
+    assert!(dump.contains("pages: 1 used, 1 available"));
+
+    assert!(a.alloc_pages(2, PAGE_SIZE).is_err());
+    assert!(DUMP
+        .lock()
+        .unwrap()
+        .contains("out of memory for 2 pages aligned to 0x1000"));
+}
+
+#[test]
+#[cfg(feature = "no-stats")]
+fn test_no_stats() {
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    a.alloc(Layout::from_size_align(PAGE_SIZE, 8).unwrap())
+        .unwrap();
+    let p = a.alloc_pages(2, PAGE_SIZE).unwrap();
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(a.used_pages(), 3);
+    a.dealloc_pages(p, 2);
+    assert_eq!(a.used_pages(), 1);
+    assert_eq!(a.size_class_stats().iter().count(), 0);
+    assert_eq!(a.saved_padding(), 0);
+
+    // Neither watchpoints nor exec frees without an exec region can be
+    // checked without the counters.
+    assert!(matches!(a.watch(0..1), Err(AllocError::InvalidParam)));
+    let exec = a.alloc_pages_exec(1, PAGE_SIZE).unwrap();
+    a.dealloc_pages_exec(exec, 1);
+    assert_eq!(a.used_pages(), 2);
+}
+
+/// Throws out-of-range arguments at the public API: every call must fail
+/// cleanly or do nothing, never panic (overflow checks are on in tests).
+#[test]
+fn test_hostile_inputs_do_not_panic() {
+    const BIG: [usize; 4] = [usize::MAX, usize::MAX / 2 + 1, usize::MAX - PAGE_SIZE, 0];
+
+    let (mut a, start) = allocator(8 * PAGE_SIZE);
+    let _ = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    for &n in &BIG {
+        let _ = a.alloc_pages(n, PAGE_SIZE);
+        let _ = a.alloc_pages(1, n);
+        let _ = a.alloc_pages_exec(n, PAGE_SIZE);
+        let _ = a.alloc_pages_dma(n, u64::MAX);
+        let _ = a.alloc_pages_vectored(n, n);
+        let _ = a.alloc_pages_id(n, PAGE_SIZE);
+        let _ = a.alloc_stack(n, n);
+        let _ = a.alloc_stack(1, n);
+        let _ = a.try_alloc_pages(n, PAGE_SIZE);
+        let _ = a.try_reserve(n);
+        let _ = a.try_reserve_pages(n);
+        let _ = a.contiguous_pages_available(n, PAGE_SIZE);
+        let _ = a.carve(n);
+        let _ = a.carve_guest_memory(n, n);
+        let _ = a.alloc_cacheline(n);
+        let _ = a.reclaim(n);
+        let _ = a.withdraw_tail(0, n);
+        let _ = a.withdraw_tail(n, 1);
+        let _ = a.split_region(0, n);
+        let _ = a.set_boundary(0, Some(n));
+        let _ = a.set_exec_region(Some(n));
+        let _ = a.set_region_priority(n, 1);
+        let _ = a.disable_region(n);
+        let _ = a.set_preferred_region(n);
+        let _ = a.region_fragmentation(n);
+        let _ = a.get_page(n);
+        let _ = a.put_page(n);
+        let _ = a.page_refcount(n);
+        let _ = a.region_of(n);
+        let _ = a.set_min_align(n);
+        let _ = a.add_memory(n, n);
+        let _ = a.add_memory(start, n);
+        let _ = a.exclude(n..n);
+        let _ = a.exclude(0..n);
+        let _ = EarlyAllocator::<PAGE_SIZE>::page_align_from_log2(n as u32);
+        let _ = a.watch(n..n);
+        a.dealloc_pages(n, n);
+        a.dealloc_pages(start, n);
+        a.dealloc_pages(n, 1);
+        a.dealloc_pages_exec(n, n);
+        a.set_quarantine_depth(n);
+        for align in [1, 8, PAGE_SIZE] {
+            if let Ok(layout) = Layout::from_size_align(n / 4, align) {
+                let _ = a.alloc(layout);
+                let _ = a.can_allocate(layout);
+                let _ = a.alloc_dma(layout, 0xFFFF);
+                let _ = a.alloc_from_top(layout);
+                let _ = a.alloc_excess(layout);
+                let _ = a.alloc_many(layout, n);
+            }
+        }
+    }
+    a.unwatch();
+    let _ = a.free_ranges().count();
+    let _ = a.utilization().count();
+    let _ = a.fragmentation();
+    assert!(a.total_bytes() >= 8 * PAGE_SIZE);
+
+    for (start, size) in [(0, 0), (usize::MAX, usize::MAX), (usize::MAX - 1, 2)] {
+        a.init(start, size);
+        assert!(a.alloc(Layout::new::<u64>()).is_err());
+    }
+
+    // Page frees of memory the page counters never saw.
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    let page = Layout::from_size_align(PAGE_SIZE, PAGE_SIZE).unwrap();
+    let top = a.alloc_from_top(page).unwrap().as_ptr() as usize;
+    a.dealloc_pages(top, 1);
+    let run = a.alloc_pages(2, PAGE_SIZE).unwrap();
+    a.dealloc_pages_exec(run, 2);
+    a.dealloc_pages(run, 1);
+    a.dealloc_pages(run + PAGE_SIZE, 1);
+    if let Ok(table) = a.alloc_page_table() {
+        a.dealloc_pages(table.addr(), 1);
+        a.dealloc_page_table(table);
+    }
+}
+
+#[test]
+#[cfg(feature = "trace")]
+fn test_trace_replay() {
+    use crate::{TraceEntry, TraceOp};
+
+    let buf = Box::leak(Box::new([TraceEntry::EMPTY; 8]));
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    assert!(a.start_trace(buf).is_none());
+    let layout = Layout::from_size_align(24, 8).unwrap();
+    let p = a.alloc(layout).unwrap();
+    let pages = a.alloc_pages(2, PAGE_SIZE).unwrap();
+    assert!(a.alloc_pages(8, PAGE_SIZE).is_err());
+    a.dealloc(p, layout);
+    a.dealloc_pages(pages, 2);
+    a.alloc(Layout::from_size_align(64, 64).unwrap()).unwrap();
+
+    let (entries, dropped) = a.trace();
+    assert_eq!(dropped, 0);
+    assert_eq!(entries.len(), 6);
+    assert_eq!(entries[1].op, TraceOp::AllocPages);
+    assert_eq!(entries[1].offset, Some(pages - start));
+    assert_eq!(entries[2].offset, None);
+    let recorded = entries.to_vec();
+
+    let (mut b, _) = allocator(4 * PAGE_SIZE);
+    assert_eq!(b.replay(&recorded), Ok(()));
+    let (mut c, _) = allocator(PAGE_SIZE);
+    assert_eq!(c.replay(&recorded), Err(1));
+    assert_eq!(a.stop_trace().map(|buf| buf.len()), Some(8));
+}
+
+#[test]
+#[cfg(feature = "trace")]
+fn test_trace_clock() {
+    use crate::TraceEntry;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NOW: AtomicU64 = AtomicU64::new(100);
+    let buf = Box::leak(Box::new([TraceEntry::EMPTY; 4]));
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    a.start_trace(buf);
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    a.set_trace_clock(Some(|| NOW.fetch_add(1, Ordering::Relaxed)));
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+
+    let stamps: Vec<_> = a.trace().0.iter().map(|e| e.timestamp).collect();
+    assert_eq!(stamps, [0, 100, 101]);
+}
+
+#[test]
+#[cfg(feature = "canary")]
+#[should_panic(expected = "overwritten")]
+fn test_gap_canary() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    let p = a.alloc(layout).unwrap();
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    a.alloc(layout).unwrap();
+    // Run one byte past the second block, into the canary above it.
+    unsafe { p.as_ptr().add(2 * 16).write(0) };
+    let _ = a.alloc(layout);
+}
+
+#[test]
+#[cfg(feature = "sentinel")]
+fn test_end_sentinel() {
+    use crate::EarlyAllocError;
+
+    let (a, start) = allocator(4 * PAGE_SIZE);
+    assert!(a.verify().is_ok());
+    let last = start + 4 * PAGE_SIZE - 8;
+    unsafe { (last as *mut u64).write(0) };
+    assert!(matches!(
+        a.verify(),
+        Err(EarlyAllocError::Corrupted { region: 0, addr }) if addr == last
+    ));
+    // Once the top page is handed out, its owner may write the last word.
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), start + 3 * PAGE_SIZE);
+    unsafe { ((start + 4 * PAGE_SIZE - 8) as *mut u64).write(0) };
+    assert!(a.verify().is_ok());
+}
+
+#[test]
+fn test_misaligned_region() {
+    let base = arena(8 * PAGE_SIZE);
+    let start = base + 0x10;
+    let end = base + 4 * PAGE_SIZE + 0x30;
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    a.init(start, end - start);
+    assert_eq!(a.total_pages(), 3);
+    assert_eq!(a.available_pages(), 3);
+
+    let mut runs = [0; 3];
+    for (i, run) in runs.iter_mut().enumerate() {
+        *run = a.alloc_pages(1, PAGE_SIZE).unwrap();
+        assert_eq!(*run, base + (3 - i) * PAGE_SIZE);
+    }
+    assert!(a.alloc_pages(1, PAGE_SIZE).is_err());
+    // The sliver below the first whole page still serves bytes.
+    let layout = Layout::from_size_align(PAGE_SIZE - 0x10, 8).unwrap();
+    let p = a.alloc(layout).unwrap();
+    assert_eq!(p.as_ptr() as usize, start);
+    a.dealloc(p, layout);
+
+    for run in runs.into_iter().rev() {
+        a.dealloc_pages(run, 1);
+    }
+    assert_eq!(a.used_bytes(), 0);
+    assert_eq!(a.available_bytes(), end - start);
+    // Without page runs the bytes area reaches the sliver above the last page.
+    let all = Layout::from_size_align(end - start, 8).unwrap();
+    assert_eq!(a.alloc(all).unwrap().as_ptr() as usize, start);
+}
+
+#[test]
+fn test_add_memory_aligned() {
+    let base = arena(8 * PAGE_SIZE);
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    let discarded = a
+        .add_memory_aligned(base + 0x123, 4 * PAGE_SIZE, PAGE_SIZE)
+        .unwrap();
+    assert_eq!(discarded, PAGE_SIZE);
+    assert!(!a.owns(base + PAGE_SIZE - 1));
+    assert!(a.owns(base + PAGE_SIZE));
+    assert!(!a.owns(base + 4 * PAGE_SIZE));
+    assert_eq!(
+        a.add_memory_aligned(base + 4 * PAGE_SIZE, PAGE_SIZE, PAGE_SIZE)
+            .unwrap(),
+        0
+    );
+    assert!(matches!(
+        a.add_memory_aligned(base + 6 * PAGE_SIZE + 1, PAGE_SIZE, PAGE_SIZE),
+        Err(AllocError::InvalidParam)
+    ));
+    assert!(matches!(
+        a.add_memory_aligned(base + 6 * PAGE_SIZE, PAGE_SIZE, 3),
+        Err(AllocError::InvalidParam)
+    ));
+}
+
+#[test]
+fn test_raw_and_usable_bytes() {
+    let base = arena(8 * PAGE_SIZE);
+    let mut a = EarlyAllocator::<PAGE_SIZE>::new();
+    a.exclude(base + PAGE_SIZE..base + 2 * PAGE_SIZE).unwrap();
+    a.init(base, 4 * PAGE_SIZE);
+    a.add_memory_aligned(base + 4 * PAGE_SIZE + 0x10, 2 * PAGE_SIZE, PAGE_SIZE)
+        .unwrap();
+    assert_eq!(a.raw_bytes(), 6 * PAGE_SIZE);
+    assert_eq!(a.usable_bytes(), 4 * PAGE_SIZE);
+
+    let idx = a.region_of(base + 3 * PAGE_SIZE).unwrap();
+    a.withdraw_tail(idx, PAGE_SIZE).unwrap();
+    assert_eq!(a.raw_bytes(), 5 * PAGE_SIZE);
+    assert_eq!(a.usable_bytes(), 3 * PAGE_SIZE);
+}
+
+#[test]
+fn test_pressure() {
+    use crate::{Pressure, PressureThresholds};
+
+    let (mut a, _) = allocator(16 * PAGE_SIZE);
+    assert_eq!(a.pressure(), Pressure::None);
+    a.alloc_pages(12, PAGE_SIZE).unwrap();
+    assert_eq!(a.pressure(), Pressure::None);
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    assert_eq!(a.pressure(), Pressure::Moderate);
+    a.alloc_pages(3, PAGE_SIZE).unwrap();
+    assert_eq!(a.pressure(), Pressure::Critical);
+
+    let (mut a, _) = allocator(16 * PAGE_SIZE);
+    a.set_pressure_thresholds(Some(PressureThresholds {
+        moderate_bytes: 0,
+        critical_bytes: 0,
+        moderate_pages: 8,
+        critical_pages: 2,
+    }));
+    a.alloc_pages(9, PAGE_SIZE).unwrap();
+    assert_eq!(a.pressure(), Pressure::Moderate);
+    a.set_pressure_thresholds(None);
+    assert_eq!(a.pressure(), Pressure::None);
+}
+
+#[test]
+fn test_emergency_reserve() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    a.set_emergency_reserve(0x100).unwrap();
+    assert_eq!(a.emergency_available(), PAGE_SIZE);
+    let big = Layout::from_size_align(3 * PAGE_SIZE, 8).unwrap();
+    a.alloc(big).unwrap();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    assert!(a.alloc(layout).is_err());
+
+    let p = a.alloc_critical(layout).unwrap().as_ptr() as usize;
+    assert_eq!(p, start + 3 * PAGE_SIZE);
+    assert_eq!(a.emergency_available(), PAGE_SIZE - 64);
+    assert!(matches!(
+        a.set_emergency_reserve(0),
+        Err(AllocError::MemoryOverlap)
+    ));
+    let huge = Layout::from_size_align(2 * PAGE_SIZE, 8).unwrap();
+    assert!(matches!(a.alloc_critical(huge), Err(AllocError::NoMemory)));
+
+    // Freeing the reserve pages behind its back drops the reserve.
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    a.set_emergency_reserve(PAGE_SIZE).unwrap();
+    a.dealloc_pages(start + 3 * PAGE_SIZE, 1);
+    assert_eq!(a.emergency_available(), 0);
+    assert_eq!(a.alloc_pages(1, PAGE_SIZE).unwrap(), start + 3 * PAGE_SIZE);
+    while a.alloc(layout).is_ok() {}
+    assert!(a.alloc_critical(layout).is_err());
+}
+
+#[test]
+fn test_alloc_flags() {
+    use crate::AllocFlags;
+
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let p = a.alloc(layout).unwrap();
+    unsafe { p.as_ptr().write_bytes(0xaa, 64) };
+    a.dealloc(p, layout);
+
+    let p = a.alloc_flags(layout, AllocFlags::ZEROED).unwrap();
+    assert_eq!(p.as_ptr() as usize, start);
+    assert!(unsafe { core::slice::from_raw_parts(p.as_ptr(), 64) }
+        .iter()
+        .all(|&b| b == 0));
+    let top = a.alloc_flags(layout, AllocFlags::HIGH).unwrap();
+    assert_eq!(top.as_ptr() as usize, start + 4 * PAGE_SIZE - 64);
+    assert!(matches!(
+        a.alloc_flags(layout, AllocFlags::DMA | AllocFlags::HIGH),
+        Err(AllocError::InvalidParam)
+    ));
+    assert_eq!(AllocFlags::from_bits(1 << 31), None);
+
+    a.set_emergency_reserve(PAGE_SIZE).unwrap();
+    let rest = Layout::from_size_align(a.available_bytes(), 1).unwrap();
+    a.alloc(rest).unwrap();
+    assert!(a.alloc_flags(layout, AllocFlags::NONE).is_err());
+    // A reserve out of reach of 32-bit DMA is left alone.
+    if start as u64 > crate::DMA32_MASK {
+        let reserve = a.emergency_available();
+        assert!(matches!(
+            a.alloc_flags(layout, AllocFlags::DMA | AllocFlags::CRITICAL),
+            Err(AllocError::NoMemory)
+        ));
+        assert_eq!(a.emergency_available(), reserve);
+    }
+    let flags = AllocFlags::CRITICAL | AllocFlags::ZEROED;
+    assert!(a.alloc_flags(layout, flags).is_ok());
+}
+
+#[test]
+#[cfg(not(feature = "no-stats"))]
+fn test_cpu_stats() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static CPU: AtomicUsize = AtomicUsize::new(0);
+    let (mut a, _) = allocator(8 * PAGE_SIZE);
+    a.set_cpu_id(Some(|| CPU.load(Ordering::Relaxed)));
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    a.alloc(layout).unwrap();
+    CPU.store(3, Ordering::Relaxed);
+    a.alloc(layout).unwrap();
+    a.alloc_pages(2, PAGE_SIZE).unwrap();
+    CPU.store(crate::MAX_CPUS, Ordering::Relaxed);
+    a.alloc(layout).unwrap();
+
+    assert_eq!(a.cpu_stats(0).unwrap().allocs, 1);
+    let cpu3 = a.cpu_stats(3).unwrap();
+    assert_eq!(
+        (cpu3.allocs, cpu3.bytes, cpu3.page_allocs, cpu3.pages),
+        (1, 16, 1, 2)
+    );
+    assert_eq!(a.cpu_stats(crate::MAX_CPUS), None);
+    let total = a.cpu_stats_total();
+    assert_eq!((total.allocs, total.bytes, total.pages), (2, 32, 2));
+}
+
+#[test]
+#[cfg(feature = "track")]
+fn test_allocation_index() {
+    let (mut a, _) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(16, 8).unwrap();
+    let p0 = a.alloc(layout).unwrap();
+    let p1 = a.alloc(layout).unwrap();
+    a.dealloc(p0, layout);
+    assert_eq!(a.allocation_index(p0), None);
+    // The recycled block gets a fresh index.
+    let p2 = a.alloc(layout).unwrap();
+    assert_eq!(p2, p0);
+    assert_eq!(a.allocation_index(p1), Some(1));
+    assert_eq!(a.allocation_index(p2), Some(2));
+    let mut seqs: Vec<u64> = a.live_allocations().map(|r| r.seq).collect();
+    seqs.sort_unstable();
+    assert_eq!(seqs, [1, 2]);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "overlaps the free gap")]
+fn test_granted_block_checks() {
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    let layout = Layout::from_size_align(64, 64).unwrap();
+    let p = a.alloc(layout).unwrap().as_ptr() as usize;
+    a.debug_check_granted(p, 64, 64);
+    let page = a.alloc_pages(1, PAGE_SIZE).unwrap();
+    a.debug_check_granted(page, PAGE_SIZE, PAGE_SIZE);
+    // A block handed out from the middle of the gap means broken cursor math.
+    a.debug_check_granted(start + PAGE_SIZE, 64, 64);
+}
+
+#[test]
+fn test_axalloc_byte_allocator() {
+    use crate::AxallocByteAllocator;
+
+    fn assert_send<T: Send>() {}
+    assert_send::<AxallocByteAllocator<PAGE_SIZE>>();
+
+    // Mimics the expansion loop of axalloc's `GlobalAllocator::alloc`.
+    let (mut palloc, _) = allocator(64 * PAGE_SIZE);
+    let mut balloc = AxallocByteAllocator::<PAGE_SIZE>::new();
+    balloc.init(palloc.alloc_pages(1, PAGE_SIZE).unwrap(), PAGE_SIZE);
+    let layout = Layout::from_size_align(3 * PAGE_SIZE, 8).unwrap();
+    let mut expansions = 0;
+    let ptr = loop {
+        match balloc.alloc(layout) {
+            Ok(ptr) => break ptr,
+            Err(err) => {
+                assert!(matches!(err, AllocError::NoMemory));
+                let size = balloc
+                    .total_bytes()
+                    .max(layout.size())
+                    .next_power_of_two()
+                    .max(PAGE_SIZE);
+                let chunk = palloc.alloc_pages(size / PAGE_SIZE, PAGE_SIZE).unwrap();
+                balloc.add_memory(chunk, size).unwrap();
+                expansions += 1;
+            }
+        }
+    };
+    assert_eq!(expansions, 1);
+    assert_eq!(balloc.used_bytes(), layout.size());
+    balloc.dealloc(ptr, layout);
+    assert_eq!(balloc.inner().used_bytes(), 0);
+}
+
+#[test]
+fn test_erased_allocator() {
+    use crate::ErasedEarlyAllocator;
+
+    let (mut small, _) = allocator(4 * PAGE_SIZE);
+    let mut big = EarlyAllocator::<0x4000>::new();
+    let start = arena(8 * 0x4000);
+    big.init(start, 8 * 0x4000);
+    let mut erased = [
+        ErasedEarlyAllocator::new(&mut small),
+        ErasedEarlyAllocator::new(&mut big),
+    ];
+    assert_eq!(erased[1].page_size(), 0x4000);
+    let layout = Layout::from_size_align(32, 8).unwrap();
+    for alloc in &mut erased {
+        let dyn_alloc: &mut dyn ByteAllocator = alloc;
+        let p = dyn_alloc.alloc(layout).unwrap();
+        assert_eq!(dyn_alloc.used_bytes(), 32);
+        dyn_alloc.dealloc(p, layout);
+    }
+    let page = erased[1].alloc_pages(1, 0x4000).unwrap();
+    assert_eq!(page % 0x4000, 0);
+    assert_eq!(erased[1].used_pages(), 1);
+    assert_eq!(erased[0].total_pages(), 4);
+}
+
+#[test]
+fn test_raw_state() {
+    use crate::{RawRegion, RawState, MAX_REGIONS, RAW_STATE_MAGIC};
+
+    let (mut a, start) = allocator(4 * PAGE_SIZE);
+    a.alloc(Layout::from_size_align(24, 8).unwrap()).unwrap();
+    a.alloc_pages(1, PAGE_SIZE).unwrap();
+    let raw = a.as_raw_state();
+    assert_eq!(raw.magic, RAW_STATE_MAGIC);
+    assert_eq!((raw.page_size, raw.max_regions), (PAGE_SIZE, MAX_REGIONS));
+    assert_eq!(raw.bitmap, 1);
+    let r = raw.regions[0];
+    assert_eq!((r.start, r.end), (start, start + 4 * PAGE_SIZE));
+    assert_eq!(
+        (r.b_pos, r.p_pos, r.count),
+        (start + 24, start + 3 * PAGE_SIZE, 1)
+    );
+    assert_eq!(raw.regions[1], RawRegion::default());
+    assert_eq!(raw.used_pages, 1);
+    // The layout decoders rely on.
+    let word = core::mem::size_of::<usize>();
+    assert_eq!(core::mem::offset_of!(RawState, page_size), 8);
+    assert_eq!(core::mem::offset_of!(RawState, regions), 8 + 6 * word);
+    assert_eq!(core::mem::size_of::<RawRegion>(), 6 * word + 8);
+}
+
+#[test]
+#[cfg(all(feature = "ffi", not(loom)))]
+fn test_ffi() {
+    use crate::{early_add_memory, early_alloc, early_alloc_pages, early_free, ffi_allocator};
+
+    let start = arena(8 * PAGE_SIZE);
+    assert_eq!(unsafe { early_add_memory(start, 8 * PAGE_SIZE) }, 0);
+    assert_eq!(unsafe { early_add_memory(start, PAGE_SIZE) }, -1);
+    let p = early_alloc(40, 8);
+    assert_eq!(p as usize, start);
+    assert!(early_alloc(8, 3).is_null());
+    assert_eq!(early_alloc_pages(1, PAGE_SIZE), start + 7 * PAGE_SIZE);
+    assert_eq!(ffi_allocator().lock().used_bytes(), 40 + PAGE_SIZE);
+    unsafe { early_free(p, 40, 8) };
+    assert_eq!(ffi_allocator().lock().used_bytes(), PAGE_SIZE);
+}
@@ -0,0 +1,95 @@
+//! The stretches of the pages areas holding byte blocks from
+//! `alloc_from_top`, so that they are never taken for page runs.
+
+use core::ops::Range;
+
+use crate::region::align_down;
+
+/// Maximum number of separate stretches of top byte blocks. Blocks taken
+/// one after the other share a stretch, so only page runs allocated in
+/// between start new ones.
+pub const TOP_SPAN_CAPACITY: usize = 16;
+
+/// The whole pages taken by top byte blocks, as `(start, end)` spans, each
+/// clipped to its region.
+pub(crate) struct TopSpans {
+    spans: [(usize, usize); TOP_SPAN_CAPACITY],
+    len: usize,
+}
+
+impl TopSpans {
+    pub const fn new() -> Self {
+        Self {
+            spans: [(0, 0); TOP_SPAN_CAPACITY],
+            len: 0,
+        }
+    }
+
+    /// Records `[start, end)` of the region `region`, merging it with the
+    /// spans of the region it touches. Returns `false` if it touches none
+    /// and the table is full.
+    pub fn insert(&mut self, region: Range<usize>, start: usize, end: usize) -> bool {
+        let mut start = start.max(region.start);
+        let mut end = end.min(region.end);
+        if start >= end {
+            return true;
+        }
+        let mut idx = 0;
+        while idx < self.len {
+            let (s, e) = self.spans[idx];
+            if region.start <= s && e <= region.end && s <= end && start <= e {
+                start = start.min(s);
+                end = end.max(e);
+                self.len -= 1;
+                self.spans[idx] = self.spans[self.len];
+            } else {
+                idx += 1;
+            }
+        }
+        if self.len == TOP_SPAN_CAPACITY {
+            return false;
+        }
+        self.spans[self.len] = (start, end);
+        self.len += 1;
+        true
+    }
+
+    /// Returns `true` if `[start, end)` overlaps a span.
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        self.iter().any(|(s, e)| s < end && start < e)
+    }
+
+    /// Number of bytes of the spans inside `range`.
+    pub fn bytes_in(&self, range: Range<usize>) -> usize {
+        self.iter()
+            .map(|(s, e)| e.min(range.end).saturating_sub(s.max(range.start)))
+            .sum()
+    }
+
+    /// Drops the parts of the spans of the region `region` below the page
+    /// holding `to`, once its pages area has been moved back up to `to`.
+    pub fn cut_below(&mut self, region: Range<usize>, to: usize, page_size: usize) {
+        let floor = if to < region.end {
+            align_down(to, page_size).max(region.start)
+        } else {
+            region.end
+        };
+        let mut idx = 0;
+        while idx < self.len {
+            let (s, e) = &mut self.spans[idx];
+            if region.start <= *s && *e <= region.end {
+                *s = (*s).max(floor);
+            }
+            if *s >= *e {
+                self.len -= 1;
+                self.spans[idx] = self.spans[self.len];
+            } else {
+                idx += 1;
+            }
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.spans[..self.len].iter().copied()
+    }
+}
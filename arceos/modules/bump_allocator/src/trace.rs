@@ -0,0 +1,87 @@
+//! Record and replay of allocation traces, enabled by the `trace` feature.
+
+/// The kind of a traced operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceOp {
+    /// [`ByteAllocator::alloc`](allocator::ByteAllocator::alloc).
+    Alloc,
+    /// [`ByteAllocator::dealloc`](allocator::ByteAllocator::dealloc).
+    Dealloc,
+    /// [`PageAllocator::alloc_pages`](allocator::PageAllocator::alloc_pages).
+    AllocPages,
+    /// [`PageAllocator::dealloc_pages`](allocator::PageAllocator::dealloc_pages).
+    DeallocPages,
+}
+
+/// One traced operation.
+///
+/// Addresses are recorded as offsets from the start of region 0, so a trace
+/// can be replayed against an allocator set up the same way at another base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// What was done.
+    pub op: TraceOp,
+    /// Size in bytes, or number of pages for page operations.
+    pub size: usize,
+    /// Requested alignment in bytes.
+    pub align: usize,
+    /// Offset of the granted or freed block, `None` if the allocation failed.
+    pub offset: Option<usize>,
+    /// When it was done, read from the clock set with
+    /// [`set_trace_clock`](crate::EarlyAllocator::set_trace_clock), or `0`
+    /// without one. Replaying ignores it.
+    pub timestamp: u64,
+}
+
+impl TraceEntry {
+    /// An empty entry, e.g. to initialize a trace buffer.
+    pub const EMPTY: Self = Self {
+        op: TraceOp::Alloc,
+        size: 0,
+        align: 0,
+        offset: None,
+        timestamp: 0,
+    };
+}
+
+/// Appends entries to the caller-supplied buffer until it is full.
+pub(crate) struct Tracer {
+    buf: &'static mut [TraceEntry],
+    len: usize,
+    dropped: usize,
+}
+
+impl Tracer {
+    pub fn new(buf: &'static mut [TraceEntry]) -> Self {
+        Self {
+            buf,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn push(&mut self, entry: TraceEntry) {
+        match self.buf.get_mut(self.len) {
+            Some(slot) => {
+                *slot = entry;
+                self.len += 1;
+            }
+            None => self.dropped += 1,
+        }
+    }
+
+    /// The entries recorded so far.
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.buf[..self.len]
+    }
+
+    /// Number of entries that did not fit in the buffer.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Gives the buffer back to the caller.
+    pub fn into_buf(self) -> &'static mut [TraceEntry] {
+        self.buf
+    }
+}
@@ -0,0 +1,99 @@
+//! Bookkeeping of live byte allocations, enabled by the `track` feature.
+
+use core::panic::Location;
+
+/// Maximum number of live byte allocations that can be tracked at once.
+pub const TRACK_CAPACITY: usize = 128;
+
+/// A live byte allocation recorded by the tracker.
+#[derive(Debug, Clone, Copy)]
+pub struct AllocRecord {
+    /// Start address of the block.
+    pub addr: usize,
+    /// Requested size of the block.
+    pub size: usize,
+    /// Requested alignment of the block.
+    pub align: usize,
+    /// Code location that requested the block.
+    pub caller: &'static Location<'static>,
+    /// The tag set with
+    /// [`set_alloc_tag`](crate::EarlyAllocator::set_alloc_tag) when the
+    /// block was allocated.
+    pub tag: Option<&'static str>,
+    /// Index of the allocation among all byte allocations since `init`,
+    /// untracked ones included, to reconstruct their order from a dump.
+    pub seq: u64,
+}
+
+pub(crate) struct Tracker {
+    records: [Option<AllocRecord>; TRACK_CAPACITY],
+    len: usize,
+    untracked: usize,
+    next_seq: u64,
+    pub tag: Option<&'static str>,
+}
+
+impl Tracker {
+    pub const fn new() -> Self {
+        Self {
+            records: [None; TRACK_CAPACITY],
+            len: 0,
+            untracked: 0,
+            next_seq: 0,
+            tag: None,
+        }
+    }
+
+    /// Hands out the index of the next allocation.
+    pub fn next_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    pub fn insert(&mut self, record: AllocRecord) {
+        if self.len < TRACK_CAPACITY {
+            self.records[self.len] = Some(record);
+            self.len += 1;
+        } else {
+            self.untracked += 1;
+        }
+    }
+
+    pub fn remove(&mut self, addr: usize) -> Option<AllocRecord> {
+        let idx = self.iter().position(|r| r.addr == addr)?;
+        let record = self.records[idx].take();
+        self.len -= 1;
+        self.records[idx] = self.records[self.len].take();
+        record
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AllocRecord> {
+        self.records[..self.len].iter().flatten()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut AllocRecord> {
+        self.records[..self.len].iter_mut().flatten()
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&AllocRecord) -> bool) {
+        let mut idx = 0;
+        while idx < self.len {
+            if self.records[idx].as_ref().is_some_and(&mut f) {
+                idx += 1;
+            } else {
+                self.len -= 1;
+                self.records[idx] = self.records[self.len].take();
+            }
+        }
+    }
+
+    pub fn sort_by_addr(&mut self) {
+        self.records[..self.len].sort_unstable_by_key(|r| r.map(|r| r.addr));
+    }
+
+    /// Number of allocations that did not fit in the table.
+    pub fn untracked(&self) -> usize {
+        self.untracked
+    }
+}
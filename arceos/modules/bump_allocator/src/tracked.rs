@@ -0,0 +1,45 @@
+//! Ownership records for handing early pages over to a frame tracker.
+
+use crate::EarlyAllocator;
+
+/// A run of frames in use in the pages area of a region, as reported by
+/// [`EarlyAllocator::tracked_frames`].
+///
+/// Adjacent allocations are merged into one record: the allocator does not
+/// remember where one run ends and the next begins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrackedFrame {
+    /// Physical address of the first frame, page-aligned.
+    pub paddr: usize,
+    /// Number of frames in the run.
+    pub pages: usize,
+    /// Index of the region the frames were allocated from.
+    pub source_region: usize,
+}
+
+impl<const PAGE_SIZE: usize> EarlyAllocator<PAGE_SIZE> {
+    /// Iterates over the frames in use in the pages areas, region by region
+    /// in address order, so a frame tracker set up later can adopt them
+    /// instead of handing them out again.
+    ///
+    /// Physical addresses are computed as `vaddr - phys_virt_offset`, the
+    /// linear mapping ArceOS sets up with `PHYS_VIRT_OFFSET`; pass `0` if
+    /// the allocator manages physical addresses. Byte allocations and the
+    /// free gap are not reported, except in the frame shared with the lowest
+    /// page run.
+    pub fn tracked_frames(
+        &self,
+        phys_virt_offset: usize,
+    ) -> impl Iterator<Item = TrackedFrame> + '_ {
+        self.sorted_indices().iter().flat_map(move |&idx| {
+            let idx = idx as usize;
+            self.regions[idx]
+                .used_runs(PAGE_SIZE)
+                .map(move |run| TrackedFrame {
+                    paddr: run.start - phys_virt_offset,
+                    pages: (run.end - run.start).div_ceil(PAGE_SIZE),
+                    source_region: idx,
+                })
+        })
+    }
+}